@@ -0,0 +1,720 @@
+//! Diffの計算と表示用の行への変換
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use git2::{DiffOptions, Oid, Repository};
+
+use crate::{DiffFileData, DiffLineData, MainWindow};
+
+/// 各行から大文字小文字を区別せずに検索語を探す（重ならない一致をすべて返す）
+/// 戻り値: (行番号, 開始位置, 長さ)。位置と長さは文字単位（等幅表示でのハイライト用）
+pub fn find_text_matches<'a>(
+    lines: impl IntoIterator<Item = &'a str>,
+    query: &str,
+) -> Vec<(usize, usize, usize)> {
+    let fold = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let query: Vec<char> = query.chars().map(fold).collect();
+    if query.is_empty() {
+        return vec![];
+    }
+    let mut matches = vec![];
+    for (line_idx, line) in lines.into_iter().enumerate() {
+        let chars: Vec<char> = line.chars().map(fold).collect();
+        let mut start = 0;
+        while start + query.len() <= chars.len() {
+            if chars[start..start + query.len()] == query[..] {
+                matches.push((line_idx, start, query.len()));
+                start += query.len();
+            } else {
+                start += 1;
+            }
+        }
+    }
+    matches
+}
+
+// ========== 別スレッドでのDiff計算 ==========
+
+/// コミット（または2コミット間）の変更統計
+#[derive(Clone, Copy, Default)]
+pub struct DiffStats {
+    files_changed: usize,
+    insertions: usize,
+    deletions: usize,
+}
+
+impl DiffStats {
+    fn from_diff(diff: &git2::Diff) -> Self {
+        diff.stats()
+            .map(|stats| DiffStats {
+                files_changed: stats.files_changed(),
+                insertions: stats.insertions(),
+                deletions: stats.deletions(),
+            })
+            .unwrap_or_default()
+    }
+
+    /// UIの統計表示用プロパティに反映する
+    pub fn apply_to(&self, ui: &MainWindow) {
+        ui.set_diff_files_changed(self.files_changed as i32);
+        ui.set_diff_insertions(self.insertions as i32);
+        ui.set_diff_deletions(self.deletions as i32);
+    }
+}
+
+pub type TreeDiff = (Vec<DiffFileData>, Vec<DiffLineData>, usize, DiffStats);
+
+/// 別スレッドでコミットのDiffファイル一覧とDiff内容を計算する
+pub fn compute_commit_diff_in_thread(
+    repo_path: String,
+    commit_hash: String,
+    context_lines: u32,
+) -> TreeDiff {
+    let Ok(repo) = Repository::open(&repo_path) else {
+        return Default::default();
+    };
+
+    if commit_hash.is_empty() {
+        return Default::default();
+    }
+
+    let Ok(commit) = repo.find_commit(Oid::from_str(&commit_hash).unwrap_or(Oid::zero())) else {
+        return Default::default();
+    };
+    let Ok(tree) = commit.tree() else {
+        return Default::default();
+    };
+
+    let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+
+    diff_trees(&repo, parent_tree.as_ref(), &tree, context_lines)
+}
+
+/// Diffキャッシュに保持するエントリ数
+pub const DIFF_CACHE_CAPACITY: usize = 16;
+
+/// Diffキャッシュのキー（file_indexがNoneならコミット全体、Someならそのファイルのみ）
+#[derive(Clone, PartialEq, Eq)]
+pub struct DiffCacheKey {
+    pub commit_hash: String,
+    pub file_index: Option<usize>,
+    pub context_lines: u32,
+}
+
+/// 最近計算したコミットDiffのLRUキャッシュ（コミットを行き来したときに再計算しない）
+/// ファイル単位のエントリではファイル一覧と統計は空
+pub struct DiffCache {
+    capacity: usize,
+    entries: VecDeque<(DiffCacheKey, TreeDiff)>, // 末尾ほど最近使われたもの
+}
+
+pub type SharedDiffCache = Arc<Mutex<DiffCache>>;
+
+impl DiffCache {
+    pub fn new(capacity: usize) -> Self {
+        DiffCache {
+            capacity,
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// キャッシュ済みのDiffを返し、そのエントリを最近使われたものにする
+    pub fn get(&mut self, key: &DiffCacheKey) -> Option<TreeDiff> {
+        let pos = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(pos)?;
+        let diff = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(diff)
+    }
+
+    /// Diffを追加し、容量を超えたら最も古く使われたものを捨てる
+    pub fn insert(&mut self, key: DiffCacheKey, diff: TreeDiff) {
+        self.entries.retain(|(k, _)| k != &key);
+        self.entries.push_back((key, diff));
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// ツリー間のDiffを作成し、リネーム・コピーを検出する
+fn diff_tree_with_renames<'a>(
+    repo: &'a Repository,
+    old_tree: Option<&git2::Tree>,
+    new_tree: &git2::Tree,
+    opts: &mut DiffOptions,
+) -> Result<git2::Diff<'a>, git2::Error> {
+    let mut diff = repo.diff_tree_to_tree(old_tree, Some(new_tree), Some(opts))?;
+    let mut find_opts = git2::DiffFindOptions::new();
+    find_opts.renames(true).copies(true);
+    diff.find_similar(Some(&mut find_opts))?;
+    Ok(diff)
+}
+
+/// 2つのツリー間のDiffファイル一覧（ファイルごとの+/-行数付き）、最初のファイルのDiff内容、変更統計を計算する
+pub fn diff_trees(
+    repo: &Repository,
+    old_tree: Option<&git2::Tree>,
+    new_tree: &git2::Tree,
+    context_lines: u32,
+) -> TreeDiff {
+    let mut opts = DiffOptions::new();
+    let Ok(diff) = diff_tree_with_renames(repo, old_tree, new_tree, &mut opts) else {
+        return Default::default();
+    };
+    let stats = DiffStats::from_diff(&diff);
+
+    // ファイル一覧を取得
+    let mut files = vec![];
+    for (idx, delta) in diff.deltas().enumerate() {
+        let status = match delta.status() {
+            git2::Delta::Added => "A",
+            git2::Delta::Deleted => "D",
+            git2::Delta::Modified => "M",
+            git2::Delta::Renamed => "R",
+            git2::Delta::Copied => "C",
+            git2::Delta::Typechange => "T",
+            git2::Delta::Conflicted => "U",
+            _ => "?",
+        };
+        let (path, old_path) = delta_paths(&delta);
+
+        // ファイルごとの追加・削除行数（バイナリは0）
+        let (additions, deletions) = git2::Patch::from_diff(&diff, idx)
+            .ok()
+            .flatten()
+            .and_then(|patch| patch.line_stats().ok())
+            .map(|(_, additions, deletions)| (additions, deletions))
+            .unwrap_or((0, 0));
+
+        files.push(DiffFileData {
+            filename: path.into(),
+            old_filename: old_path.unwrap_or_default().into(),
+            status: status.into(),
+            additions: additions as i32,
+            deletions: deletions as i32,
+        });
+    }
+
+    // 最初のファイルのDiff内容を取得
+    let (diff_lines, total_count) = match diff.deltas().next() {
+        Some(delta) => diff_single_file(repo, old_tree, new_tree, &delta, context_lines),
+        None => (vec![], 0),
+    };
+
+    (files, diff_lines, total_count, stats)
+}
+
+/// 2つのツリー間のDiffのうち、file_index番目のファイルのDiff内容を計算する
+pub fn diff_trees_file(
+    repo: &Repository,
+    old_tree: Option<&git2::Tree>,
+    new_tree: &git2::Tree,
+    file_index: usize,
+    context_lines: u32,
+) -> (Vec<DiffLineData>, usize) {
+    let mut opts = DiffOptions::new();
+    let Ok(diff) = diff_tree_with_renames(repo, old_tree, new_tree, &mut opts) else {
+        return (vec![], 0);
+    };
+
+    match diff.deltas().nth(file_index) {
+        Some(delta) => diff_single_file(repo, old_tree, new_tree, &delta, context_lines),
+        None => (vec![], 0),
+    }
+}
+
+/// Deltaの表示用パスと、リネーム・コピー時の元パスを取得
+fn delta_paths(delta: &git2::DiffDelta) -> (String, Option<String>) {
+    let path_of = |file: git2::DiffFile| file.path().map(|p| p.to_string_lossy().to_string());
+    let new_path = path_of(delta.new_file());
+    let old_path = path_of(delta.old_file());
+    let path = new_path
+        .clone()
+        .or_else(|| old_path.clone())
+        .unwrap_or_default();
+    let old_path = match delta.status() {
+        git2::Delta::Renamed | git2::Delta::Copied => old_path.filter(|p| *p != path),
+        _ => None,
+    };
+    (path, old_path)
+}
+
+/// 1ファイル分のDiff内容を計算する（リネームは元パスも含めて比較）
+fn diff_single_file(
+    repo: &Repository,
+    old_tree: Option<&git2::Tree>,
+    new_tree: &git2::Tree,
+    delta: &git2::DiffDelta,
+    context_lines: u32,
+) -> (Vec<DiffLineData>, usize) {
+    let (path, old_path) = delta_paths(delta);
+    let mut opts = DiffOptions::new();
+    opts.pathspec(&path);
+    // コピー元は別のDeltaとして残るため、元パスを含めるのはリネームのみ
+    if let (git2::Delta::Renamed, Some(old_path)) = (delta.status(), &old_path) {
+        opts.pathspec(old_path);
+    }
+    opts.context_lines(context_lines);
+
+    let Ok(diff) = diff_tree_with_renames(repo, old_tree, new_tree, &mut opts) else {
+        return (vec![], 0);
+    };
+
+    parse_diff_standalone(&diff)
+}
+
+/// 2コミット比較時のDiff欄タイトル（比較中でなければ空）
+pub fn compare_title(from: &str, to: &str) -> String {
+    if from.is_empty() {
+        return String::new();
+    }
+    format!(
+        "Compare {}..{}",
+        &from[..7.min(from.len())],
+        &to[..7.min(to.len())]
+    )
+}
+
+/// Diff行数の上限（パフォーマンス対策）
+const MAX_DIFF_LINES: usize = 200;
+/// カウント上限（これ以上は計算しない）
+const MAX_COUNT_LINES: usize = 100000;
+
+/// 行末に空白（スペース・タブ）があるか
+pub fn has_trailing_whitespace(text: &str) -> bool {
+    text.ends_with([' ', '\t'])
+}
+
+/// ファイルモードの変更（実行ビットの付け外しなど）を表す行
+/// 内容が同じでモードだけ変わったファイルはhunkがないので、これがないとdiffが空に見える
+fn mode_change_line(delta: &git2::DiffDelta) -> Option<DiffLineData> {
+    let old_mode = u32::from(delta.old_file().mode());
+    let new_mode = u32::from(delta.new_file().mode());
+    if delta.status() != git2::Delta::Modified || old_mode == new_mode {
+        return None;
+    }
+    Some(DiffLineData {
+        content: format!("old mode {:o} / new mode {:o}", old_mode, new_mode).into(),
+        line_type: "@@".into(),
+        old_line_num: 0,
+        new_line_num: 0,
+        hunk_index: -1,
+        trailing_ws: false,
+        has_tab: false,
+    })
+}
+
+/// Diffをパースするスタンドアロン関数
+pub fn parse_diff_standalone(diff: &git2::Diff) -> (Vec<DiffLineData>, usize) {
+    use std::cell::Cell;
+    let lines = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+    let current_hunk_index = Cell::new(-1i32);
+    let truncated = Cell::new(false);
+    let total_lines = Cell::new(0usize);
+    let stop_processing = Cell::new(false);
+    let non_utf8 = Cell::new(false);
+
+    let lines_clone = lines.clone();
+    let _ = diff.print(git2::DiffFormat::Patch, |delta, _hunk, line| {
+        if stop_processing.get() {
+            return false;
+        }
+
+        // カウント上限チェック
+        if total_lines.get() >= MAX_COUNT_LINES {
+            stop_processing.set(true);
+            return false;
+        }
+        total_lines.set(total_lines.get() + 1);
+
+        // 表示上限チェック
+        if lines_clone.borrow().len() >= MAX_DIFF_LINES {
+            truncated.set(true);
+            return true; // カウントのために継続
+        }
+
+        let line_type = match line.origin() {
+            '+' => "+",
+            '-' => "-",
+            ' ' => " ",
+            'H' | 'F' => "@@",
+            _ => "",
+        };
+
+        if line.origin() == 'H' {
+            current_hunk_index.set(current_hunk_index.get() + 1);
+        }
+
+        let old_line_num = line.old_lineno().map(|n| n as i32).unwrap_or(0);
+        let new_line_num = line.new_lineno().map(|n| n as i32).unwrap_or(0);
+
+        if line.origin() == 'F' {
+            if let Some(path) = delta.new_file().path() {
+                lines_clone.borrow_mut().push(DiffLineData {
+                    content: format!("--- {}", path.display()).into(),
+                    line_type: "diff".into(),
+                    old_line_num: 0,
+                    new_line_num: 0,
+                    hunk_index: -1,
+                    trailing_ws: false,
+                    has_tab: false,
+                });
+            }
+            if let Some(mode_line) = mode_change_line(&delta) {
+                lines_clone.borrow_mut().push(mode_line);
+            }
+        } else {
+            // UTF-8以外（レガシーエンコーディング）の行も捨てずに置換文字で表示する
+            let content = String::from_utf8_lossy(line.content());
+            if matches!(content, std::borrow::Cow::Owned(_)) {
+                non_utf8.set(true);
+            }
+            // CRLFの行末の\rは表示しない
+            let text = content.trim_end_matches('\n').trim_end_matches('\r');
+            if !text.is_empty() || line_type == " " {
+                lines_clone.borrow_mut().push(DiffLineData {
+                    content: text.into(),
+                    line_type: line_type.into(),
+                    old_line_num,
+                    new_line_num,
+                    hunk_index: current_hunk_index.get(),
+                    trailing_ws: has_trailing_whitespace(text),
+                    has_tab: text.contains('\t'),
+                });
+            }
+        }
+        true
+    });
+
+    let mut result = lines.borrow_mut().clone();
+
+    // 置換文字を含むことを先頭で知らせる
+    if non_utf8.get() {
+        result.insert(
+            0,
+            DiffLineData {
+                content: "(non-UTF8, shown lossily)".into(),
+                line_type: "@@".into(),
+                old_line_num: 0,
+                new_line_num: 0,
+                hunk_index: -1,
+                trailing_ws: false,
+                has_tab: false,
+            },
+        );
+    }
+
+    // 切り捨てメッセージを追加
+    if truncated.get() {
+        result.push(DiffLineData {
+            content: format!(
+                "... (truncated: diff exceeds {} lines, view on GitHub for full diff)",
+                MAX_DIFF_LINES
+            )
+            .into(),
+            line_type: "@@".into(),
+            old_line_num: 0,
+            new_line_num: 0,
+            hunk_index: -1,
+            trailing_ws: false,
+            has_tab: false,
+        });
+    }
+
+    (result, total_lines.get())
+}
+
+/// バイナリファイル用のDiff表示プレースホルダー
+pub fn binary_file_placeholder() -> DiffLineData {
+    DiffLineData {
+        content: "(Binary file)".into(),
+        line_type: " ".into(),
+        old_line_num: 0,
+        new_line_num: 0,
+        hunk_index: 0,
+        trailing_ws: false,
+        has_tab: false,
+    }
+}
+
+/// 一部だけステージしたファイルのStaged / Unstagedの差分を区切り行を挟んで続ける
+/// Unstaged側のhunk番号はStaged側の続きにずらし、折りたたみが混ざらないようにする
+pub fn combine_staged_and_unstaged(
+    staged: Vec<DiffLineData>,
+    unstaged: Vec<DiffLineData>,
+) -> Vec<DiffLineData> {
+    let section = |title: &str| DiffLineData {
+        content: format!("══ {} ══", title).into(),
+        line_type: "diff".into(),
+        old_line_num: 0,
+        new_line_num: 0,
+        hunk_index: -1,
+        trailing_ws: false,
+        has_tab: false,
+    };
+    let hunk_offset = staged
+        .iter()
+        .map(|line| line.hunk_index + 1)
+        .max()
+        .unwrap_or(0)
+        .max(0);
+    let mut lines = Vec::with_capacity(staged.len() + unstaged.len() + 2);
+    lines.push(section("Staged (HEAD → index)"));
+    lines.extend(staged);
+    lines.push(section("Unstaged (index → working tree)"));
+    lines.extend(unstaged.into_iter().map(|mut line| {
+        if line.hunk_index >= 0 {
+            line.hunk_index += hunk_offset;
+        }
+        line
+    }));
+    lines
+}
+
+/// ファイル内容を行番号付きのDiff行として表示用に変換
+pub fn file_content_to_lines(content: &str) -> Vec<DiffLineData> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| DiffLineData {
+            content: line.into(),
+            line_type: " ".into(),
+            old_line_num: 0,
+            new_line_num: (i + 1) as i32,
+            hunk_index: -1,
+            trailing_ws: has_trailing_whitespace(line),
+            has_tab: line.contains('\t'),
+        })
+        .collect()
+}
+
+/// 折りたたんだhunkの行を除いたDiff行を返す（@@ヘッダー行は常に残す）
+pub fn filter_collapsed_hunks(
+    lines: impl IntoIterator<Item = DiffLineData>,
+    collapsed: &std::collections::HashSet<i32>,
+) -> Vec<DiffLineData> {
+    lines
+        .into_iter()
+        .filter(|line| line.line_type == "@@" || !collapsed.contains(&line.hunk_index))
+        .collect()
+}
+
+/// Diff表示のrow行目が属するファイルのパスを、直前のファイルヘッダー（"--- path" とそれに続く "+++ path"）から求める
+/// ヘッダー行自身はそのヘッダーのファイルに属する。新規ファイル（"--- /dev/null"）は "+++" 側のパスを使う
+pub fn diff_path_at(lines: &[DiffLineData], row: usize) -> Option<String> {
+    let row = row.min(lines.len().checked_sub(1)?);
+    let header_path = |line: &DiffLineData, prefix: &str| {
+        if line.line_type != "diff" {
+            return None;
+        }
+        let path = line.content.strip_prefix(prefix)?;
+        (path != "/dev/null").then(|| path.to_string())
+    };
+    let start = (0..=row)
+        .rev()
+        .find(|&i| lines[i].line_type == "diff" && lines[i].content.starts_with("--- "))?;
+    lines
+        .get(start + 1)
+        .and_then(|line| header_path(line, "+++ "))
+        .or_else(|| header_path(&lines[start], "--- "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::init_test_repo;
+
+    #[test]
+    fn text_matches_are_case_insensitive_and_non_overlapping() {
+        let lines = ["Foo foo", "bar", "aaaa", "ÄBC äbc"];
+        assert_eq!(
+            find_text_matches(lines.iter().copied(), "FOO"),
+            vec![(0, 0, 3), (0, 4, 3)]
+        );
+        assert_eq!(
+            find_text_matches(lines.iter().copied(), "aa"),
+            vec![(2, 0, 2), (2, 2, 2)]
+        );
+        // 位置はバイトではなく文字単位
+        assert_eq!(
+            find_text_matches(lines.iter().copied(), "bc"),
+            vec![(3, 1, 2), (3, 5, 2)]
+        );
+        assert!(find_text_matches(lines.iter().copied(), "").is_empty());
+    }
+
+    #[test]
+    fn whitespace_flags_are_attached_to_diff_lines() {
+        assert!(has_trailing_whitespace("let x = 1; "));
+        assert!(has_trailing_whitespace("value\t"));
+        assert!(!has_trailing_whitespace("value"));
+        assert!(!has_trailing_whitespace(""));
+
+        let lines = file_content_to_lines("\tindented\nplain  \nclean");
+        let flags: Vec<(bool, bool)> = lines.iter().map(|l| (l.trailing_ws, l.has_tab)).collect();
+        assert_eq!(flags, vec![(false, true), (true, false), (false, false)]);
+    }
+
+    fn diff_line(line_type: &str, hunk_index: i32) -> DiffLineData {
+        DiffLineData {
+            content: "".into(),
+            line_type: line_type.into(),
+            old_line_num: 0,
+            new_line_num: 0,
+            hunk_index,
+            trailing_ws: false,
+            has_tab: false,
+        }
+    }
+
+    #[test]
+    fn diff_cache_evicts_least_recently_used() {
+        let key = |hash: &str, file_index| DiffCacheKey {
+            commit_hash: hash.to_string(),
+            file_index,
+            context_lines: 3,
+        };
+        let diff = |total| (vec![], vec![], total, DiffStats::default());
+        let mut cache = DiffCache::new(2);
+        cache.insert(key("a", None), diff(1));
+        cache.insert(key("b", None), diff(2));
+        // aを参照したので次に追い出されるのはb
+        assert_eq!(cache.get(&key("a", None)).map(|d| d.2), Some(1));
+        cache.insert(key("a", Some(0)), diff(3));
+        assert!(cache.get(&key("b", None)).is_none());
+        assert_eq!(cache.get(&key("a", None)).map(|d| d.2), Some(1));
+        assert_eq!(cache.get(&key("a", Some(0))).map(|d| d.2), Some(3));
+        // コンテキスト行数が違えば別エントリ
+        let mut wider = key("a", None);
+        wider.context_lines = 10;
+        assert!(cache.get(&wider).is_none());
+
+        cache.clear();
+        assert!(cache.get(&key("a", None)).is_none());
+    }
+
+    #[test]
+    fn combined_diff_separates_sections_and_renumbers_hunks() {
+        let staged = vec![diff_line("diff", -1), diff_line("@@", 0), diff_line("+", 0)];
+        let unstaged = vec![
+            diff_line("diff", -1),
+            diff_line("@@", 0),
+            diff_line("-", 0),
+            diff_line("@@", 1),
+        ];
+        let lines = combine_staged_and_unstaged(staged, unstaged);
+        assert_eq!(lines.len(), 9);
+        assert!(lines[0].content.contains("Staged"));
+        assert!(lines[4].content.contains("Unstaged"));
+        let hunks: Vec<i32> = lines.iter().map(|l| l.hunk_index).collect();
+        assert_eq!(hunks, vec![-1, -1, 0, 0, -1, -1, 1, 1, 2]);
+        // 区切り行はファイルパスとして扱わない
+        assert_eq!(diff_path_at(&lines, 8), None);
+    }
+
+    #[test]
+    fn collapsed_hunks_keep_only_their_headers() {
+        let lines = vec![
+            diff_line("diff", -1),
+            diff_line("@@", 0),
+            diff_line("-", 0),
+            diff_line("+", 0),
+            diff_line("@@", 1),
+            diff_line(" ", 1),
+        ];
+        let collapsed = std::collections::HashSet::from([0]);
+        let shown: Vec<(String, i32)> = filter_collapsed_hunks(lines, &collapsed)
+            .into_iter()
+            .map(|l| (l.line_type.to_string(), l.hunk_index))
+            .collect();
+        assert_eq!(
+            shown,
+            vec![
+                ("diff".to_string(), -1),
+                ("@@".to_string(), 0),
+                ("@@".to_string(), 1),
+                (" ".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_path_comes_from_nearest_file_header() {
+        let mut lines = vec![
+            diff_line("diff", -1),
+            diff_line("@@", 0),
+            diff_line("+", 0),
+            diff_line("diff", -1),
+            diff_line("diff", -1),
+            diff_line("+", 1),
+        ];
+        lines[0].content = "--- src/a.rs".into();
+        lines[3].content = "--- /dev/null".into();
+        lines[4].content = "+++ src/new.rs".into();
+        assert_eq!(diff_path_at(&lines, 2).as_deref(), Some("src/a.rs"));
+        assert_eq!(diff_path_at(&lines, 5).as_deref(), Some("src/new.rs"));
+        // ヘッダー行は次のファイルのもの
+        assert_eq!(diff_path_at(&lines, 3).as_deref(), Some("src/new.rs"));
+        assert_eq!(diff_path_at(&lines, 4).as_deref(), Some("src/new.rs"));
+        assert_eq!(diff_path_at(&[], 0), None);
+    }
+
+    #[test]
+    fn non_utf8_and_crlf_lines_are_displayed() {
+        let (_dir, repo, _) = init_test_repo("encoding");
+        let tree_with = |content: &[u8]| {
+            let blob = repo.blob(content).unwrap();
+            let mut builder = repo.treebuilder(None).unwrap();
+            builder.insert("legacy.txt", blob, 0o100644).unwrap();
+            repo.find_tree(builder.write().unwrap()).unwrap()
+        };
+        let old_tree = tree_with(b"same\r\nold\r\n");
+        // Shift_JISの「日本」
+        let new_tree = tree_with(b"same\r\n\x93\xfa\x96\x7b\r\n");
+        let diff = repo
+            .diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)
+            .unwrap();
+
+        let (lines, _) = parse_diff_standalone(&diff);
+        assert_eq!(lines[0].content, "(non-UTF8, shown lossily)");
+        let body: Vec<(String, String)> = lines
+            .iter()
+            .filter(|l| matches!(l.line_type.as_str(), "+" | "-" | " "))
+            .map(|l| (l.line_type.to_string(), l.content.to_string()))
+            .collect();
+        assert_eq!(body.len(), 3);
+        assert_eq!(body[0], (" ".to_string(), "same".to_string()));
+        assert_eq!(body[1], ("-".to_string(), "old".to_string()));
+        assert_eq!(body[2].0, "+");
+        assert!(body[2].1.contains('\u{FFFD}') && !body[2].1.ends_with('\r'));
+    }
+
+    #[test]
+    fn executable_bit_change_is_shown_in_diff() {
+        let (_dir, repo, _) = init_test_repo("mode-change");
+        let blob = repo.blob(b"#!/bin/sh\n").unwrap();
+        let tree_with_mode = |mode: i32| {
+            let mut builder = repo.treebuilder(None).unwrap();
+            builder.insert("run.sh", blob, mode).unwrap();
+            repo.find_tree(builder.write().unwrap()).unwrap()
+        };
+        let old_tree = tree_with_mode(0o100644);
+        let new_tree = tree_with_mode(0o100755);
+        let diff = repo
+            .diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)
+            .unwrap();
+
+        let (lines, _) = parse_diff_standalone(&diff);
+        let contents: Vec<String> = lines.iter().map(|l| l.content.to_string()).collect();
+        assert_eq!(
+            contents,
+            vec!["--- run.sh", "old mode 100644 / new mode 100755"]
+        );
+    }
+}
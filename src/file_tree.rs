@@ -0,0 +1,164 @@
+//! Staged / Unstagedのファイル一覧をディレクトリツリーで表示するための変換
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::{FileData, FileTreeRow};
+
+/// ファイル一覧から組み立てたディレクトリツリーの1階層
+#[derive(Debug, Default, PartialEq)]
+pub struct DirNode {
+    pub dirs: BTreeMap<String, DirNode>,
+    /// (表示名, 元の一覧でのインデックス)
+    pub files: Vec<(String, usize)>,
+}
+
+impl DirNode {
+    /// 配下（サブディレクトリを含む）のファイル数
+    fn file_count(&self) -> usize {
+        self.files.len() + self.dirs.values().map(DirNode::file_count).sum::<usize>()
+    }
+}
+
+/// フラットなファイル一覧をパスの区切りでディレクトリツリーにする
+/// まとめた未追跡ディレクトリ（"dir/"）はその1行をファイルとして扱い、展開中の子の行は含めない
+pub fn build_file_tree(files: &[FileData]) -> DirNode {
+    let mut root = DirNode::default();
+    for (index, file) in files.iter().enumerate() {
+        if file.in_group {
+            continue;
+        }
+        let is_group = file.filename.ends_with('/');
+        let mut parts: Vec<&str> = file
+            .filename
+            .trim_end_matches('/')
+            .split('/')
+            .filter(|p| !p.is_empty())
+            .collect();
+        let Some(name) = parts.pop() else {
+            continue;
+        };
+        let mut node = &mut root;
+        for part in parts {
+            node = node.dirs.entry(part.to_string()).or_default();
+        }
+        let name = if is_group {
+            format!("{}/", name)
+        } else {
+            name.to_string()
+        };
+        node.files.push((name, index));
+    }
+    root
+}
+
+/// ツリーを表示用の行に展開する（ディレクトリを先、ファイルを後に名前順で並べる）
+/// collapsedに含まれるディレクトリ（"src/ui" の形式）は配下の行を出さない
+pub fn file_tree_rows(root: &DirNode, collapsed: &HashSet<String>) -> Vec<FileTreeRow> {
+    let mut rows = vec![];
+    push_rows(root, "", 0, collapsed, &mut rows);
+    rows
+}
+
+fn push_rows(
+    node: &DirNode,
+    prefix: &str,
+    depth: i32,
+    collapsed: &HashSet<String>,
+    rows: &mut Vec<FileTreeRow>,
+) {
+    for (name, dir) in &node.dirs {
+        let path = format!("{}{}", prefix, name);
+        let expanded = !collapsed.contains(&path);
+        rows.push(FileTreeRow {
+            name: name.as_str().into(),
+            path: path.as_str().into(),
+            depth,
+            is_dir: true,
+            expanded,
+            file_index: -1,
+            file_count: dir.file_count() as i32,
+        });
+        if expanded {
+            push_rows(dir, &format!("{}/", path), depth + 1, collapsed, rows);
+        }
+    }
+    let mut files: Vec<&(String, usize)> = node.files.iter().collect();
+    files.sort();
+    for (name, index) in files {
+        rows.push(FileTreeRow {
+            name: name.as_str().into(),
+            path: format!("{}{}", prefix, name).into(),
+            depth,
+            is_dir: false,
+            expanded: false,
+            file_index: *index as i32,
+            file_count: 0,
+        });
+    }
+}
+
+/// フォルダ（"src/ui" の形式）配下のファイル名を一覧の順に返す
+pub fn files_under_folder(files: &[FileData], folder: &str) -> Vec<String> {
+    let prefix = format!("{}/", folder.trim_end_matches('/'));
+    files
+        .iter()
+        .filter(|f| !f.in_group && f.filename.starts_with(prefix.as_str()))
+        .map(|f| f.filename.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str) -> FileData {
+        FileData {
+            filename: name.into(),
+            status: "M".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tree_lists_directories_before_files_and_honours_collapsed() {
+        let files = vec![
+            file("README.md"),
+            file("src/main.rs"),
+            file("src/ui/view.rs"),
+            file("assets/"),
+        ];
+        let tree = build_file_tree(&files);
+        let rows = file_tree_rows(&tree, &HashSet::new());
+        let names: Vec<(i32, &str, i32)> = rows
+            .iter()
+            .map(|r| (r.depth, r.name.as_str(), r.file_index))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (0, "src", -1),
+                (1, "ui", -1),
+                (2, "view.rs", 2),
+                (1, "main.rs", 1),
+                (0, "README.md", 0),
+                (0, "assets/", 3),
+            ]
+        );
+        assert_eq!(rows[0].file_count, 2);
+        assert_eq!(rows[2].path.as_str(), "src/ui/view.rs");
+
+        let collapsed: HashSet<String> = ["src/ui".to_string()].into_iter().collect();
+        let rows = file_tree_rows(&tree, &collapsed);
+        assert_eq!(rows.len(), 5);
+        assert!(!rows[1].expanded);
+    }
+
+    #[test]
+    fn folder_files_match_whole_path_components() {
+        let files = vec![file("src/a.rs"), file("src2/b.rs"), file("src/x/c.rs")];
+        assert_eq!(
+            files_under_folder(&files, "src"),
+            vec!["src/a.rs".to_string(), "src/x/c.rs".to_string()]
+        );
+    }
+}
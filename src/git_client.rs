@@ -0,0 +1,4103 @@
+//! Gitリポジトリの操作（git2とgit CLI）
+
+use chrono::{DateTime, Local, TimeZone};
+use git2::{BranchType, DiffOptions, Oid, Repository, Signature, StatusOptions};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::diff::{
+    binary_file_placeholder, combine_staged_and_unstaged, diff_trees, diff_trees_file,
+    has_trailing_whitespace, parse_diff_standalone, TreeDiff,
+};
+use crate::graph::{
+    author_email, mapped_author_name, uncommitted_message, CommitRow, GraphCacheKey, RefKind,
+    SharedGraphCache, GRAPH_AUTHOR_SCAN_LIMIT,
+};
+use crate::remote::{self, RemoteTransport};
+use crate::{
+    CommitData, DiffLineData, FileData, LocalBranchData, MergeLineData, ReflogEntry,
+    RemoteBranchData, RepoStats, StashData, WorktreeData,
+};
+
+// Windowsでコンソールウィンドウを非表示にしてgitコマンドを作成するヘルパー
+#[cfg(target_os = "windows")]
+pub fn create_git_command() -> std::process::Command {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    let mut cmd = std::process::Command::new("git");
+    cmd.creation_flags(CREATE_NO_WINDOW);
+    cmd
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn create_git_command() -> std::process::Command {
+    std::process::Command::new("git")
+}
+
+/// gitコマンドがPATHにあって実行できるか（起動時に一度だけ確認する）
+pub fn git_cli_available() -> bool {
+    create_git_command()
+        .arg("--version")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// git CLIのstderrが認証失敗を示しているか判定
+pub fn is_auth_failure(stderr: &str) -> bool {
+    const AUTH_FAILURE_PATTERNS: [&str; 7] = [
+        "authentication failed",
+        "could not read username",
+        "could not read password",
+        "terminal prompts disabled",
+        "invalid username or password",
+        "permission denied (publickey",
+        "http basic: access denied",
+    ];
+    let lower = stderr.to_lowercase();
+    AUTH_FAILURE_PATTERNS.iter().any(|p| lower.contains(p))
+}
+
+/// リモート操作（push/pull/fetch/clone）のエラーを生成
+/// 認証失敗は他のエラーと区別する
+pub fn remote_command_error(action: &str, stderr: &str) -> GitError {
+    if is_auth_failure(stderr) {
+        GitError::AuthFailed(action.to_string())
+    } else {
+        GitError::Other(format!("{} failed: {}", action, stderr.trim()))
+    }
+}
+
+/// git pushのstderrがnon-fast-forwardによる拒否を示しているか判定
+fn is_push_rejected(stderr: &str) -> bool {
+    let lower = stderr.to_lowercase();
+    lower.contains("non-fast-forward")
+        || (lower.contains("[rejected]")
+            && (lower.contains("fetch first") || lower.contains("behind")))
+}
+
+/// gitの進捗出力行（例: "Receiving objects:  45% (450/1000)"）からフェーズ名と割合を抽出
+fn parse_git_progress(line: &str) -> Option<(String, i32)> {
+    let line = line.trim();
+    let line = line.strip_prefix("remote:").unwrap_or(line).trim();
+    let (phase, rest) = line.split_once(':')?;
+    let (percent, _) = rest.split_once('%')?;
+    let percent = percent.trim().parse::<i32>().ok()?;
+    Some((phase.trim().to_string(), percent.clamp(0, 100)))
+}
+
+/// git bisectの進行状況
+#[derive(Debug, PartialEq)]
+pub enum BisectProgress {
+    /// good/badがまだ揃っていない（gitのstatus行）
+    Waiting(String),
+    /// 次にテストするコミットをチェックアウト済み（残りステップ数はgitの概算）
+    Testing { steps: u32 },
+    /// 最初の悪いコミットが特定された
+    Found(String),
+}
+
+impl BisectProgress {
+    pub fn describe(&self) -> String {
+        match self {
+            BisectProgress::Waiting(status) => status.clone(),
+            BisectProgress::Testing { steps } => format!("roughly {} steps left", steps),
+            BisectProgress::Found(hash) => {
+                format!("first bad commit is {}", &hash[..7.min(hash.len())])
+            }
+        }
+    }
+}
+
+/// git bisect good/badの出力から進行状況を解析
+/// 例: "Bisecting: 3 revisions left to test after this (roughly 2 steps)"
+fn parse_bisect_output(output: &str) -> BisectProgress {
+    for line in output.lines() {
+        let line = line.trim();
+        if let Some(hash) = line.strip_suffix(" is the first bad commit") {
+            return BisectProgress::Found(hash.trim().to_string());
+        }
+        if line.starts_with("Bisecting:") {
+            let steps = line
+                .split_once("(roughly ")
+                .and_then(|(_, rest)| rest.split_whitespace().next())
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(0);
+            return BisectProgress::Testing { steps };
+        }
+        if let Some(status) = line.strip_prefix("status: ") {
+            return BisectProgress::Waiting(status.to_string());
+        }
+    }
+    BisectProgress::Waiting(String::new())
+}
+
+/// 実行中のgit子プロセスと、ユーザーがキャンセルしたかどうか（別スレッドからkillできるように共有する）
+#[derive(Default)]
+pub struct ChildSlot {
+    child: Mutex<Option<std::process::Child>>,
+    canceled: AtomicBool,
+}
+
+impl ChildSlot {
+    /// キャンセルを記録し、実行中のプロセスがあればkillする（終了の回収は実行側で行う）
+    pub fn cancel(&self) {
+        self.canceled.store(true, Ordering::SeqCst);
+        if let Ok(mut child) = self.child.lock() {
+            if let Some(child) = child.as_mut() {
+                let _ = child.kill();
+            }
+        }
+    }
+
+    /// 次の実行に備えてキャンセルの記録を消す
+    pub fn reset(&self) {
+        self.canceled.store(false, Ordering::SeqCst);
+    }
+}
+
+/// gitコマンドを実行し、stderrの進捗出力（\r区切り）を逐次コールバックに渡す
+/// child_slotを渡すと実行中のプロセスがそこに格納され、外部からキャンセルできる
+/// キャンセルされて失敗した場合は ErrorKind::Interrupted を返す
+/// 戻り値: (成功したか, 進捗行以外のstderr)
+pub fn run_git_with_progress(
+    mut cmd: std::process::Command,
+    child_slot: Option<&ChildSlot>,
+    mut on_progress: impl FnMut(&str, i32),
+) -> std::io::Result<(bool, String)> {
+    let mut child = cmd
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+    let stderr = child.stderr.take();
+    let local_slot = ChildSlot::default();
+    let slot = child_slot.unwrap_or(&local_slot);
+    if let Ok(mut guard) = slot.child.lock() {
+        *guard = Some(child);
+    }
+    // 起動する前にキャンセルされていた場合
+    if slot.canceled.load(Ordering::SeqCst) {
+        slot.cancel();
+    }
+
+    let mut other_output = String::new();
+    let read_result = match stderr {
+        Some(stderr) => read_git_progress(stderr, &mut other_output, &mut on_progress),
+        None => Ok(()),
+    };
+    let child = slot.child.lock().ok().and_then(|mut guard| guard.take());
+    let Some(mut child) = child else {
+        return Err(std::io::Error::other("Lost the git process"));
+    };
+    // 読み取りに失敗してもゾンビを残さないよう、終了させて回収する
+    if let Err(e) = read_result {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(e);
+    }
+
+    let status = child.wait()?;
+    if !status.success() && slot.canceled.load(Ordering::SeqCst) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Interrupted,
+            "Canceled",
+        ));
+    }
+    Ok((status.success(), other_output))
+}
+
+/// gitのstderrを最後まで読み、進捗行はon_progressに、それ以外はother_outputに振り分ける
+fn read_git_progress(
+    mut stderr: impl std::io::Read,
+    other_output: &mut String,
+    on_progress: &mut impl FnMut(&str, i32),
+) -> std::io::Result<()> {
+    let mut buf = [0u8; 4096];
+    let mut line: Vec<u8> = Vec::new();
+    let mut last_progress: Option<(String, i32)> = None;
+    let mut handle_line = |line: &[u8], other_output: &mut String| {
+        let text = String::from_utf8_lossy(line).to_string();
+        if let Some(progress) = parse_git_progress(&text) {
+            // 同じ値の連続通知は間引く
+            if last_progress.as_ref() != Some(&progress) {
+                on_progress(&progress.0, progress.1);
+                last_progress = Some(progress);
+            }
+        } else if !text.trim().is_empty() {
+            other_output.push_str(&text);
+            other_output.push('\n');
+        }
+    };
+    loop {
+        let n = stderr.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        for &b in &buf[..n] {
+            if b == b'\r' || b == b'\n' {
+                handle_line(&line, other_output);
+                line.clear();
+            } else {
+                line.push(b);
+            }
+        }
+    }
+    if !line.is_empty() {
+        handle_line(&line, other_output);
+    }
+    Ok(())
+}
+
+/// 全リモートをfetch（手動Refreshと定期fetchで共用、別スレッドから呼ぶ）
+pub fn fetch_all(
+    path: &str,
+    transport: RemoteTransport,
+    on_progress: impl FnMut(&str, i32),
+) -> Result<(), GitError> {
+    if transport == RemoteTransport::Embedded {
+        return remote::fetch_all(path, on_progress);
+    }
+    let mut cmd = create_git_command();
+    cmd.args(["fetch", "--all", "--progress"])
+        .current_dir(path)
+        .env("GIT_TERMINAL_PROMPT", "0");
+    match run_git_with_progress(cmd, None, on_progress) {
+        Ok((true, _)) => Ok(()),
+        Ok((false, stderr)) => Err(remote_command_error("Fetch", &stderr)),
+        Err(e) => Err(format!("Fetch error: {}", e).into()),
+    }
+}
+
+/// ブランチをoriginにプッシュ（別スレッドから呼ぶ）
+/// upstreamがないブランチでも自動的にupstreamを設定する
+pub fn push_branch(path: &str, branch: &str, transport: RemoteTransport) -> Result<(), GitError> {
+    if transport == RemoteTransport::Embedded {
+        return remote::push_branch(path, branch);
+    }
+    // GUIからは端末プロンプトに応答できないため無効化（認証失敗として即座に返す）
+    let output = create_git_command()
+        .args(["push", "-u", "origin", branch])
+        .current_dir(path)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !is_auth_failure(&stderr) && is_push_rejected(&stderr) {
+            return Err(GitError::PushRejected);
+        }
+        return Err(remote_command_error("Push", &stderr));
+    }
+
+    Ok(())
+}
+
+/// リモートからプル（別スレッドから呼ぶ）
+/// modeは "merge"（通常のpull）/ "ff-only" / "rebase"
+pub fn pull_repo(path: &str, mode: &str, transport: RemoteTransport) -> Result<(), GitError> {
+    if transport == RemoteTransport::Embedded {
+        return remote::pull_repo(path, mode);
+    }
+    let args: &[&str] = match mode {
+        "ff-only" => &["pull", "--ff-only"],
+        "rebase" => &["pull", "--rebase"],
+        _ => &["pull"],
+    };
+    let output = create_git_command()
+        .args(args)
+        .current_dir(path)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if mode == "ff-only"
+            && !is_auth_failure(&stderr)
+            && stderr.to_lowercase().contains("fast-forward")
+        {
+            return Err("Cannot fast-forward; diverged. Pull with merge or rebase".into());
+        }
+        return Err(remote_command_error("Pull", &stderr));
+    }
+
+    Ok(())
+}
+
+/// 設定済みの外部マージツールでコンフリクトを解決（終了まで待つので別スレッドから呼ぶ）
+pub fn run_mergetool(path: &str, filename: &str) -> Result<(), GitError> {
+    let configured = Repository::open(path)
+        .and_then(|repo| repo.config())
+        .and_then(|config| config.get_string("merge.tool"))
+        .is_ok();
+    if !configured {
+        return Err("No mergetool configured; set one with `git config merge.tool <tool>`".into());
+    }
+
+    let output = create_git_command()
+        .args(["mergetool", "--no-prompt", "--", filename])
+        .current_dir(path)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Mergetool failed: {}", stderr.trim()).into());
+    }
+    Ok(())
+}
+
+/// git format-patchと同じ規則でパッチのファイル名を作る（例: 0001-Fix-typo.patch）
+/// 英数字と . _ 以外の連続は - にまとめ、件名部分は52文字までに切り詰める
+pub fn patch_file_name(number: usize, subject: &str) -> String {
+    let mut slug = String::new();
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.chars().take(52).collect();
+    let slug = slug.trim_end_matches(['-', '.']);
+    if slug.is_empty() {
+        format!("{:04}.patch", number)
+    } else {
+        format!("{:04}-{}.patch", number, slug)
+    }
+}
+
+/// リモートブランチ名（origin/feature/x）からリモート名を除いたローカルブランチ名（feature/x）
+pub fn local_name_for_remote(remote_ref: &str) -> String {
+    remote_ref
+        .split_once('/')
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_default()
+}
+
+/// git log --format="%H %G?" の出力から署名の検証結果を解析
+/// G（有効）とU（有効だが信頼度不明）を検証済みとみなす
+fn parse_signature_check(output: &str) -> HashMap<String, bool> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().split_once(' '))
+        .map(|(hash, code)| (hash.to_string(), matches!(code.trim(), "G" | "U")))
+        .collect()
+}
+
+/// コミットの署名状態を調べる（検証はgit CLIを使うので別スレッドから呼ぶ）
+/// gitコマンドがない・検証を実行できなかった場合、署名付きのコミットは "unknown" とする
+/// 戻り値: (フルハッシュ, "verified" / "unverified" / "unsigned" / "unknown")
+pub fn check_commit_signatures(
+    path: &str,
+    hashes: &[String],
+    git_available: bool,
+) -> Vec<(String, &'static str)> {
+    let Ok(repo) = Repository::open(path) else {
+        return vec![];
+    };
+    let mut results = vec![];
+    let mut signed = vec![];
+    for hash in hashes {
+        let Ok(oid) = Oid::from_str(hash) else {
+            continue;
+        };
+        if repo.extract_signature(&oid, None).is_ok() {
+            signed.push(hash.clone());
+        } else {
+            results.push((hash.clone(), "unsigned"));
+        }
+    }
+    if !git_available {
+        results.extend(signed.into_iter().map(|hash| (hash, "unknown")));
+        return results;
+    }
+    if signed.is_empty() {
+        return results;
+    }
+
+    // 署名付きのコミットだけまとめて検証（GPG/SSHの設定はgitに任せる）
+    let verified = create_git_command()
+        .args(["log", "--no-walk=unsorted", "--format=%H %G?"])
+        .args(&signed)
+        .current_dir(path)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| parse_signature_check(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default();
+    for hash in signed {
+        let status = match verified.get(&hash) {
+            Some(true) => "verified",
+            Some(false) => "unverified",
+            None => "unknown",
+        };
+        results.push((hash, status));
+    }
+    results
+}
+
+/// git cloneの追加オプション引数を生成
+/// depthは空欄または0で無効。それ以外は正の整数でなければエラー
+pub fn build_clone_args(
+    shallow: bool,
+    depth: &str,
+    single_branch: bool,
+) -> Result<Vec<String>, String> {
+    let depth = depth.trim();
+    let depth = if depth.is_empty() {
+        0
+    } else {
+        match depth.parse::<i64>() {
+            Ok(n) if n >= 0 => n,
+            _ => return Err(format!("Depth must be a positive integer: {}", depth)),
+        }
+    };
+
+    let mut args = vec![];
+    if depth > 0 {
+        args.push("--depth".to_string());
+        args.push(depth.to_string());
+    } else if shallow {
+        args.push("--depth".to_string());
+        args.push("1".to_string());
+    }
+    if single_branch {
+        args.push("--single-branch".to_string());
+    }
+    Ok(args)
+}
+
+pub const DEFAULT_DIFF_CONTEXT_LINES: u32 = 3;
+
+/// スタッシュ適用の結果
+#[derive(Debug, PartialEq, Eq)]
+pub enum StashApplyOutcome {
+    Clean,
+    /// 3-wayマージで競合したファイル（作業ツリーにマーカー付きで書き出し済み）
+    Conflicts(Vec<String>),
+}
+
+/// スタッシュが変更したファイルのうち、作業ツリーやインデックスに未コミットの変更があるもの
+fn stash_overlapping_changes(repo: &Repository, index: usize) -> Vec<String> {
+    let Some(stash_oid) = repo
+        .reflog("refs/stash")
+        .ok()
+        .and_then(|reflog| reflog.get(index).map(|e| e.id_new()))
+    else {
+        return vec![];
+    };
+    let Ok(stash) = repo.find_commit(stash_oid) else {
+        return vec![];
+    };
+    let (Ok(stash_tree), Ok(base_tree)) = (
+        stash.tree(),
+        stash.parent(0).and_then(|parent| parent.tree()),
+    ) else {
+        return vec![];
+    };
+    let Ok(diff) = repo.diff_tree_to_tree(Some(&base_tree), Some(&stash_tree), None) else {
+        return vec![];
+    };
+    let Ok(statuses) = repo.statuses(None) else {
+        return vec![];
+    };
+    let dirty: std::collections::HashSet<String> = statuses
+        .iter()
+        .filter(|s| !s.status().is_ignored())
+        .filter_map(|s| s.path().map(|p| p.to_string()))
+        .collect();
+    diff.deltas()
+        .filter_map(|d| d.new_file().path().map(|p| p.to_string_lossy().to_string()))
+        .filter(|path| dirty.contains(path))
+        .collect()
+}
+
+/// スタッシュのメッセージを「On <branch>: 」の接頭辞と本文に分ける
+pub fn split_stash_message(message: &str) -> (Option<&str>, &str) {
+    for prefix in ["On ", "WIP on "] {
+        if let Some((branch, rest)) = message
+            .strip_prefix(prefix)
+            .and_then(|m| m.split_once(": "))
+        {
+            return (Some(branch), rest);
+        }
+    }
+    (None, message)
+}
+
+/// 変更後のスタッシュメッセージ（元のブランチ名の接頭辞は残す）
+fn renamed_stash_message(old_message: &str, new_message: &str) -> String {
+    match split_stash_message(old_message) {
+        (Some(branch), _) => format!("On {}: {}", branch, new_message),
+        (None, _) => new_message.to_string(),
+    }
+}
+
+/// パスを比較・表示用に正規化（末尾の区切り文字を取り除く）
+fn normalize_path(path: &Path) -> String {
+    let normalized: std::path::PathBuf = path.components().collect();
+    normalized.to_string_lossy().to_string()
+}
+
+/// 削除する未追跡のファイル・ディレクトリの中身を取り消し用に読み込む
+/// 戻り値: (ワークツリーからのパス, 内容) の一覧。ディレクトリは内容をNoneとして空のものも含める
+fn snapshot_untracked(
+    workdir: &Path,
+    path: &Path,
+) -> std::io::Result<Vec<(String, Option<Vec<u8>>)>> {
+    let relative = |p: &Path| {
+        p.strip_prefix(workdir)
+            .unwrap_or(p)
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+    let mut files = vec![];
+    if fs::symlink_metadata(path)?.is_dir() {
+        files.push((relative(path), None));
+        for entry in fs::read_dir(path)? {
+            files.extend(snapshot_untracked(workdir, &entry?.path())?);
+        }
+    } else {
+        files.push((relative(path), Some(fs::read(path)?)));
+    }
+    Ok(files)
+}
+
+/// 削除したパスの親ディレクトリが空になった場合、ワークツリーのルートまで順に削除する
+fn remove_empty_parent_dirs(workdir: &Path, removed: &Path) {
+    let mut dir = removed.parent();
+    while let Some(d) = dir {
+        if !d.starts_with(workdir) || normalize_path(d) == normalize_path(workdir) {
+            break;
+        }
+        let is_empty = d
+            .read_dir()
+            .map(|mut entries| entries.next().is_none())
+            .unwrap_or(false);
+        if !is_empty || fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
+
+// ========== GitClient ==========
+
+/// Git操作のエラー
+/// UIは種類で分岐し（Pullを促す、スタッシュして切り替えるなど）、表示はDisplayに任せる
+#[derive(Debug)]
+pub enum GitError {
+    /// リポジトリを開いていない
+    NoRepository,
+    /// 指定フォルダは存在するがGitリポジトリではない
+    NotARepository,
+    /// HEADがまだコミットを持たない（HEADが指すブランチ名）
+    UnbornHead(String),
+    /// 認証に失敗した（操作名: "Push" など）
+    AuthFailed(String),
+    /// リモートが先に進んでいてpushが拒否された（non-fast-forward）
+    PushRejected,
+    /// チェックアウト先と未コミットの変更が衝突した（衝突したファイル）
+    CheckoutConflict(Vec<String>),
+    /// HEADにもupstreamにも取り込まれていないコミットがある（forceで削除可能）
+    NotFullyMerged,
+    /// 対象（ブランチ、スタッシュ、hunkなど）が見つからない（表示用のメッセージ）
+    NotFound(String),
+    /// テキストとして表示できないファイル
+    BinaryFile,
+    /// git2のエラー
+    Git(git2::Error),
+    /// ファイル操作やgitコマンド起動のエラー
+    Io(std::io::Error),
+    /// その他のエラー（表示用のメッセージ）
+    Other(String),
+}
+
+impl std::fmt::Display for GitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitError::NoRepository => write!(f, "No repository"),
+            GitError::NotARepository => write!(f, "Not a git repository"),
+            GitError::UnbornHead(branch) => {
+                write!(f, "Create a commit first: {} has no commits yet", branch)
+            }
+            GitError::AuthFailed(action) => write!(
+                f,
+                "Authentication failed ({}): check your credential helper or SSH key",
+                action
+            ),
+            GitError::PushRejected => {
+                write!(f, "Push rejected: remote has new commits. Pull first?")
+            }
+            GitError::CheckoutConflict(files) => write!(
+                f,
+                "Local changes conflict with the checkout: {}",
+                files.join(", ")
+            ),
+            GitError::NotFullyMerged => write!(f, "Branch is not fully merged"),
+            GitError::NotFound(msg) | GitError::Other(msg) => write!(f, "{}", msg),
+            GitError::BinaryFile => write!(f, "Binary file"),
+            GitError::Git(e) => write!(f, "{}", e),
+            GitError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for GitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GitError::Git(e) => Some(e),
+            GitError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<git2::Error> for GitError {
+    fn from(e: git2::Error) -> Self {
+        GitError::Git(e)
+    }
+}
+
+impl From<std::io::Error> for GitError {
+    fn from(e: std::io::Error) -> Self {
+        GitError::Io(e)
+    }
+}
+
+impl From<String> for GitError {
+    fn from(msg: String) -> Self {
+        GitError::Other(msg)
+    }
+}
+
+impl From<&str> for GitError {
+    fn from(msg: &str) -> Self {
+        GitError::Other(msg.to_string())
+    }
+}
+
+/// コンフリクト解決でどちらの版を採用するか
+#[derive(Clone, Copy)]
+pub enum ConflictSide {
+    /// 現在のブランチ側（stage 2）
+    Ours,
+    /// マージ・リバート対象側（stage 3）
+    Theirs,
+}
+
+/// インデックスから指定パスのコンフリクト（base/ours/theirsの各ステージ）を探す
+fn find_index_conflict(index: &git2::Index, path: &str) -> Result<git2::IndexConflict, GitError> {
+    index
+        .conflicts()?
+        .flatten()
+        .find(|c| {
+            [&c.ancestor, &c.our, &c.their]
+                .into_iter()
+                .flatten()
+                .any(|e| e.path == path.as_bytes())
+        })
+        .ok_or_else(|| GitError::NotFound(format!("{} is not conflicted", path)))
+}
+
+/// コンフリクト中のファイルの3つの版（削除されている側はNone）
+pub struct ConflictVersions {
+    pub base: Option<Vec<String>>,
+    pub ours: Option<Vec<String>>,
+    pub theirs: Option<Vec<String>>,
+}
+
+/// HEADがまだコミットを持たない（最初のコミット前のブランチを指している）か
+fn head_is_unborn(repo: &Repository) -> bool {
+    matches!(repo.head(), Err(ref e) if e.code() == git2::ErrorCode::UnbornBranch)
+}
+
+/// HEADがまだコミットを持たない（unborn）場合のエラー
+/// ブランチ名はHEADの参照先（init.defaultBranchで決まる）から取る
+fn unborn_head_error(repo: &Repository) -> GitError {
+    let branch = repo
+        .find_reference("HEAD")
+        .ok()
+        .and_then(|r| {
+            r.symbolic_target()
+                .map(|t| t.trim_start_matches("refs/heads/").to_string())
+        })
+        .unwrap_or_else(|| "HEAD".to_string());
+    GitError::UnbornHead(branch)
+}
+
+/// ローカルブランチとupstreamの関係
+#[derive(Debug, PartialEq)]
+enum UpstreamStatus {
+    /// upstreamが設定されていない
+    NotConfigured,
+    /// 設定されたupstreamが存在しない（サーバー側で削除されfetch --pruneされた等）
+    Gone(String),
+    /// upstreamと比較できた
+    Tracking {
+        name: String,
+        ahead: usize,
+        behind: usize,
+    },
+}
+
+/// 取り消し可能な操作と、取り消しに必要な直前の状態
+#[derive(Debug)]
+enum UndoAction {
+    /// 変更を破棄した追跡中のファイルの破棄前の内容（Noneは破棄前にファイルがなかった＝削除を破棄した）
+    /// checkout_headはインデックスもHEADに戻すため、破棄前のエントリも持つ（Noneはインデックスになかった）
+    Discard {
+        path: String,
+        content: Option<Vec<u8>>,
+        index_entry: Option<git2::IndexEntry>,
+    },
+    /// 削除した未追跡のファイル・ディレクトリの中身（ワークツリーからのパス, 内容。Noneはディレクトリ）
+    DiscardUntracked {
+        path: String,
+        files: Vec<(String, Option<Vec<u8>>)>,
+    },
+    /// 削除したブランチと、それが指していたコミット・upstream（"origin/main" など）
+    DeleteBranch {
+        name: String,
+        oid: Oid,
+        upstream: Option<String>,
+    },
+    /// ハードリセット前のHEAD（リセットしたブランチ名。detached HEADなら空）
+    HardReset { branch: String, old_head: Oid },
+}
+
+impl UndoAction {
+    fn describe(&self) -> String {
+        match self {
+            UndoAction::Discard { path, .. } | UndoAction::DiscardUntracked { path, .. } => {
+                format!("discard of {}", path)
+            }
+            UndoAction::DeleteBranch { name, .. } => format!("delete of branch {}", name),
+            UndoAction::HardReset { old_head, .. } => {
+                format!("hard reset from {}", &old_head.to_string()[..7])
+            }
+        }
+    }
+}
+
+/// サイドバーに表示するブランチ・ワークツリー一覧
+/// refが多いリポジトリでは取得に時間がかかるため、開いた直後は別スレッドで読み込む
+pub struct BranchLists {
+    pub local: Vec<LocalBranchData>,
+    pub remote: Vec<RemoteBranchData>,
+    pub worktrees: Vec<WorktreeData>,
+}
+
+/// 取り消し履歴に残す操作数の上限
+const MAX_UNDO_ACTIONS: usize = 10;
+
+pub struct GitClient {
+    repo: Option<Repository>,
+    repo_path: Option<String>,
+    show_ignored: bool,                // 無視ファイルもUnstagedに表示するか
+    run_commit_hooks: bool,            // git CLI経由でコミットしてフックを実行するか
+    remote_transport: RemoteTransport, // fetch / push / pullの実行方法
+    git_available: bool,               // gitコマンドがPATHにあるか
+    pub diff_context_lines: u32,       // Diffの前後に表示するコンテキスト行数
+    graph_cache: SharedGraphCache,
+    undo_stack: RefCell<Vec<UndoAction>>, // 直近の破壊的な操作（新しいものが末尾）
+    graph_author_filter: String,          // グラフを作者メールで絞り込む（空なら全員）
+    graph_branch_filter: String,          // グラフを指定ブランチから到達できるコミットに絞り込む
+    current_branch_only: bool,            // グラフに現在のブランチ（HEAD）の履歴だけを表示する
+    group_untracked_dirs: bool,           // 新規ディレクトリ内の未追跡ファイルを1行にまとめる
+    expanded_untracked_dirs: HashSet<String>, // まとめた行のうち展開中のディレクトリ（"dir/"）
+}
+
+/// 未コミットの変更数（表示のみの無視ファイルは除く）
+/// まとめたディレクトリは含むファイル数で数え、展開中なら子の行で数える
+pub fn uncommitted_count(staged: &[FileData], unstaged: &[FileData]) -> usize {
+    let unstaged_count: usize = unstaged
+        .iter()
+        .filter(|f| f.status != "I")
+        .map(|f| match (f.group_count, f.expanded) {
+            (0, _) => 1,
+            (_, true) => 0,
+            (n, false) => n as usize,
+        })
+        .sum();
+    staged.len() + unstaged_count
+}
+
+/// 未追跡ディレクトリ（"dir/"）内のファイルをディレクトリ単位の1行にまとめる
+/// 展開中のディレクトリはまとめた行の直後に中のファイルを並べる
+fn group_untracked_dirs(
+    files: Vec<FileData>,
+    dirs: &[String],
+    expanded: &HashSet<String>,
+) -> Vec<FileData> {
+    let dir_of = |f: &FileData| {
+        if f.status != "?" {
+            return None;
+        }
+        dirs.iter().find(|d| f.filename.starts_with(d.as_str()))
+    };
+    let mut grouped: Vec<FileData> = vec![];
+    let mut children: HashMap<&str, Vec<FileData>> = HashMap::new();
+    for file in files {
+        let Some(dir) = dir_of(&file) else {
+            grouped.push(file);
+            continue;
+        };
+        let entry = children.entry(dir.as_str()).or_default();
+        if entry.is_empty() {
+            // ディレクトリ内で最初のファイルの位置にまとめた行を置く
+            grouped.push(FileData {
+                filename: dir.as_str().into(),
+                old_filename: "".into(),
+                status: "?".into(),
+                staged: false,
+                partially_staged: false,
+                group_count: 0,
+                expanded: expanded.contains(dir.as_str()),
+                in_group: false,
+            });
+        }
+        entry.push(FileData {
+            in_group: true,
+            ..file
+        });
+    }
+
+    let mut result = vec![];
+    for mut file in grouped {
+        let Some(files) = children.remove(file.filename.as_str()) else {
+            result.push(file);
+            continue;
+        };
+        file.group_count = files.len() as i32;
+        let expanded = file.expanded;
+        result.push(file);
+        if expanded {
+            result.extend(files);
+        }
+    }
+    result
+}
+
+/// autosquash用のメッセージ（"fixup! 件名" / "squash! 件名"、bodyがあれば空行を挟んで続ける）
+fn autosquash_message(prefix: &str, subject: &str, body: &str) -> String {
+    let body = body.trim();
+    if body.is_empty() {
+        format!("{} {}", prefix, subject)
+    } else {
+        format!("{} {}\n\n{}", prefix, subject, body)
+    }
+}
+
+/// Staged / Unstagedの両方に現れるファイル（一部だけステージ済み）に印を付ける
+fn mark_partially_staged(staged: &mut [FileData], unstaged: &mut [FileData]) {
+    let staged_names: HashSet<String> = staged.iter().map(|f| f.filename.to_string()).collect();
+    let mut both = HashSet::new();
+    for file in unstaged.iter_mut() {
+        if staged_names.contains(file.filename.as_str()) {
+            file.partially_staged = true;
+            both.insert(file.filename.to_string());
+        }
+    }
+    for file in staged.iter_mut() {
+        file.partially_staged = both.contains(file.filename.as_str());
+    }
+}
+
+impl GitClient {
+    pub fn new() -> Self {
+        Self {
+            repo: None,
+            repo_path: None,
+            show_ignored: false,
+            run_commit_hooks: false,
+            remote_transport: RemoteTransport::default(),
+            git_available: true,
+            diff_context_lines: DEFAULT_DIFF_CONTEXT_LINES,
+            graph_cache: Arc::new(Mutex::new(None)),
+            undo_stack: RefCell::new(vec![]),
+            graph_author_filter: String::new(),
+            graph_branch_filter: String::new(),
+            current_branch_only: false,
+            group_untracked_dirs: false,
+            expanded_untracked_dirs: HashSet::new(),
+        }
+    }
+
+    pub fn set_current_branch_only(&mut self, only: bool) {
+        self.current_branch_only = only;
+    }
+
+    pub fn graph_branch_filter(&self) -> &str {
+        &self.graph_branch_filter
+    }
+
+    /// グラフの絞り込み条件を設定（空文字列は絞り込みなし）
+    pub fn set_graph_filter(&mut self, author_email: &str, branch: &str) {
+        self.graph_author_filter = author_email.trim().to_lowercase();
+        self.graph_branch_filter = branch.to_string();
+    }
+
+    pub fn set_show_ignored(&mut self, show: bool) {
+        self.show_ignored = show;
+    }
+
+    pub fn set_group_untracked_dirs(&mut self, group: bool) {
+        self.group_untracked_dirs = group;
+    }
+
+    /// まとめた未追跡ディレクトリの展開・折りたたみを切り替える
+    pub fn toggle_untracked_dir(&mut self, dir: &str) {
+        if !self.expanded_untracked_dirs.remove(dir) {
+            self.expanded_untracked_dirs.insert(dir.to_string());
+        }
+    }
+
+    pub fn set_run_commit_hooks(&mut self, run: bool) {
+        self.run_commit_hooks = run;
+    }
+
+    pub fn set_remote_transport(&mut self, transport: RemoteTransport) {
+        self.remote_transport = transport;
+    }
+
+    pub fn remote_transport(&self) -> RemoteTransport {
+        self.remote_transport
+    }
+
+    pub fn set_git_available(&mut self, available: bool) {
+        self.git_available = available;
+    }
+
+    /// fetch / push / pullを実行できるか（gitコマンドがなくても組み込みのlibgit2なら可能）
+    pub fn remote_available(&self) -> bool {
+        self.git_available || self.remote_transport == RemoteTransport::Embedded
+    }
+
+    pub fn set_diff_context_lines(&mut self, lines: u32) {
+        self.diff_context_lines = lines;
+    }
+
+    /// リポジトリを開く（サブフォルダを指定した場合は上位の.gitを探索する）
+    /// repo_pathには指定パスではなく検出したワークツリーのルートを保持する
+    pub fn open_repo(&mut self, path: &str) -> Result<(), GitError> {
+        match Repository::discover(path) {
+            Ok(repo) => {
+                // bareリポジトリはワークツリーがないため.gitディレクトリ自体を使用
+                let root = repo.workdir().unwrap_or_else(|| repo.path());
+                self.repo_path = Some(normalize_path(root));
+                self.repo = Some(repo);
+                self.undo_stack.borrow_mut().clear();
+                self.expanded_untracked_dirs.clear();
+                Ok(())
+            }
+            Err(e) if e.code() == git2::ErrorCode::NotFound && Path::new(path).is_dir() => {
+                Err(GitError::NotARepository)
+            }
+            Err(e) => Err(format!("Failed to open repository: {}", e.message()).into()),
+        }
+    }
+
+    /// 別スレッドで重い読み込みをするための複製を作る
+    /// 同じリポジトリを開き直し、表示設定とグラフキャッシュを引き継ぐ（取り消し履歴は持たない）
+    pub fn reopen(&self) -> Option<GitClient> {
+        let repo = Repository::open(self.repo.as_ref()?.path()).ok()?;
+        Some(GitClient {
+            repo: Some(repo),
+            repo_path: self.repo_path.clone(),
+            show_ignored: self.show_ignored,
+            run_commit_hooks: self.run_commit_hooks,
+            remote_transport: self.remote_transport,
+            git_available: self.git_available,
+            diff_context_lines: self.diff_context_lines,
+            graph_cache: self.graph_cache.clone(),
+            undo_stack: RefCell::new(vec![]),
+            graph_author_filter: self.graph_author_filter.clone(),
+            graph_branch_filter: self.graph_branch_filter.clone(),
+            current_branch_only: self.current_branch_only,
+            group_untracked_dirs: self.group_untracked_dirs,
+            expanded_untracked_dirs: self.expanded_untracked_dirs.clone(),
+        })
+    }
+
+    /// コミットテンプレートを取得（commit.template → .gitmessage の順に探す）
+    /// '#'で始まるコメント行は取り除く
+    pub fn get_commit_template(&self) -> Option<String> {
+        let repo = self.repo.as_ref()?;
+        let template_path = repo
+            .config()
+            .ok()
+            .and_then(|c| c.get_path("commit.template").ok())
+            .or_else(|| repo.workdir().map(|w| w.join(".gitmessage")))?;
+
+        let content = fs::read_to_string(template_path).ok()?;
+        let template = content
+            .lines()
+            .filter(|l| !l.starts_with('#'))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let template = template.trim_end().to_string();
+        if template.is_empty() {
+            None
+        } else {
+            Some(template)
+        }
+    }
+
+    /// プルリクエストのテンプレートを取得（.github/ → ルート → docs/ の順に探す）
+    /// GitHubと同じくファイル名の大文字小文字は区別しない
+    pub fn get_pull_request_template(&self) -> Option<String> {
+        let workdir = self.repo.as_ref()?.workdir()?;
+        [".github", "", "docs"].iter().find_map(|dir| {
+            let entries = fs::read_dir(workdir.join(dir)).ok()?;
+            let path = entries.flatten().map(|e| e.path()).find(|p| {
+                p.is_file()
+                    && p.file_name()
+                        .and_then(|n| n.to_str())
+                        .is_some_and(|n| n.eq_ignore_ascii_case("pull_request_template.md"))
+            })?;
+            let content = fs::read_to_string(path).ok()?;
+            let template = content.trim_end();
+            (!template.is_empty()).then(|| template.to_string())
+        })
+    }
+
+    /// 指定フォルダに新しいリポジトリを作成して開く
+    pub fn init_repo(&mut self, path: &str) -> Result<(), GitError> {
+        let repo = Repository::init(path)?;
+        self.repo = Some(repo);
+        self.repo_path = Some(path.to_string());
+        self.undo_stack.borrow_mut().clear();
+        Ok(())
+    }
+
+    pub fn get_repo_path(&self) -> Option<String> {
+        self.repo_path.clone()
+    }
+
+    /// ワークツリー内の相対パスを絶対パスに解決
+    pub fn resolve_workdir_path(&self, filename: &str) -> Option<std::path::PathBuf> {
+        let workdir = self.repo.as_ref()?.workdir()?;
+        Some(workdir.join(filename))
+    }
+
+    /// 現在のブランチ名（detached HEADの場合は空）
+    pub fn get_current_branch(&self) -> String {
+        self.repo.as_ref().map_or("".to_string(), |repo| {
+            if repo.head_detached().unwrap_or(false) {
+                return String::new();
+            }
+            repo.head()
+                .ok()
+                .and_then(|h| h.shorthand().map(|s| s.to_string()))
+                .unwrap_or_default()
+        })
+    }
+
+    /// detached HEADなら指しているコミットの短縮ハッシュを返す
+    pub fn get_detached_head(&self) -> Option<String> {
+        let repo = self.repo.as_ref()?;
+        if !repo.head_detached().unwrap_or(false) {
+            return None;
+        }
+        let oid = repo.head().ok()?.target()?;
+        Some(oid.to_string()[..7].to_string())
+    }
+
+    pub fn get_local_branches(&self) -> Vec<LocalBranchData> {
+        let Some(repo) = &self.repo else {
+            return vec![];
+        };
+        let current = self.get_current_branch();
+
+        let mut branches = vec![];
+
+        if let Ok(branch_iter) = repo.branches(Some(BranchType::Local)) {
+            for branch in branch_iter.flatten() {
+                if let Some(name) = branch.0.name().ok().flatten() {
+                    let (upstream, upstream_gone, ahead, behind) =
+                        match self.get_upstream_status(name) {
+                            UpstreamStatus::NotConfigured => (String::new(), false, 0, 0),
+                            UpstreamStatus::Gone(upstream) => (upstream, true, 0, 0),
+                            UpstreamStatus::Tracking {
+                                name,
+                                ahead,
+                                behind,
+                            } => (name, false, ahead, behind),
+                        };
+                    branches.push(LocalBranchData {
+                        name: name.into(),
+                        is_current: name == current,
+                        ahead: ahead as i32,
+                        behind: behind as i32,
+                        diverged: ahead > 0 && behind > 0,
+                        upstream: upstream.into(),
+                        upstream_gone,
+                    });
+                }
+            }
+        }
+
+        // 現在のブランチを先頭に、続いて分岐・push待ち・pull待ち・同期済みの順にまとめる（各グループ内は名前順）
+        let group = |b: &LocalBranchData| match (b.ahead > 0, b.behind > 0) {
+            (true, true) => 0,
+            (true, false) => 1,
+            (false, true) => 2,
+            (false, false) => 3,
+        };
+        branches.sort_by_key(|b| (!b.is_current, group(b)));
+        branches
+    }
+
+    /// 現在のブランチがupstreamより何コミット進んで/遅れているか（upstreamがなければ (0, 0)）
+    pub fn get_ahead_behind(&self) -> (usize, usize) {
+        match self.get_upstream_status(&self.get_current_branch()) {
+            UpstreamStatus::Tracking { ahead, behind, .. } => (ahead, behind),
+            _ => (0, 0),
+        }
+    }
+
+    /// ローカルブランチのupstreamの状態と、upstreamより何コミット進んで/遅れているか
+    fn get_upstream_status(&self, branch_name: &str) -> UpstreamStatus {
+        let Some(repo) = &self.repo else {
+            return UpstreamStatus::NotConfigured;
+        };
+        let Ok(branch) = repo.find_branch(branch_name, BranchType::Local) else {
+            return UpstreamStatus::NotConfigured;
+        };
+        // 設定上のupstream名（リモートブランチが存在しなくても取得できる）
+        let Some(upstream_ref) = repo
+            .branch_upstream_name(&format!("refs/heads/{}", branch_name))
+            .ok()
+            .and_then(|name| name.as_str().map(|n| n.to_string()))
+        else {
+            return UpstreamStatus::NotConfigured;
+        };
+        let Ok(upstream) = branch.upstream() else {
+            let name = upstream_ref
+                .strip_prefix("refs/remotes/")
+                .or_else(|| upstream_ref.strip_prefix("refs/heads/"))
+                .unwrap_or(&upstream_ref);
+            return UpstreamStatus::Gone(name.to_string());
+        };
+        let name = upstream
+            .name()
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+            .to_string();
+        let ahead_behind = branch
+            .get()
+            .target()
+            .zip(upstream.get().target())
+            .and_then(|(local, upstream)| repo.graph_ahead_behind(local, upstream).ok());
+        let (ahead, behind) = ahead_behind.unwrap_or((0, 0));
+        UpstreamStatus::Tracking {
+            name,
+            ahead,
+            behind,
+        }
+    }
+
+    /// ブランチのupstreamを設定・変更する（remote_refは origin/main のようなリモートブランチ名）
+    pub fn set_upstream(&self, branch_name: &str, remote_ref: &str) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        // 存在しないリモートブランチはgit2のエラーより分かりやすく返す
+        if repo.find_branch(remote_ref, BranchType::Remote).is_err() {
+            return Err(GitError::NotFound(format!(
+                "Remote branch not found: {}",
+                remote_ref
+            )));
+        }
+        let mut branch = repo.find_branch(branch_name, BranchType::Local)?;
+        branch
+            .set_upstream(Some(remote_ref))
+            .map_err(GitError::from)
+    }
+
+    /// ブランチのupstreamを解除する
+    pub fn unset_upstream(&self, branch_name: &str) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let mut branch = repo.find_branch(branch_name, BranchType::Local)?;
+        branch.set_upstream(None).map_err(GitError::from)
+    }
+
+    /// ブランチ・ワークツリー一覧をまとめて取得
+    pub fn branch_lists(&self) -> BranchLists {
+        BranchLists {
+            local: self.get_local_branches(),
+            remote: self.get_remote_branches(),
+            worktrees: self.get_worktrees(),
+        }
+    }
+
+    /// メインワークツリーとリンクされたワークツリーの一覧を取得
+    pub fn get_worktrees(&self) -> Vec<WorktreeData> {
+        let Some(repo) = &self.repo else {
+            return vec![];
+        };
+        if repo.is_bare() {
+            return vec![];
+        }
+        let current_path = self.repo_path.clone().unwrap_or_default();
+        let head_branch = |wt_repo: &Repository| {
+            wt_repo
+                .head()
+                .ok()
+                .and_then(|h| h.shorthand().map(|s| s.to_string()))
+                .unwrap_or_default()
+        };
+
+        let mut worktrees = vec![];
+
+        // メインワークツリー（commondirは共有の.gitディレクトリ）
+        if let Some(main_dir) = repo.commondir().parent() {
+            let path = normalize_path(main_dir);
+            let branch = Repository::open(main_dir)
+                .map(|r| head_branch(&r))
+                .unwrap_or_default();
+            worktrees.push(WorktreeData {
+                name: "(main)".into(),
+                is_current: path == current_path,
+                path: path.into(),
+                branch: branch.into(),
+            });
+        }
+
+        // リンクされたワークツリー
+        if let Ok(names) = repo.worktrees() {
+            for name in names.iter().flatten() {
+                let Ok(worktree) = repo.find_worktree(name) else {
+                    continue;
+                };
+                let path = normalize_path(worktree.path());
+                let branch = Repository::open_from_worktree(&worktree)
+                    .map(|r| head_branch(&r))
+                    .unwrap_or_default();
+                worktrees.push(WorktreeData {
+                    name: name.into(),
+                    is_current: path == current_path,
+                    path: path.into(),
+                    branch: branch.into(),
+                });
+            }
+        }
+
+        worktrees
+    }
+
+    pub fn get_remote_branches(&self) -> Vec<RemoteBranchData> {
+        let Some(repo) = &self.repo else {
+            return vec![];
+        };
+
+        let mut branches = vec![];
+
+        if let Ok(branch_iter) = repo.branches(Some(BranchType::Remote)) {
+            for branch in branch_iter.flatten() {
+                if let Some(name) = branch.0.name().ok().flatten() {
+                    if !name.ends_with("/HEAD") {
+                        branches.push(RemoteBranchData { name: name.into() });
+                    }
+                }
+            }
+        }
+
+        branches
+    }
+
+    /// グラフ計算の入力（キャッシュキー）を取得する（UIスレッドで呼ぶ軽い処理）
+    /// uncommittedは呼び出し側がget_statusの結果から数えたUncommittedの変更数
+    pub fn graph_cache_key(&self, limit: usize, uncommitted: usize) -> Option<GraphCacheKey> {
+        let repo = self.repo.as_ref()?;
+        let current_branch = self.get_current_branch();
+
+        // ブランチ・タグごとのコミットを取得
+        let mut branch_heads: HashMap<String, Vec<(String, RefKind)>> = HashMap::new();
+
+        if let Ok(branches) = repo.branches(Some(BranchType::Local)) {
+            for branch in branches.flatten() {
+                if let (Some(name), Ok(reference)) = (
+                    branch.0.name().ok().flatten(),
+                    branch.0.get().peel_to_commit(),
+                ) {
+                    branch_heads
+                        .entry(reference.id().to_string())
+                        .or_default()
+                        .push((name.to_string(), RefKind::Local));
+                }
+            }
+        }
+        if let Ok(branches) = repo.branches(Some(BranchType::Remote)) {
+            for branch in branches.flatten() {
+                if let (Some(name), Ok(reference)) = (
+                    branch.0.name().ok().flatten(),
+                    branch.0.get().peel_to_commit(),
+                ) {
+                    if !name.ends_with("/HEAD") {
+                        branch_heads
+                            .entry(reference.id().to_string())
+                            .or_default()
+                            .push((name.to_string(), RefKind::Remote));
+                    }
+                }
+            }
+        }
+        // 注釈付きタグはタグオブジェクトからコミットまでたどる
+        if let Ok(tags) = repo.tag_names(None) {
+            for name in tags.iter().flatten() {
+                if let Ok(commit) = repo
+                    .revparse_single(&format!("refs/tags/{}", name))
+                    .and_then(|obj| obj.peel_to_commit())
+                {
+                    branch_heads
+                        .entry(commit.id().to_string())
+                        .or_default()
+                        .push((name.to_string(), RefKind::Tag));
+                }
+            }
+        }
+
+        // HEADのOIDを取得
+        let head_oid = repo
+            .head()
+            .ok()
+            .and_then(|h| h.peel_to_commit().ok())
+            .map(|c| c.id().to_string());
+
+        let mut sorted_heads: Vec<(String, Vec<(String, RefKind)>)> = branch_heads
+            .into_iter()
+            .map(|(oid, mut names)| {
+                names.sort();
+                (oid, names)
+            })
+            .collect();
+        sorted_heads.sort();
+
+        let key = GraphCacheKey {
+            repo_path: self.repo_path.clone(),
+            branch_heads: sorted_heads,
+            head_oid,
+            current_branch,
+            has_uncommitted: uncommitted > 0,
+            limit,
+            author_filter: self.graph_author_filter.clone(),
+            branch_filter: self.graph_branch_filter.clone(),
+            current_branch_only: self.current_branch_only,
+        };
+        Some(key)
+    }
+
+    /// トポロジーに関わる状態が前回と同じならキャッシュ済みのグラフを返す（Uncommitted行のみ更新）
+    pub fn cached_graph(
+        &self,
+        key: &GraphCacheKey,
+        uncommitted: usize,
+    ) -> Option<(Vec<CommitData>, Vec<MergeLineData>)> {
+        let guard = self.graph_cache.lock().ok()?;
+        let cache = guard.as_ref().filter(|c| &c.key == key)?;
+        let mut commits: Vec<CommitData> = cache
+            .commits
+            .iter()
+            .cloned()
+            .map(CommitRow::into_commit_data)
+            .collect();
+        if key.has_uncommitted {
+            if let Some(row) = commits.first_mut() {
+                row.message = uncommitted_message(uncommitted);
+                row.date = chrono::Local::now()
+                    .format("%d %b %H:%M")
+                    .to_string()
+                    .into();
+            }
+        }
+        Some((commits, cache.merge_lines.clone()))
+    }
+
+    pub fn graph_cache_handle(&self) -> SharedGraphCache {
+        self.graph_cache.clone()
+    }
+
+    pub fn get_status(&self) -> (Vec<FileData>, Vec<FileData>) {
+        let Some(repo) = &self.repo else {
+            return (vec![], vec![]);
+        };
+
+        let mut staged = vec![];
+        let mut unstaged = vec![];
+
+        let mut opts = StatusOptions::new();
+        opts.include_untracked(true);
+        opts.recurse_untracked_dirs(true);
+        opts.include_ignored(self.show_ignored);
+        // リネームを検出して "old → new" として表示する
+        opts.renames_head_to_index(true);
+        opts.renames_index_to_workdir(true);
+
+        let file = |filename: &str, old_filename: &str, status: &str, staged: bool| FileData {
+            filename: filename.into(),
+            old_filename: old_filename.into(),
+            status: status.into(),
+            staged,
+            partially_staged: false,
+            group_count: 0,
+            expanded: false,
+            in_group: false,
+        };
+        // リネームの (旧パス, 新パス) を取得
+        let rename_paths = |delta: Option<git2::DiffDelta>| {
+            let delta = delta?;
+            let old = delta.old_file().path()?.to_string_lossy().to_string();
+            let new = delta.new_file().path()?.to_string_lossy().to_string();
+            Some((old, new))
+        };
+
+        if let Ok(statuses) = repo.statuses(Some(&mut opts)) {
+            for entry in statuses.iter() {
+                // リネームの場合、path()は旧パスを返す
+                let path = entry.path().unwrap_or("").to_string();
+                let status = entry.status();
+
+                if status.is_ignored() {
+                    unstaged.push(file(&path, "", "I", false));
+                    continue;
+                }
+
+                // コンフリクト中のファイルはUnstaged側に "U" として表示する
+                if status.is_conflicted() {
+                    unstaged.push(file(&path, "", "U", false));
+                    continue;
+                }
+
+                if status.is_index_new() {
+                    staged.push(file(&path, "", "A", true));
+                } else if status.is_index_modified() {
+                    staged.push(file(&path, "", "M", true));
+                } else if status.is_index_deleted() {
+                    staged.push(file(&path, "", "D", true));
+                } else if status.is_index_renamed() {
+                    let (old, new) =
+                        rename_paths(entry.head_to_index()).unwrap_or((path.clone(), path.clone()));
+                    staged.push(file(&new, &old, "R", true));
+                }
+
+                // インデックスでリネーム済みのファイルは、作業ツリー側では新パスで扱う
+                let wt_path = entry
+                    .index_to_workdir()
+                    .and_then(|delta| delta.new_file().path())
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_else(|| path.clone());
+                if status.is_wt_new() {
+                    unstaged.push(file(&wt_path, "", "?", false));
+                } else if status.is_wt_modified() {
+                    unstaged.push(file(&wt_path, "", "M", false));
+                } else if status.is_wt_deleted() {
+                    unstaged.push(file(&wt_path, "", "D", false));
+                } else if status.is_wt_renamed() {
+                    let (old, new) = rename_paths(entry.index_to_workdir())
+                        .unwrap_or((path.clone(), path.clone()));
+                    unstaged.push(file(&new, &old, "R", false));
+                }
+            }
+        }
+        mark_partially_staged(&mut staged, &mut unstaged);
+        if self.group_untracked_dirs {
+            let dirs = self.untracked_dirs();
+            unstaged = group_untracked_dirs(unstaged, &dirs, &self.expanded_untracked_dirs);
+        }
+        (staged, unstaged)
+    }
+
+    /// 丸ごと未追跡のディレクトリ（最上位のみ、"dir/" の形式）を取得
+    fn untracked_dirs(&self) -> Vec<String> {
+        let Some(repo) = &self.repo else {
+            return vec![];
+        };
+        let mut opts = StatusOptions::new();
+        opts.include_untracked(true);
+        opts.recurse_untracked_dirs(false);
+        let Ok(statuses) = repo.statuses(Some(&mut opts)) else {
+            return vec![];
+        };
+        statuses
+            .iter()
+            .filter(|e| e.status().is_wt_new())
+            .filter_map(|e| e.path().map(str::to_string))
+            .filter(|p| p.ends_with('/'))
+            .collect()
+    }
+
+    /// ファイルのリネーム元パスを取得（リネームでなければNone）
+    /// staged=trueならHEAD→Index、falseならIndex→Workdirのリネームを探す
+    fn find_rename_source(&self, filename: &str, staged: bool) -> Option<String> {
+        let repo = self.repo.as_ref()?;
+        let mut opts = StatusOptions::new();
+        opts.include_untracked(true);
+        opts.recurse_untracked_dirs(true);
+        opts.renames_head_to_index(true);
+        opts.renames_index_to_workdir(true);
+
+        let statuses = repo.statuses(Some(&mut opts)).ok()?;
+        for entry in statuses.iter() {
+            let status = entry.status();
+            let delta = if staged && status.is_index_renamed() {
+                entry.head_to_index()
+            } else if !staged && status.is_wt_renamed() {
+                entry.index_to_workdir()
+            } else {
+                None
+            };
+            let Some(delta) = delta else {
+                continue;
+            };
+            if delta.new_file().path() == Some(Path::new(filename)) {
+                return delta
+                    .old_file()
+                    .path()
+                    .map(|p| p.to_string_lossy().to_string());
+            }
+        }
+        None
+    }
+
+    /// コンフリクト中のファイルをours/theirsの版で置き換え、解決済みとしてインデックスに登録
+    /// 採用する側でファイルが削除されている場合は削除として解決する
+    pub fn resolve_conflict(&self, path: &str, side: ConflictSide) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let workdir = repo.workdir().ok_or("No workdir")?;
+        let mut index = repo.index()?;
+
+        let conflict = find_index_conflict(&index, path)?;
+        let entry = match side {
+            ConflictSide::Ours => conflict.our,
+            ConflictSide::Theirs => conflict.their,
+        };
+
+        let full_path = workdir.join(path);
+        match entry {
+            Some(entry) => {
+                let blob = repo.find_blob(entry.id)?;
+                if let Some(parent) = full_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&full_path, blob.content())?;
+                // add_pathはコンフリクトのステージも取り除く
+                index.add_path(Path::new(path))?;
+            }
+            None => {
+                if full_path.exists() {
+                    fs::remove_file(&full_path)?;
+                }
+                index.conflict_remove(Path::new(path))?;
+                let _ = index.remove_path(Path::new(path));
+            }
+        }
+        index.write()?;
+        Ok(())
+    }
+
+    /// コンフリクト中のファイルについて、インデックスのステージ1〜3（base/ours/theirs）の内容を行単位で取得
+    pub fn get_conflict_versions(&self, path: &str) -> Result<ConflictVersions, GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let index = repo.index()?;
+        let conflict = find_index_conflict(&index, path)?;
+
+        let read_lines =
+            |entry: Option<git2::IndexEntry>| -> Result<Option<Vec<String>>, GitError> {
+                let Some(entry) = entry else {
+                    return Ok(None);
+                };
+                let blob = repo.find_blob(entry.id)?;
+                if blob.is_binary() {
+                    return Ok(Some(vec!["(binary file)".to_string()]));
+                }
+                let text = String::from_utf8_lossy(blob.content());
+                Ok(Some(text.lines().map(|l| l.to_string()).collect()))
+            };
+
+        Ok(ConflictVersions {
+            base: read_lines(conflict.ancestor)?,
+            ours: read_lines(conflict.our)?,
+            theirs: read_lines(conflict.their)?,
+        })
+    }
+
+    /// 手動で編集したコンフリクトファイルを解決済みとしてインデックスに登録
+    pub fn mark_resolved(&self, path: &str) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let workdir = repo.workdir().ok_or("No workdir")?;
+        let mut index = repo.index()?;
+
+        if workdir.join(path).exists() {
+            index.add_path(Path::new(path))?;
+        } else {
+            index.conflict_remove(Path::new(path))?;
+            let _ = index.remove_path(Path::new(path));
+        }
+        index.write()?;
+        Ok(())
+    }
+
+    pub fn stage_file(&self, filename: &str) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let mut index = repo.index()?;
+
+        // まとめた未追跡ディレクトリ（"dir/"）は中のファイルをすべて追加する
+        if filename.ends_with('/') {
+            index.add_all(
+                [filename.trim_end_matches('/')].iter(),
+                git2::IndexAddOption::DEFAULT,
+                None,
+            )?;
+            index.write()?;
+            return Ok(());
+        }
+
+        // リネームの場合は旧パスの削除と新パスの追加を同じインデックス書き込みで行う
+        if let Some(old_path) = self.find_rename_source(filename, false) {
+            index.remove_path(Path::new(&old_path))?;
+        }
+
+        let path = Path::new(filename);
+        if path.exists()
+            || repo
+                .workdir()
+                .map(|w| w.join(path).exists())
+                .unwrap_or(false)
+        {
+            index.add_path(path)?;
+        } else {
+            index.remove_path(path)?;
+        }
+        index.write()?;
+        Ok(())
+    }
+
+    pub fn stage_all(&self) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let mut index = repo.index()?;
+        index.add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)?;
+        index.write()?;
+        Ok(())
+    }
+
+    pub fn unstage_file(&self, filename: &str) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let head = repo.head()?;
+        let obj = head.peel(git2::ObjectType::Commit)?;
+        // リネームの場合は旧パスも戻さないと削除だけがステージに残る
+        let mut paths = vec![filename.to_string()];
+        if let Some(old_path) = self.find_rename_source(filename, true) {
+            paths.push(old_path);
+        }
+        repo.reset_default(Some(&obj), paths.iter().map(Path::new))?;
+        Ok(())
+    }
+
+    pub fn unstage_all(&self) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let head = repo.head()?;
+        let obj = head.peel(git2::ObjectType::Commit)?;
+        repo.reset_default(Some(&obj), ["*"])?;
+        Ok(())
+    }
+
+    /// user.name / user.email が設定されているか（ローカル・グローバル設定を含む）
+    pub fn has_identity(&self) -> bool {
+        let Some(repo) = &self.repo else {
+            return false;
+        };
+        let Ok(config) = repo.config() else {
+            return false;
+        };
+        let is_set = |key: &str| {
+            config
+                .get_string(key)
+                .map(|v| !v.trim().is_empty())
+                .unwrap_or(false)
+        };
+        is_set("user.name") && is_set("user.email")
+    }
+
+    /// user.name / user.email をリポジトリのローカル設定に書き込む
+    pub fn set_identity(&self, name: &str, email: &str) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        if name.trim().is_empty() || email.trim().is_empty() {
+            return Err("Name and email are required".into());
+        }
+
+        let mut config = repo
+            .config()
+            .and_then(|c| c.open_level(git2::ConfigLevel::Local))?;
+        config.set_str("user.name", name.trim())?;
+        config.set_str("user.email", email.trim())?;
+        Ok(())
+    }
+
+    /// コミットを作成し、作成したコミットを返す（allow_emptyがfalseの場合、親と同じツリーならエラー）
+    /// authorを指定した場合は作者のみ差し替え、コミッターは設定済みの署名を使う
+    pub fn commit(
+        &self,
+        message: &str,
+        allow_empty: bool,
+        author: Option<(&str, &str)>,
+    ) -> Result<Oid, GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        if self.run_commit_hooks {
+            return self.commit_with_hooks(message, allow_empty, author);
+        }
+
+        let mut index = repo.index()?;
+        let oid = index.write_tree()?;
+        let tree = repo.find_tree(oid)?;
+
+        let sig = repo.signature()?;
+        let author_sig = match author {
+            Some((name, email)) => Signature::now(name, email)?,
+            None => sig.clone(),
+        };
+        let head = repo.head()?;
+        let parent = head.peel_to_commit()?;
+
+        if !allow_empty && parent.tree_id() == tree.id() {
+            return Err("Nothing to commit".into());
+        }
+
+        repo.commit(Some("HEAD"), &author_sig, &sig, message, &tree, &[&parent])
+            .map_err(GitError::from)
+    }
+
+    /// git commitコマンドでコミット（pre-commit / commit-msg フックを実行する）
+    /// フックが失敗した場合はその出力をエラーとして返す
+    fn commit_with_hooks(
+        &self,
+        message: &str,
+        allow_empty: bool,
+        author: Option<(&str, &str)>,
+    ) -> Result<Oid, GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        use std::io::Write;
+        let workdir = repo.workdir().ok_or("No workdir")?;
+        let mut args = vec!["commit".to_string(), "-F".to_string(), "-".to_string()];
+        if allow_empty {
+            args.push("--allow-empty".to_string());
+        }
+        if let Some((name, email)) = author {
+            args.push(format!("--author={} <{}>", name, email));
+        }
+        let mut child = create_git_command()
+            .args(&args)
+            .current_dir(workdir)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(message.as_bytes())?;
+        }
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            if !allow_empty && stdout.contains("nothing to commit") {
+                return Err("Nothing to commit".into());
+            }
+            // フックはstdoutに出力することも多いため両方をまとめる
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let detail = format!("{}\n{}", stderr.trim(), stdout.trim());
+            return Err(format!("git commit failed: {}", detail.trim()).into());
+        }
+
+        repo.refname_to_id("HEAD").map_err(GitError::from)
+    }
+
+    /// ステージ済みの変更を直前のコミットに取り込む（メッセージと作者はそのまま）
+    pub fn amend_no_edit(&self) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        let head = repo
+            .head()
+            .and_then(|h| h.peel_to_commit())
+            .map_err(|_| "No commit to amend".to_string())?;
+        let mut index = repo.index()?;
+        let oid = index.write_tree()?;
+        if oid == head.tree_id() {
+            return Err("Nothing to amend".into());
+        }
+
+        if self.run_commit_hooks {
+            let workdir = repo.workdir().ok_or("No workdir")?;
+            let output = create_git_command()
+                .args(["commit", "--amend", "--no-edit"])
+                .current_dir(workdir)
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped())
+                .output()?;
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                let detail = format!("{}\n{}", stderr.trim(), stdout.trim());
+                return Err(format!("git commit failed: {}", detail.trim()).into());
+            }
+            return Ok(());
+        }
+
+        let tree = repo.find_tree(oid)?;
+        let sig = repo.signature()?;
+        head.amend(Some("HEAD"), None, Some(&sig), None, None, Some(&tree))?;
+        Ok(())
+    }
+
+    /// ステージ済みの変更を "fixup! <対象の件名>" としてコミットする
+    /// rebase --autosquash で対象のコミットに取り込まれる（メッセージは対象のまま）
+    pub fn commit_fixup(&self, target_hash: &str) -> Result<(), GitError> {
+        let subject = self.commit_subject(target_hash)?;
+        self.commit(&autosquash_message("fixup!", &subject, ""), false, None)
+            .map(|_| ())
+    }
+
+    /// ステージ済みの変更を "squash! <対象の件名>" としてコミットする
+    /// messageは本文として続け、rebase --autosquash 時に対象のメッセージへ追加される
+    pub fn commit_squash(&self, target_hash: &str, message: &str) -> Result<(), GitError> {
+        let subject = self.commit_subject(target_hash)?;
+        self.commit(
+            &autosquash_message("squash!", &subject, message),
+            false,
+            None,
+        )
+        .map(|_| ())
+    }
+
+    fn commit_subject(&self, hash: &str) -> Result<String, GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let commit = repo.revparse_single(hash)?.peel_to_commit()?;
+        Ok(commit.summary().unwrap_or("").to_string())
+    }
+
+    /// 指定コミット以降を rebase -i --autosquash で並べ替え、fixup! / squash! を取り込む
+    /// 指示の編集はせずにそのまま実行し、コンフリクトなどで止まった場合は中止して元に戻す
+    /// 範囲にマージが含まれても平らにならないよう --rebase-merges で構造を保つ
+    pub fn rebase_autosquash(&self, target_hash: &str) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let workdir = repo.workdir().ok_or("No workdir")?;
+        let target = repo.revparse_single(target_hash)?.peel_to_commit()?;
+        // 対象自体も書き換えるため、その親を基点にする（ルートコミットなら --root）
+        let base = match target.parent_id(0) {
+            Ok(parent) => parent.to_string(),
+            Err(_) => "--root".to_string(),
+        };
+        let output = create_git_command()
+            .args(["rebase", "-i", "--autosquash", "--rebase-merges", &base])
+            .env("GIT_SEQUENCE_EDITOR", ":")
+            .env("GIT_EDITOR", ":")
+            .current_dir(workdir)
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .output()?;
+        if !output.status.success() {
+            let _ = create_git_command()
+                .args(["rebase", "--abort"])
+                .current_dir(workdir)
+                .output();
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("Autosquash failed: {}", stderr.trim()).into());
+        }
+        Ok(())
+    }
+
+    /// コミットがいずれかのリモート追跡ブランチから到達可能（push済み）か
+    pub fn is_pushed(&self, hash: &str) -> bool {
+        let Some(repo) = &self.repo else {
+            return false;
+        };
+        let Ok(oid) = repo.revparse_single(hash).map(|o| o.id()) else {
+            return false;
+        };
+        let Ok(branches) = repo.branches(Some(BranchType::Remote)) else {
+            return false;
+        };
+        branches
+            .flatten()
+            .filter_map(|(branch, _)| branch.get().target())
+            .any(|remote| remote == oid || repo.graph_descendant_of(remote, oid).unwrap_or(false))
+    }
+
+    /// HEADをtargetへリセットするとpush済みのコミットがブランチから外れるか
+    pub fn reset_drops_pushed(&self, target: &str) -> bool {
+        let Some(repo) = &self.repo else {
+            return false;
+        };
+        let (Some(head), Ok(target)) = (
+            repo.head().ok().and_then(|h| h.target()),
+            repo.revparse_single(target).map(|o| o.id()),
+        ) else {
+            return false;
+        };
+        let moves_forward =
+            target == head || repo.graph_descendant_of(target, head).unwrap_or(false);
+        !moves_forward && self.is_pushed(&head.to_string())
+    }
+
+    pub fn checkout_branch(&self, name: &str) -> Result<(), GitError> {
+        self.checkout_branch_with(name, false)
+    }
+
+    /// 未コミットの変更を破棄してチェックアウトする
+    pub fn force_checkout_branch(&self, name: &str) -> Result<(), GitError> {
+        self.checkout_branch_with(name, true)
+    }
+
+    fn checkout_branch_with(&self, name: &str, force: bool) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        let obj = repo
+            .revparse_single(&format!("refs/heads/{}", name))
+            .map_err(|e| {
+                if head_is_unborn(repo) {
+                    unborn_head_error(repo)
+                } else {
+                    e.into()
+                }
+            })?;
+
+        // 未コミットの変更と衝突するファイルを集めて、分かりやすいエラーにする
+        let conflicts = RefCell::new(vec![]);
+        let mut opts = git2::build::CheckoutBuilder::new();
+        if force {
+            opts.force();
+        } else {
+            opts.safe()
+                .notify_on(git2::CheckoutNotificationType::CONFLICT)
+                .notify(|_, path, _, _, _| {
+                    if let Some(path) = path {
+                        conflicts
+                            .borrow_mut()
+                            .push(path.to_string_lossy().into_owned());
+                    }
+                    true
+                });
+        }
+
+        let result = repo.checkout_tree(&obj, Some(&mut opts));
+        drop(opts);
+        if let Err(e) = result {
+            let conflicts = conflicts.into_inner();
+            if e.code() == git2::ErrorCode::Conflict || !conflicts.is_empty() {
+                return Err(GitError::CheckoutConflict(conflicts));
+            }
+            return Err(e.into());
+        }
+        repo.set_head(&format!("refs/heads/{}", name))?;
+        Ok(())
+    }
+
+    /// 未コミットの変更をスタッシュしてからチェックアウトする
+    /// チェックアウトに失敗してもスタッシュは残すので変更は失われない
+    pub fn checkout_branch_stashing(&mut self, name: &str) -> Result<(), GitError> {
+        self.stash_save(&format!("Auto-stash before checkout {}", name), false)?;
+        self.checkout_branch(name).map_err(|e| {
+            format!(
+                "Stashed local changes as stash@{{0}}, but checkout still failed: {}",
+                e
+            )
+            .into()
+        })
+    }
+
+    pub fn checkout_remote_branch(&self, remote_name: &str) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        let local_name = local_name_for_remote(remote_name);
+        if local_name.is_empty() {
+            return Err("Invalid remote branch name".into());
+        }
+
+        // 既にローカルブランチが存在するかチェック
+        if repo.find_branch(&local_name, BranchType::Local).is_ok() {
+            // 既存のローカルブランチにチェックアウト
+            return self.checkout_branch(&local_name);
+        }
+
+        // 新しいローカルブランチを作成してチェックアウト
+        self.create_tracking_branch(remote_name, &local_name, true)
+    }
+
+    /// リモートブランチを追跡するローカルブランチを任意の名前で作成する（upstreamも設定）
+    pub fn create_tracking_branch(
+        &self,
+        remote_ref: &str,
+        local_name: &str,
+        checkout: bool,
+    ) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        let local_name = local_name.trim();
+        if local_name.is_empty() {
+            return Err("Branch name is empty".into());
+        }
+        if repo.find_branch(local_name, BranchType::Local).is_ok() {
+            return Err(format!("Branch already exists: {}", local_name).into());
+        }
+
+        // リモートブランチのコミットを取得
+        let commit = repo
+            .find_branch(remote_ref, BranchType::Remote)
+            .and_then(|b| b.get().peel_to_commit())?;
+
+        // 新しいローカルブランチを作成して追跡先を設定
+        let mut branch = repo.branch(local_name, &commit, false)?;
+        branch.set_upstream(Some(remote_ref))?;
+
+        if checkout {
+            self.checkout_branch(local_name)?;
+        }
+        Ok(())
+    }
+
+    pub fn create_branch(&self, name: &str) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        if head_is_unborn(repo) {
+            return Err(unborn_head_error(repo));
+        }
+        let head = repo.head()?;
+        let commit = head.peel_to_commit()?;
+
+        repo.branch(name, &commit, false)?;
+        self.checkout_branch(name)?;
+        Ok(())
+    }
+
+    /// ローカルブランチを削除
+    /// git2は未マージでも削除してしまうため、forceでなければHEADかupstreamに取り込まれていることを確認する
+    pub fn delete_branch(&self, name: &str, force: bool) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        let mut branch = repo.find_branch(name, BranchType::Local)?;
+        if !force {
+            if let Some(oid) = branch.get().target() {
+                let head = repo.head().ok().and_then(|h| h.target());
+                let upstream = branch.upstream().ok().and_then(|u| u.get().target());
+                let merged = [head, upstream].into_iter().flatten().any(|target| {
+                    target == oid || repo.graph_descendant_of(target, oid).unwrap_or(false)
+                });
+                if !merged {
+                    return Err(GitError::NotFullyMerged);
+                }
+            }
+        }
+        let oid = branch.get().target();
+        // 削除するとupstreamの設定も消えるので、取り消し用に控えておく
+        let upstream = branch
+            .upstream()
+            .ok()
+            .and_then(|u| u.name().ok().flatten().map(str::to_string));
+        branch.delete()?;
+        if let Some(oid) = oid {
+            self.push_undo(UndoAction::DeleteBranch {
+                name: name.to_string(),
+                oid,
+                upstream,
+            });
+        }
+        Ok(())
+    }
+
+    /// intoブランチに完全にマージ済みのローカルブランチ名（現在のブランチとinto自身は含めない）
+    pub fn get_merged_branches(&self, into: &str) -> Vec<String> {
+        let Some(repo) = &self.repo else {
+            return vec![];
+        };
+        let Some(target) = repo
+            .find_branch(into, BranchType::Local)
+            .ok()
+            .and_then(|b| b.get().target())
+        else {
+            return vec![];
+        };
+        let current = self.get_current_branch();
+
+        let mut merged = vec![];
+        if let Ok(branch_iter) = repo.branches(Some(BranchType::Local)) {
+            for (branch, _) in branch_iter.flatten() {
+                let (Some(name), Some(oid)) = (branch.name().ok().flatten(), branch.get().target())
+                else {
+                    continue;
+                };
+                if name == current || name == into {
+                    continue;
+                }
+                if oid == target || repo.graph_descendant_of(target, oid).unwrap_or(false) {
+                    merged.push(name.to_string());
+                }
+            }
+        }
+        merged
+    }
+
+    pub fn merge_branch(&self, name: &str) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        let branch = repo.find_branch(name, BranchType::Local)?;
+        let reference = branch.get();
+        let annotated = repo.reference_to_annotated_commit(reference)?;
+
+        let (analysis, _) = repo.merge_analysis(&[&annotated])?;
+
+        if analysis.is_up_to_date() {
+            return Ok(());
+        }
+
+        if analysis.is_fast_forward() {
+            let refname = format!("refs/heads/{}", self.get_current_branch());
+            let mut reference = repo.find_reference(&refname)?;
+            reference.set_target(annotated.id(), "Fast-forward")?;
+            repo.set_head(&refname)?;
+            repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+        } else {
+            return Err("Merge requires manual resolution".into());
+        }
+
+        Ok(())
+    }
+
+    pub fn get_stashes(&mut self) -> Vec<StashData> {
+        let Some(repo) = &mut self.repo else {
+            return vec![];
+        };
+        let mut stashes = vec![];
+        let mut stash_idx = 0;
+        let _ = repo.stash_foreach(|index, name, _oid| {
+            stashes.push(StashData {
+                index: index as i32,
+                message: name.into(),
+            });
+            stash_idx += 1;
+            true
+        });
+        stashes
+    }
+
+    /// グラフの作者絞り込みの候補（"Name <email>"、コミット数の多い順）
+    /// 大きなリポジトリでも重くならないよう直近のコミットだけを見る
+    pub fn get_graph_authors(&self) -> Vec<String> {
+        let Some(repo) = &self.repo else {
+            return vec![];
+        };
+        let Ok(mut revwalk) = repo.revwalk() else {
+            return vec![];
+        };
+        revwalk.set_sorting(git2::Sort::TIME).ok();
+        let _ = revwalk.push_glob("refs/heads");
+        let _ = revwalk.push_glob("refs/remotes");
+
+        let mailmap = repo.mailmap().ok();
+        let mut counts: HashMap<String, (String, usize)> = HashMap::new();
+        for oid in revwalk.take(GRAPH_AUTHOR_SCAN_LIMIT).flatten() {
+            let Ok(commit) = repo.find_commit(oid) else {
+                continue;
+            };
+            counts
+                .entry(author_email(&commit, mailmap.as_ref()).to_lowercase())
+                .or_insert_with(|| (mapped_author_name(&commit, mailmap.as_ref()), 0))
+                .1 += 1;
+        }
+        let mut authors: Vec<(String, String, usize)> = counts
+            .into_iter()
+            .map(|(email, (name, count))| (name, email, count))
+            .collect();
+        authors.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        authors
+            .into_iter()
+            .map(|(name, email, _)| format!("{} <{}>", name, email))
+            .collect()
+    }
+
+    /// 現在のブランチのコミット数、ブランチ数、貢献者数（作者メールの種類）、最初と最後のコミット日
+    pub fn get_repo_stats(&self) -> Result<RepoStats, GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        let branch_count = repo.branches(Some(BranchType::Local))?.count() as i32;
+
+        // 未コミットのリポジトリ（HEADが解決できない）なら全て0
+        let Ok(head) = repo.head().and_then(|h| h.peel_to_commit()) else {
+            return Ok(RepoStats {
+                branch_count,
+                ..Default::default()
+            });
+        };
+
+        let mailmap = repo.mailmap().ok();
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(head.id())?;
+
+        let mut commit_count = 0;
+        let mut emails = std::collections::HashSet::new();
+        let mut first: Option<i64> = None;
+        let mut last: Option<i64> = None;
+        for oid in revwalk.flatten() {
+            let Ok(commit) = repo.find_commit(oid) else {
+                continue;
+            };
+            commit_count += 1;
+            emails.insert(author_email(&commit, mailmap.as_ref()).to_lowercase());
+            let time = commit.time().seconds();
+            first = Some(first.map_or(time, |t| t.min(time)));
+            last = Some(last.map_or(time, |t| t.max(time)));
+        }
+
+        let format_date = |secs: Option<i64>| {
+            secs.and_then(|s| Local.timestamp_opt(s, 0).single())
+                .map(|d| d.format("%Y-%m-%d").to_string())
+                .unwrap_or_default()
+        };
+        Ok(RepoStats {
+            commit_count,
+            branch_count,
+            contributor_count: emails.len() as i32,
+            first_commit: format_date(first).into(),
+            last_commit: format_date(last).into(),
+        })
+    }
+
+    /// 参照のreflogを新しい順に取得
+    pub fn get_reflog(&self, ref_name: &str) -> Vec<ReflogEntry> {
+        let Some(repo) = &self.repo else {
+            return vec![];
+        };
+        let Ok(reflog) = repo.reflog(ref_name) else {
+            return vec![];
+        };
+        reflog
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let oid = entry.id_new().to_string();
+                let datetime: DateTime<Local> = Local
+                    .timestamp_opt(entry.committer().when().seconds(), 0)
+                    .single()
+                    .unwrap_or_else(Local::now);
+                ReflogEntry {
+                    selector: format!("{}@{{{}}}", ref_name, i).into(),
+                    hash: oid[..7].into(),
+                    full_hash: oid.into(),
+                    message: entry.message().unwrap_or("").into(),
+                    date: datetime.format("%d %b %H:%M").to_string().into(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn stash_save(&mut self, message: &str, include_untracked: bool) -> Result<(), GitError> {
+        let Some(repo) = &mut self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let signature = repo.signature()?;
+        let mut flags = git2::StashFlags::DEFAULT;
+        if include_untracked {
+            flags.insert(git2::StashFlags::INCLUDE_UNTRACKED);
+        }
+        repo.stash_save(&signature, message, Some(flags))
+            .map(|_| ())
+            .map_err(GitError::from)
+    }
+
+    /// スタッシュを3-wayマージで適用（スタッシュは残す）
+    /// コミット済みの変更と競合した場合はマーカー付きで書き出し、コンフリクトのファイル一覧を返す
+    pub fn stash_apply(&mut self, index: usize) -> Result<StashApplyOutcome, GitError> {
+        let Some(repo) = &mut self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        // 作業ツリーの未コミットの変更は上書きしない
+        let mut checkout = git2::build::CheckoutBuilder::new();
+        checkout
+            .safe()
+            .allow_conflicts(true)
+            .conflict_style_merge(true);
+        let mut options = git2::StashApplyOptions::new();
+        options.checkout_options(checkout);
+
+        if let Err(e) = repo.stash_apply(index, Some(&mut options)) {
+            if e.code() == git2::ErrorCode::Conflict {
+                let overlapping = stash_overlapping_changes(repo, index);
+                if !overlapping.is_empty() {
+                    return Err(format!(
+                        "Local changes would be overwritten: {}; commit or stash them first",
+                        overlapping.join(", ")
+                    )
+                    .into());
+                }
+            }
+            return Err(e.into());
+        }
+
+        let mut repo_index = repo.index()?;
+        repo_index.read(true)?;
+        let conflicts: Vec<String> = repo_index
+            .conflicts()?
+            .flatten()
+            .filter_map(|c| c.our.or(c.their).or(c.ancestor))
+            .map(|entry| String::from_utf8_lossy(&entry.path).to_string())
+            .collect();
+        if conflicts.is_empty() {
+            Ok(StashApplyOutcome::Clean)
+        } else {
+            Ok(StashApplyOutcome::Conflicts(conflicts))
+        }
+    }
+
+    /// スタッシュを適用して削除（コンフリクトが出た場合は失わないようにスタッシュを残す）
+    pub fn stash_pop(&mut self, index: usize) -> Result<StashApplyOutcome, GitError> {
+        let outcome = self.stash_apply(index)?;
+        if outcome == StashApplyOutcome::Clean {
+            self.stash_drop(index)?;
+        }
+        Ok(outcome)
+    }
+
+    pub fn stash_drop(&mut self, index: usize) -> Result<(), GitError> {
+        let Some(repo) = &mut self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        repo.stash_drop(index).map_err(GitError::from)
+    }
+
+    /// スタッシュのメッセージを変更
+    /// git2では直接変更できないため、同じスタッシュコミットを新しいメッセージで記録し直して古い記録を削除する
+    /// （コミット自体は再利用するので、保存されたツリーや未追跡ファイルはそのまま。一覧の位置も保つ）
+    pub fn stash_rename(&mut self, index: usize, new_message: &str) -> Result<(), GitError> {
+        let Some(repo) = &mut self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let new_message = new_message.trim();
+        if new_message.is_empty() {
+            return Err("Stash message is empty".into());
+        }
+        let mut reflog = repo.reflog("refs/stash")?;
+        if index >= reflog.len() {
+            return Err(GitError::NotFound(format!("No stash at index {}", index)));
+        }
+
+        // 新しい順に (スタッシュコミット, 記録者, メッセージ) を集めて対象だけ差し替える
+        let entries: Vec<(Oid, Signature<'static>, String)> = reflog
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let message = entry.message().unwrap_or_default();
+                let message = if i == index {
+                    renamed_stash_message(message, new_message)
+                } else {
+                    message.to_string()
+                };
+                (entry.id_new(), entry.committer().to_owned(), message)
+            })
+            .collect();
+        let stash_oid = entries[index].0;
+        repo.find_commit(stash_oid)?;
+
+        while !reflog.is_empty() {
+            reflog.remove(0, false)?;
+        }
+        // 古い順に追記し直す（refs/stash の指す先は変わらない）
+        for (oid, committer, message) in entries.iter().rev() {
+            reflog.append(*oid, committer, Some(message))?;
+        }
+        reflog.write().map_err(GitError::from)
+    }
+
+    pub fn get_commit_file_diff(&self, oid: &str, file_index: usize) -> (Vec<DiffLineData>, usize) {
+        let Some(repo) = &self.repo else {
+            return (vec![], 0);
+        };
+
+        if oid.is_empty() {
+            return (vec![], 0);
+        }
+
+        let Ok(commit) = repo.find_commit(Oid::from_str(oid).unwrap_or(Oid::zero())) else {
+            return (vec![], 0);
+        };
+        let Ok(tree) = commit.tree() else {
+            return (vec![], 0);
+        };
+
+        let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+
+        diff_trees_file(
+            repo,
+            parent_tree.as_ref(),
+            &tree,
+            file_index,
+            self.diff_context_lines,
+        )
+    }
+
+    /// 2つのコミットを (古い方, 新しい方) の順に並べる
+    /// 祖先関係があればそれに従い、なければコミット日時で判断する
+    pub fn order_commits(&self, a: &str, b: &str) -> (String, String) {
+        let ordered = (a.to_string(), b.to_string());
+        let swapped = (b.to_string(), a.to_string());
+        let Some(repo) = &self.repo else {
+            return ordered;
+        };
+        let (Ok(oid_a), Ok(oid_b)) = (Oid::from_str(a), Oid::from_str(b)) else {
+            return ordered;
+        };
+        if repo.graph_descendant_of(oid_a, oid_b).unwrap_or(false) {
+            return swapped;
+        }
+        if repo.graph_descendant_of(oid_b, oid_a).unwrap_or(false) {
+            return ordered;
+        }
+        let time = |oid| {
+            repo.find_commit(oid)
+                .map(|c| c.time().seconds())
+                .unwrap_or(0)
+        };
+        if time(oid_a) > time(oid_b) {
+            swapped
+        } else {
+            ordered
+        }
+    }
+
+    /// 任意の2つのリビジョン（コミット・ブランチ名など）間のDiffを取得
+    pub fn diff_refs(&self, from: &str, to: &str) -> TreeDiff {
+        let Some(repo) = &self.repo else {
+            return Default::default();
+        };
+        let (Ok(from_tree), Ok(to_tree)) = (
+            repo.revparse_single(from).and_then(|o| o.peel_to_tree()),
+            repo.revparse_single(to).and_then(|o| o.peel_to_tree()),
+        ) else {
+            return Default::default();
+        };
+
+        diff_trees(repo, Some(&from_tree), &to_tree, self.diff_context_lines)
+    }
+
+    /// 2つのリビジョン間のDiffのうち、file_index番目のファイルのDiff内容を取得
+    pub fn get_refs_file_diff(
+        &self,
+        from: &str,
+        to: &str,
+        file_index: usize,
+    ) -> (Vec<DiffLineData>, usize) {
+        let Some(repo) = &self.repo else {
+            return (vec![], 0);
+        };
+        let (Ok(from_tree), Ok(to_tree)) = (
+            repo.revparse_single(from).and_then(|o| o.peel_to_tree()),
+            repo.revparse_single(to).and_then(|o| o.peel_to_tree()),
+        ) else {
+            return (vec![], 0);
+        };
+
+        diff_trees_file(
+            repo,
+            Some(&from_tree),
+            &to_tree,
+            file_index,
+            self.diff_context_lines,
+        )
+    }
+
+    /// 指定コミット時点のファイル内容を取得（バイナリの場合はGitError::BinaryFile）
+    pub fn get_file_at_commit(&self, hash: &str, path: &str) -> Result<String, GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        let oid = Oid::from_str(hash)?;
+        let commit = repo.find_commit(oid)?;
+        let tree = commit.tree()?;
+        let entry = tree
+            .get_path(Path::new(path))
+            .map_err(|_| GitError::NotFound(format!("{} does not exist at this revision", path)))?;
+        let blob = repo.find_blob(entry.id())?;
+
+        if blob.is_binary() {
+            return Err(GitError::BinaryFile);
+        }
+        String::from_utf8(blob.content().to_vec()).map_err(|_| GitError::BinaryFile)
+    }
+
+    pub fn get_file_diff(&self, filename: &str, staged: bool) -> (Vec<DiffLineData>, usize) {
+        let Some(repo) = &self.repo else {
+            return (vec![], 0);
+        };
+
+        let mut opts = DiffOptions::new();
+        opts.pathspec(filename);
+        opts.context_lines(self.diff_context_lines);
+
+        let diff = if staged {
+            let Ok(head_tree) = repo.head().and_then(|h| h.peel_to_tree()) else {
+                return (vec![], 0);
+            };
+            repo.diff_tree_to_index(Some(&head_tree), None, Some(&mut opts))
+        } else {
+            // Include untracked files in diff
+            opts.include_untracked(true);
+
+            repo.diff_index_to_workdir(None, Some(&mut opts))
+        };
+
+        match diff {
+            Ok(d) => {
+                let (lines, total_lines) = self.parse_diff(&d);
+                // If no diff lines but it's an unstaged file, it might be untracked (new file)
+                // Read the file content directly and show as all additions
+                if lines.is_empty() && !staged {
+                    let lines = self.get_new_file_diff(repo, filename);
+                    let count = lines.len();
+                    return (lines, count);
+                }
+                (lines, total_lines)
+            }
+            Err(_) => {
+                // If diff failed and it's unstaged, try reading as new file
+                if !staged {
+                    let lines = self.get_new_file_diff(repo, filename);
+                    let count = lines.len();
+                    return (lines, count);
+                }
+                (vec![], 0)
+            }
+        }
+    }
+
+    /// StagedとUnstagedの差分を続けて表示する（一部だけステージしたファイル用）
+    pub fn get_combined_file_diff(&self, filename: &str) -> (Vec<DiffLineData>, usize) {
+        let (staged, staged_total) = self.get_file_diff(filename, true);
+        let (unstaged, unstaged_total) = self.get_file_diff(filename, false);
+        let lines = combine_staged_and_unstaged(staged, unstaged);
+        (lines, staged_total + unstaged_total + 2)
+    }
+
+    /// Get diff for a new (untracked) file by reading its contents
+    fn get_new_file_diff(&self, repo: &Repository, filename: &str) -> Vec<DiffLineData> {
+        let workdir = match repo.workdir() {
+            Some(w) => w,
+            None => return vec![],
+        };
+
+        let file_path = workdir.join(filename);
+        let content = match fs::read_to_string(&file_path) {
+            Ok(c) => c,
+            Err(_) => {
+                // Try reading as binary and show a placeholder message
+                match fs::read(&file_path) {
+                    Ok(_) => return vec![binary_file_placeholder()],
+                    Err(_) => return vec![],
+                }
+            }
+        };
+
+        let mut lines = vec![];
+
+        // Add file header
+        lines.push(DiffLineData {
+            content: "--- /dev/null".into(),
+            line_type: "diff".into(),
+            old_line_num: 0,
+            new_line_num: 0,
+            hunk_index: -1,
+            trailing_ws: false,
+            has_tab: false,
+        });
+        lines.push(DiffLineData {
+            content: format!("+++ {}", filename).into(),
+            line_type: "diff".into(),
+            old_line_num: 0,
+            new_line_num: 0,
+            hunk_index: -1,
+            trailing_ws: false,
+            has_tab: false,
+        });
+
+        // Add hunk header
+        let line_count = content.lines().count();
+        lines.push(DiffLineData {
+            content: format!("@@ -0,0 +1,{} @@", line_count).into(),
+            line_type: "@@".into(),
+            old_line_num: 0,
+            new_line_num: 0,
+            hunk_index: 0,
+            trailing_ws: false,
+            has_tab: false,
+        });
+
+        // Add all lines as additions
+        for (i, line) in content.lines().enumerate() {
+            lines.push(DiffLineData {
+                content: format!("+{}", line).into(),
+                line_type: "+".into(),
+                old_line_num: 0,
+                new_line_num: (i + 1) as i32,
+                hunk_index: 0,
+                trailing_ws: has_trailing_whitespace(line),
+                has_tab: line.contains('\t'),
+            });
+        }
+
+        lines
+    }
+
+    fn parse_diff(&self, diff: &git2::Diff) -> (Vec<DiffLineData>, usize) {
+        parse_diff_standalone(diff)
+    }
+
+    /// ファイルのUnstaged diffをhunkごとに取得（ヘッダー行と "+"/"-"/" " 付きの行）
+    fn unstaged_hunks(&self, filename: &str) -> Result<Vec<(String, Vec<String>)>, GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        // Unstaged diffを取得
+        let mut opts = DiffOptions::new();
+        opts.pathspec(filename);
+        opts.context_lines(self.diff_context_lines);
+
+        let diff = repo.diff_index_to_workdir(None, Some(&mut opts))?;
+
+        let mut hunks: Vec<(String, Vec<String>)> = vec![];
+        let _ = diff.print(git2::DiffFormat::Patch, |_delta, hunk, line| {
+            match line.origin() {
+                'H' => {
+                    // Hunkヘッダー
+                    let header = hunk
+                        .and_then(|h| std::str::from_utf8(h.header()).ok())
+                        .map(|header| header.trim_end().to_string())
+                        .unwrap_or_default();
+                    hunks.push((header, vec![]));
+                }
+                '+' | '-' | ' ' => {
+                    if let (Some((_, lines)), Ok(content)) =
+                        (hunks.last_mut(), std::str::from_utf8(line.content()))
+                    {
+                        lines.push(format!("{}{}", line.origin(), content));
+                    }
+                }
+                _ => {}
+            }
+            true
+        });
+        Ok(hunks)
+    }
+
+    /// 指定したhunkだけのパッチを git apply --cached でインデックスに適用
+    fn apply_hunks_to_index(
+        &self,
+        filename: &str,
+        hunks: &[(String, Vec<String>)],
+    ) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        // パッチを生成
+        let mut patch =
+            format!("diff --git a/{filename} b/{filename}\n--- a/{filename}\n+++ b/{filename}\n");
+        for (header, lines) in hunks {
+            patch.push_str(header);
+            patch.push('\n');
+            patch.push_str(&lines.join(""));
+        }
+
+        // git applyでパッチを適用（--cachedでインデックスに適用）
+        use std::io::Write;
+        let workdir = repo.workdir().ok_or("No workdir")?;
+        let mut child = create_git_command()
+            .args(["apply", "--cached", "-"])
+            .current_dir(workdir)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(patch.as_bytes())?;
+        }
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("Failed to stage hunk: {}", stderr).into());
+        }
+
+        Ok(())
+    }
+
+    /// 特定のHunkをステージングする
+    pub fn stage_hunk(&self, filename: &str, hunk_index: usize) -> Result<(), GitError> {
+        let hunks = self.unstaged_hunks(filename)?;
+        let Some(hunk) = hunks.get(hunk_index) else {
+            return Err(GitError::NotFound("Hunk not found".into()));
+        };
+        self.apply_hunks_to_index(filename, std::slice::from_ref(hunk))
+    }
+
+    /// 指定したhunk以外をすべてステージ（デバッグ用の変更だけ残したいときなど）
+    pub fn stage_all_hunks_except(&self, filename: &str, skip_hunk: usize) -> Result<(), GitError> {
+        let mut hunks = self.unstaged_hunks(filename)?;
+        if skip_hunk >= hunks.len() {
+            return Err(GitError::NotFound("Hunk not found".into()));
+        }
+        hunks.remove(skip_hunk);
+        if hunks.is_empty() {
+            return Err("No other hunks to stage".into());
+        }
+        self.apply_hunks_to_index(filename, &hunks)
+    }
+
+    pub fn discard_file(&self, filename: &str) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        // Check if the file is untracked (new file)
+        let mut opts = StatusOptions::new();
+        opts.include_untracked(true);
+        // まとめた未追跡ディレクトリ（"dir/"）はディレクトリの行として探す
+        opts.recurse_untracked_dirs(!filename.ends_with('/'));
+
+        if let Ok(statuses) = repo.statuses(Some(&mut opts)) {
+            for entry in statuses.iter() {
+                let path = entry.path().unwrap_or("");
+                // Compare the path with the filename (untracked dirs may end with '/')
+                if path.trim_end_matches('/') == filename.trim_end_matches('/') {
+                    let status = entry.status();
+                    if status.is_wt_new() {
+                        // Untracked file or directory - delete it directly
+                        let workdir = repo.workdir().ok_or("No workdir")?;
+                        let file_path = workdir.join(filename.trim_end_matches('/'));
+                        // 取り消し用に中身を保存（ディレクトリは中のファイルをすべて）
+                        let files = snapshot_untracked(workdir, &file_path)
+                            .map_err(|e| format!("Failed to save {} for undo: {}", filename, e))?;
+                        if file_path.is_dir() {
+                            fs::remove_dir_all(&file_path)
+                                .map_err(|e| format!("Failed to delete directory: {}", e))?;
+                        } else {
+                            fs::remove_file(&file_path)
+                                .map_err(|e| format!("Failed to delete file: {}", e))?;
+                        }
+                        remove_empty_parent_dirs(workdir, &file_path);
+                        self.push_undo(UndoAction::DiscardUntracked {
+                            path: filename.to_string(),
+                            files,
+                        });
+                        return Ok(());
+                    }
+                    break;
+                }
+            }
+        }
+
+        // For tracked files, restore from HEAD
+        let content = repo
+            .workdir()
+            .and_then(|workdir| fs::read(workdir.join(filename)).ok());
+        let index_entry = repo.index()?.get_path(Path::new(filename), 0);
+        let mut checkout_opts = git2::build::CheckoutBuilder::new();
+        checkout_opts.path(filename);
+        checkout_opts.force();
+
+        repo.checkout_head(Some(&mut checkout_opts))?;
+        self.push_undo(UndoAction::Discard {
+            path: filename.to_string(),
+            content,
+            index_entry,
+        });
+        Ok(())
+    }
+
+    /// ファイルをトップレベルの.gitignoreに追加（なければ作成）
+    pub fn add_to_gitignore(&self, filename: &str) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let workdir = repo.workdir().ok_or("No workdir")?;
+        let gitignore_path = workdir.join(".gitignore");
+
+        let existing = fs::read_to_string(&gitignore_path).unwrap_or_default();
+        // ルートからのパスとして登録（同名の別ディレクトリのファイルは対象外）
+        let entry = format!("/{}", filename.trim_start_matches('/'));
+        if existing.lines().any(|l| l.trim() == entry) {
+            return Ok(());
+        }
+
+        let mut content = existing;
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&entry);
+        content.push('\n');
+        fs::write(&gitignore_path, content).map_err(GitError::from)
+    }
+
+    /// プッシュ対象（ワークツリーのパス, 現在のブランチ名）を取得
+    /// detached HEADではプッシュできないのでエラーにする
+    pub fn push_target(&self) -> Result<(String, String), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        let workdir = repo.workdir().ok_or("No workdir")?;
+        if let Some(hash) = self.get_detached_head() {
+            return Err(format!(
+                "HEAD is detached at {}; create a branch before pushing",
+                hash
+            )
+            .into());
+        }
+        let branch = self.get_current_branch();
+        if branch.is_empty() {
+            return Err("No current branch".into());
+        }
+        Ok((workdir.to_string_lossy().to_string(), branch))
+    }
+
+    /// GitHubのリポジトリURLを取得
+    fn get_github_url(&self) -> Option<String> {
+        let repo = self.repo.as_ref()?;
+        let remote = repo.find_remote("origin").ok()?;
+        let url = remote.url()?;
+
+        // SSH形式 (git@github.com:user/repo.git) をHTTPS形式に変換
+        if url.starts_with("git@github.com:") {
+            let path = url
+                .strip_prefix("git@github.com:")?
+                .strip_suffix(".git")
+                .unwrap_or(url.strip_prefix("git@github.com:")?);
+            return Some(format!("https://github.com/{}", path));
+        }
+
+        // HTTPS形式 (https://github.com/user/repo.git)
+        if url.starts_with("https://github.com/") {
+            let clean_url = url.strip_suffix(".git").unwrap_or(url);
+            return Some(clean_url.to_string());
+        }
+
+        None
+    }
+
+    /// Pull Request作成URLを生成
+    pub fn get_pull_request_url(&self, branch_name: &str) -> Option<String> {
+        let github_url = self.get_github_url()?;
+        // GitHub PR作成URL: https://github.com/user/repo/compare/main...branch?expand=1
+        Some(format!(
+            "{}/compare/main...{}?expand=1",
+            github_url, branch_name
+        ))
+    }
+
+    /// コミットのGitHub URLを生成
+    pub fn get_commit_github_url(&self, commit_hash: &str) -> Option<String> {
+        let github_url = self.get_github_url()?;
+        Some(format!("{}/commit/{}", github_url, commit_hash))
+    }
+
+    /// 指定したコミットにリセット
+    pub fn reset_to_commit(&self, commit_hash: &str, mode: &str) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        let obj = repo.revparse_single(commit_hash)?;
+        let commit = obj.peel_to_commit()?;
+
+        let reset_type = match mode {
+            "soft" => git2::ResetType::Soft,
+            "hard" => git2::ResetType::Hard,
+            _ => git2::ResetType::Mixed,
+        };
+        let old_head = repo.head().ok().and_then(|h| h.target());
+
+        repo.reset(commit.as_object(), reset_type, None)?;
+        if let (git2::ResetType::Hard, Some(old_head)) = (reset_type, old_head) {
+            if old_head != commit.id() {
+                self.push_undo(UndoAction::HardReset {
+                    branch: self.get_current_branch(),
+                    old_head,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// 取り消し履歴に追加（上限を超えたら古いものから捨てる）
+    fn push_undo(&self, action: UndoAction) {
+        let mut stack = self.undo_stack.borrow_mut();
+        stack.push(action);
+        if stack.len() > MAX_UNDO_ACTIONS {
+            stack.remove(0);
+        }
+    }
+
+    /// 次に取り消される操作の説明（なければ空）
+    pub fn undo_description(&self) -> String {
+        self.undo_stack
+            .borrow()
+            .last()
+            .map(|action| action.describe())
+            .unwrap_or_default()
+    }
+
+    /// 直近の破壊的な操作を取り消す。戻り値は取り消した操作の説明
+    /// 失敗した場合は履歴に戻して再試行できるようにする
+    pub fn undo_last_action(&self) -> Result<String, GitError> {
+        let Some(action) = self.undo_stack.borrow_mut().pop() else {
+            return Err("Nothing to undo".into());
+        };
+        match self.apply_undo(&action) {
+            Ok(()) => Ok(action.describe()),
+            Err(e) => {
+                self.undo_stack.borrow_mut().push(action);
+                Err(e)
+            }
+        }
+    }
+
+    fn apply_undo(&self, action: &UndoAction) -> Result<(), GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        match action {
+            UndoAction::Discard {
+                path,
+                content,
+                index_entry,
+            } => {
+                let workdir = repo.workdir().ok_or("No workdir")?;
+                let file_path = workdir.join(path);
+                match content {
+                    Some(content) => {
+                        if let Some(parent) = file_path.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        fs::write(&file_path, content)?;
+                    }
+                    None => fs::remove_file(&file_path)?,
+                }
+                let mut index = repo.index()?;
+                match index_entry {
+                    Some(entry) => index.add(entry)?,
+                    None => index.remove_path(Path::new(path))?,
+                }
+                index.write().map_err(GitError::from)
+            }
+            UndoAction::DiscardUntracked { files, .. } => {
+                let workdir = repo.workdir().ok_or("No workdir")?;
+                for (path, content) in files {
+                    let file_path = workdir.join(path);
+                    match content {
+                        Some(content) => {
+                            if let Some(parent) = file_path.parent() {
+                                fs::create_dir_all(parent)?;
+                            }
+                            fs::write(&file_path, content)?;
+                        }
+                        None => fs::create_dir_all(&file_path)?,
+                    }
+                }
+                Ok(())
+            }
+            UndoAction::DeleteBranch {
+                name,
+                oid,
+                upstream,
+            } => {
+                let commit = repo.find_commit(*oid)?;
+                let mut branch = repo.branch(name, &commit, false)?;
+                if let Some(upstream) = upstream {
+                    branch.set_upstream(Some(upstream))?;
+                }
+                Ok(())
+            }
+            UndoAction::HardReset { branch, old_head } => {
+                if self.get_current_branch() != *branch {
+                    return Err(format!(
+                        "Cannot undo reset: switched away from {}",
+                        if branch.is_empty() {
+                            "detached HEAD"
+                        } else {
+                            branch
+                        }
+                    )
+                    .into());
+                }
+                // リセット後に加えたローカルの変更を消さないよう、変更があれば戻さない
+                let mut opts = StatusOptions::new();
+                opts.include_untracked(false);
+                if !repo.statuses(Some(&mut opts))?.is_empty() {
+                    return Err(
+                        "Cannot undo reset: commit or stash your local changes first".into(),
+                    );
+                }
+                let commit = repo.find_commit(*old_head)?;
+                repo.reset(commit.as_object(), git2::ResetType::Hard, None)
+                    .map_err(GitError::from)
+            }
+        }
+    }
+
+    /// コミットをリバート（打ち消しコミットを作成）
+    /// no_commitがtrueなら変更をステージするだけでコミットしない。戻り値はリバートのコミットメッセージ
+    pub fn revert_commit(&self, commit_hash: &str, no_commit: bool) -> Result<String, GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        let obj = repo.revparse_single(commit_hash)?;
+        let commit = obj.peel_to_commit()?;
+
+        // リバートを実行
+        let mut revert_opts = git2::RevertOptions::new();
+        repo.revert(&commit, Some(&mut revert_opts))?;
+
+        let message = format!("Revert \"{}\"", commit.summary().unwrap_or(""));
+        let mut index = repo.index()?;
+        if index.has_conflicts() {
+            // リバート中の状態のまま止め、解決後に手動でコミットしてもらう
+            return Err(format!(
+                "Revert of {} has conflicts; resolve them and commit",
+                &commit.id().to_string()[..7]
+            )
+            .into());
+        }
+        if no_commit {
+            // 変更はインデックスに残し、リバート中の状態だけ解除する（後で手動コミット）
+            repo.cleanup_state()?;
+            return Ok(message);
+        }
+
+        // 自動コミット
+        let sig = repo.signature()?;
+        let tree_oid = index.write_tree()?;
+        let tree = repo.find_tree(tree_oid)?;
+        let head = repo.head()?;
+        let parent = head.peel_to_commit()?;
+
+        repo.commit(Some("HEAD"), &sig, &sig, &message, &tree, &[&parent])?;
+
+        Ok(message)
+    }
+
+    /// 履歴上のfrom〜toの範囲（両端を含む）のコミットを取得
+    pub fn commits_in_range(&self, from: &str, to: &str) -> Result<Vec<String>, GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let from_commit = repo
+            .revparse_single(from)
+            .and_then(|o| o.peel_to_commit())?;
+        let to_oid = repo.revparse_single(to)?.id();
+
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(to_oid)?;
+        for parent in from_commit.parent_ids() {
+            revwalk.hide(parent)?;
+        }
+        Ok(revwalk.flatten().map(|oid| oid.to_string()).collect())
+    }
+
+    /// コミットをgit format-patch形式のテキストにする（メールで送れるパッチ）
+    pub fn export_commit_patch(&self, hash: &str) -> Result<String, GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        let workdir = repo.workdir().ok_or("No workdir")?;
+        let output = create_git_command()
+            .args(["format-patch", "-1", "--stdout", hash])
+            .current_dir(workdir)
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("format-patch failed: {}", stderr.trim()).into());
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// 範囲（from〜to、両端を含む）のコミットを1コミット1ファイルのパッチとしてdirに書き出す
+    /// 戻り値は書き出したファイル数（マージコミットはformat-patchの仕様で含まれない）
+    pub fn export_range_patches(
+        &self,
+        from: &str,
+        to: &str,
+        dir: &Path,
+    ) -> Result<usize, GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        let workdir = repo.workdir().ok_or("No workdir")?;
+        let from_commit = repo
+            .revparse_single(from)
+            .and_then(|o| o.peel_to_commit())?;
+
+        // fromの親を除外して範囲を指定（ルートコミットなら--root）
+        let mut cmd = create_git_command();
+        cmd.arg("format-patch").arg("-o").arg(dir);
+        if from_commit.parent_count() == 0 {
+            cmd.arg("--root");
+        }
+        cmd.arg(to);
+        for parent in from_commit.parent_ids() {
+            cmd.arg(format!("^{}", parent));
+        }
+        let output = cmd
+            .current_dir(workdir)
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("format-patch failed: {}", stderr.trim()).into());
+        }
+
+        // format-patchは書き出したファイル名を1行ずつ出力する
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .count())
+    }
+
+    /// 複数のコミットを新しい順にリバート
+    /// combineがfalseならコミットごとに打ち消しコミットを作成し、trueなら1つのコミットにまとめる。
+    /// 競合した場合はそこで止め、どのコミットで失敗したかを返す。戻り値はリバートしたコミット数
+    pub fn revert_commits(&self, hashes: &[String], combine: bool) -> Result<usize, GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+        let wanted: Vec<Oid> = hashes
+            .iter()
+            .map(|h| {
+                repo.revparse_single(h)
+                    .and_then(|o| o.peel_to_commit())
+                    .map(|c| c.id())
+                    .map_err(GitError::from)
+            })
+            .collect::<Result<_, _>>()?;
+        if wanted.is_empty() {
+            return Ok(0);
+        }
+
+        // 子孫が先になるように並べる（新しいコミットから順に打ち消す）
+        let mut revwalk = repo.revwalk()?;
+        revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+        for oid in &wanted {
+            revwalk.push(*oid)?;
+        }
+        let ordered: Vec<Oid> = revwalk
+            .flatten()
+            .filter(|oid| wanted.contains(oid))
+            .take(wanted.len())
+            .collect();
+
+        if !combine {
+            for oid in &ordered {
+                let hash = oid.to_string();
+                self.revert_commit(&hash, false)
+                    .map_err(|e| format!("Revert of {} failed: {}", &hash[..7], e))?;
+            }
+            return Ok(ordered.len());
+        }
+
+        // まとめる場合はHEADのツリーに対して順に打ち消しをマージし、最後に1回だけコミットする
+        let head = repo.head().and_then(|h| h.peel_to_commit())?;
+        let mut tree = head.tree()?;
+        let mut messages = vec![];
+        for oid in &ordered {
+            let commit = repo.find_commit(*oid)?;
+            let short = &oid.to_string()[..7];
+            if commit.parent_count() != 1 {
+                return Err(format!("Cannot revert {}: not a single-parent commit", short).into());
+            }
+            let parent_tree = commit.parent(0).and_then(|p| p.tree())?;
+            let commit_tree = commit.tree()?;
+            let mut merged = repo.merge_trees(&commit_tree, &tree, &parent_tree, None)?;
+            if merged.has_conflicts() {
+                return Err(format!("Revert of {} conflicts; nothing was committed", short).into());
+            }
+            let tree_oid = merged.write_tree_to(repo)?;
+            tree = repo.find_tree(tree_oid)?;
+            messages.push(format!(
+                "Revert \"{}\"\n\nThis reverts commit {}.",
+                commit.summary().unwrap_or(""),
+                oid
+            ));
+        }
+
+        // 作業ツリーとインデックスを結果のツリーに合わせる（ローカルの変更と衝突する場合は中止）
+        let mut checkout_opts = git2::build::CheckoutBuilder::new();
+        checkout_opts.safe();
+        repo.checkout_tree(tree.as_object(), Some(&mut checkout_opts))?;
+        let mut index = repo.index()?;
+        index.read_tree(&tree)?;
+        index.write()?;
+
+        let message = if messages.len() == 1 {
+            messages.remove(0)
+        } else {
+            format!(
+                "Revert {} commits\n\n{}",
+                messages.len(),
+                messages.join("\n\n")
+            )
+        };
+        let sig = repo.signature()?;
+        repo.commit(Some("HEAD"), &sig, &sig, &message, &tree, &[&head])?;
+
+        Ok(ordered.len())
+    }
+
+    /// git bisectを実行して標準出力を返す
+    fn run_bisect(&self, args: &[&str]) -> Result<String, GitError> {
+        let Some(repo) = &self.repo else {
+            return Err(GitError::NoRepository);
+        };
+
+        let workdir = repo.workdir().ok_or("No workdir")?;
+        let output = create_git_command()
+            .arg("bisect")
+            .args(args)
+            .current_dir(workdir)
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("Bisect failed: {}", stderr.trim()).into());
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    pub fn bisect_start(&self) -> Result<(), GitError> {
+        self.run_bisect(&["start"]).map(|_| ())
+    }
+
+    pub fn bisect_mark_good(&self, hash: &str) -> Result<BisectProgress, GitError> {
+        self.run_bisect(&["good", hash])
+            .map(|output| parse_bisect_output(&output))
+    }
+
+    pub fn bisect_mark_bad(&self, hash: &str) -> Result<BisectProgress, GitError> {
+        self.run_bisect(&["bad", hash])
+            .map(|output| parse_bisect_output(&output))
+    }
+
+    /// bisectを終了して開始前のブランチに戻る
+    pub fn bisect_reset(&self) -> Result<(), GitError> {
+        self.run_bisect(&["reset"]).map(|_| ())
+    }
+
+    /// bisect中なら (現在テスト中のコミット, badとマークしたコミット) を返す
+    pub fn bisect_hashes(&self) -> Option<(String, String)> {
+        let repo = self.repo.as_ref()?;
+        if repo.state() != git2::RepositoryState::Bisect {
+            return None;
+        }
+        let head = repo
+            .head()
+            .ok()
+            .and_then(|h| h.target())
+            .map(|oid| oid.to_string())
+            .unwrap_or_default();
+        let bad = repo
+            .refname_to_id("refs/bisect/bad")
+            .map(|oid| oid.to_string())
+            .unwrap_or_default();
+        Some((head, bad))
+    }
+
+    /// インデックスからコミットハッシュを取得（表示中のグラフの行。Uncommitted行ならNone）
+    /// グラフは絞り込まれている場合があるので、計算済みのグラフから引く
+    pub fn get_commit_hash_by_index(&self, index: usize) -> Option<String> {
+        let guard = self.graph_cache.lock().ok()?;
+        let row = guard.as_ref()?.commits.get(index)?;
+        (!row.is_uncommitted).then(|| row.full_hash.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::build_commit_graph;
+    use crate::tests::init_test_repo;
+
+    #[test]
+    fn bisect_output_reports_remaining_steps() {
+        let output = "Bisecting: 6 revisions left to test after this (roughly 3 steps)\n\
+                      [0123456789abcdef0123456789abcdef01234567] Add feature\n";
+        assert_eq!(
+            parse_bisect_output(output),
+            BisectProgress::Testing { steps: 3 }
+        );
+    }
+
+    #[test]
+    fn bisect_output_reports_first_bad_commit() {
+        let output = "0123456789abcdef0123456789abcdef01234567 is the first bad commit\n\
+                      commit 0123456789abcdef0123456789abcdef01234567\n";
+        assert_eq!(
+            parse_bisect_output(output),
+            BisectProgress::Found("0123456789abcdef0123456789abcdef01234567".into())
+        );
+    }
+
+    #[test]
+    fn bisect_output_reports_waiting_status() {
+        let output = "status: waiting for good commit(s), bad commit known\n";
+        assert_eq!(
+            parse_bisect_output(output),
+            BisectProgress::Waiting("waiting for good commit(s), bad commit known".into())
+        );
+    }
+
+    #[test]
+    fn patch_file_name_follows_format_patch_rules() {
+        assert_eq!(
+            patch_file_name(1, "Fix typo in README"),
+            "0001-Fix-typo-in-README.patch"
+        );
+        assert_eq!(
+            patch_file_name(2, "feat: add  x/y support!"),
+            "0002-feat-add-x-y-support.patch"
+        );
+        assert_eq!(patch_file_name(3, "日本語"), "0003.patch");
+        let long = patch_file_name(1, &"a".repeat(80));
+        assert_eq!(long, format!("0001-{}.patch", "a".repeat(52)));
+    }
+
+    #[test]
+    fn push_rejection_is_detected_from_stderr() {
+        let stderr = "To github.com:me/repo.git\n ! [rejected]        main -> main (fetch first)\nerror: failed to push some refs\n";
+        assert!(is_push_rejected(stderr));
+        assert!(is_push_rejected(
+            " ! [rejected] main -> main (non-fast-forward)\nhint: Updates were rejected because the tip of your current branch is behind"
+        ));
+        assert!(!is_push_rejected(
+            " ! [remote rejected] main -> main (protected branch hook declined)"
+        ));
+        assert!(!is_push_rejected("fatal: Authentication failed"));
+    }
+
+    #[test]
+    fn remote_errors_are_classified_into_git_error_variants() {
+        let err = remote_command_error("Push", "fatal: Authentication failed for 'https://x'");
+        assert!(matches!(&err, GitError::AuthFailed(action) if action == "Push"));
+        assert_eq!(
+            err.to_string(),
+            "Authentication failed (Push): check your credential helper or SSH key"
+        );
+        let err = remote_command_error("Fetch", "fatal: unable to access\n");
+        assert!(matches!(err, GitError::Other(_)));
+        assert_eq!(err.to_string(), "Fetch failed: fatal: unable to access");
+        assert!(matches!(
+            GitClient::new().stage_all(),
+            Err(GitError::NoRepository)
+        ));
+    }
+
+    #[test]
+    fn graph_can_be_filtered_by_author_and_branch() {
+        let (dir, repo, first) = init_test_repo("graph-filter");
+        let other = Signature::now("Other", "other@example.com").unwrap();
+        let base = repo.find_commit(first).unwrap();
+        let tree = base.tree().unwrap();
+        let by_other = repo
+            .commit(Some("HEAD"), &other, &other, "by other", &tree, &[&base])
+            .unwrap();
+        repo.branch("side", &base, false).unwrap();
+        let on_side = repo
+            .commit(
+                Some("refs/heads/side"),
+                &other,
+                &other,
+                "on side",
+                &tree,
+                &[&base],
+            )
+            .unwrap();
+        drop((tree, base));
+        drop(repo);
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        let hashes = |client: &GitClient| {
+            let key = client.graph_cache_key(10, 0).unwrap();
+            let (rows, _) = build_commit_graph(key.repo_path.as_deref().unwrap(), &key, 0);
+            rows.iter()
+                .map(|r| r.full_hash.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        client.set_graph_filter("", "side");
+        assert_eq!(
+            hashes(&client),
+            vec![on_side.to_string(), first.to_string()]
+        );
+        // HEADのブランチだけ（sideより優先）
+        client.set_current_branch_only(true);
+        assert_eq!(
+            hashes(&client),
+            vec![by_other.to_string(), first.to_string()]
+        );
+        client.set_graph_filter("other@example.com", "");
+        assert_eq!(hashes(&client), vec![by_other.to_string()]);
+        client.set_current_branch_only(false);
+        client.set_graph_filter("", "");
+        assert_eq!(hashes(&client).len(), 3);
+    }
+
+    #[test]
+    fn repo_stats_counts_commits_and_contributors() {
+        let (dir, repo, first) = init_test_repo("repo-stats");
+        let other = Signature::now("Other", "Other@Example.com").unwrap();
+        let parent = repo.find_commit(first).unwrap();
+        let tree = parent.tree().unwrap();
+        repo.commit(Some("HEAD"), &other, &other, "second", &tree, &[&parent])
+            .unwrap();
+        drop((tree, parent));
+        drop(repo);
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        let stats = client.get_repo_stats().unwrap();
+        assert_eq!(stats.commit_count, 2);
+        assert_eq!(stats.branch_count, 1);
+        assert_eq!(stats.contributor_count, 2);
+        assert!(!stats.first_commit.is_empty());
+        assert!(stats.first_commit.as_str() <= stats.last_commit.as_str());
+    }
+
+    #[test]
+    fn stash_rename_keeps_commit_and_position() {
+        let (dir, mut repo, _) = init_test_repo("stash-rename");
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+        fs::write(dir.join("a.txt"), "first").unwrap();
+        repo.stash_save(&sig, "first", Some(git2::StashFlags::INCLUDE_UNTRACKED))
+            .unwrap();
+        fs::write(dir.join("b.txt"), "second").unwrap();
+        repo.stash_save(&sig, "second", Some(git2::StashFlags::INCLUDE_UNTRACKED))
+            .unwrap();
+        let before: Vec<Oid> = repo
+            .reflog("refs/stash")
+            .unwrap()
+            .iter()
+            .map(|e| e.id_new())
+            .collect();
+        drop(repo);
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        assert!(client.stash_rename(1, "renamed").is_ok());
+        let messages: Vec<String> = client
+            .get_stashes()
+            .iter()
+            .map(|s| s.message.to_string())
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].ends_with("second"));
+        assert!(messages[1].ends_with(": renamed"), "{}", messages[1]);
+
+        // 同じスタッシュコミットを同じ順序で参照している
+        let repo = Repository::open(&dir).unwrap();
+        let after: Vec<Oid> = repo
+            .reflog("refs/stash")
+            .unwrap()
+            .iter()
+            .map(|e| e.id_new())
+            .collect();
+        assert_eq!(before, after);
+        assert!(client.stash_rename(2, "missing").is_err());
+    }
+
+    #[test]
+    fn stash_pop_keeps_stash_on_conflict() {
+        let (dir, mut repo, oid) = init_test_repo("stash-conflict");
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+        let commit_file = |repo: &Repository, content: &str, parent: Oid| -> Oid {
+            fs::write(dir.join("a.txt"), content).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("a.txt")).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parent = repo.find_commit(parent).unwrap();
+            repo.commit(Some("HEAD"), &sig, &sig, content, &tree, &[&parent])
+                .unwrap()
+        };
+        let base = commit_file(&repo, "base\n", oid);
+        fs::write(dir.join("a.txt"), "stashed\n").unwrap();
+        repo.stash_save(&sig, "change", None).unwrap();
+        commit_file(&repo, "committed\n", base);
+        drop(repo);
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        assert_eq!(
+            client.stash_pop(0).unwrap(),
+            StashApplyOutcome::Conflicts(vec!["a.txt".to_string()])
+        );
+        // コンフリクトしたのでスタッシュは残っている
+        assert_eq!(client.get_stashes().len(), 1);
+    }
+
+    #[test]
+    fn renamed_stash_message_keeps_branch_prefix() {
+        assert_eq!(renamed_stash_message("On main: old", "new"), "On main: new");
+        assert_eq!(
+            renamed_stash_message("WIP on feature/x: abc123 subject", "new"),
+            "On feature/x: new"
+        );
+        assert_eq!(renamed_stash_message("custom", "new"), "new");
+        assert_eq!(split_stash_message("On main: a: b"), (Some("main"), "a: b"));
+    }
+
+    #[test]
+    fn slashed_local_branch_is_marked_local() {
+        let (dir, repo, oid) = init_test_repo("refs");
+        repo.branch("feature/x", &repo.find_commit(oid).unwrap(), false)
+            .unwrap();
+        repo.reference("refs/remotes/origin/main", oid, false, "test")
+            .unwrap();
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        let key = client.graph_cache_key(10, 0).unwrap();
+        let (rows, _) = build_commit_graph(key.repo_path.as_deref().unwrap(), &key, 0);
+        let refs: Vec<(String, bool, String)> = rows[0]
+            .branches
+            .iter()
+            .map(|b| (b.name.to_string(), b.is_remote, b.ref_kind.to_string()))
+            .collect();
+
+        assert!(refs.contains(&("feature/x".into(), false, "local".into())));
+        assert!(refs.contains(&("origin/main".into(), true, "remote".into())));
+    }
+
+    #[test]
+    fn conflicting_checkout_can_stash_and_retry() {
+        let (dir, repo, _) = init_test_repo("checkout-conflict");
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+        let commit_file = |content: &str, parent: &git2::Commit, update_ref: Option<&str>| {
+            fs::write(dir.join("a.txt"), content).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("a.txt")).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            repo.commit(update_ref, &sig, &sig, content, &tree, &[parent])
+                .unwrap()
+        };
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let main = commit_file("main", &head, Some("HEAD"));
+        let main = repo.find_commit(main).unwrap();
+        repo.branch("other", &head, false).unwrap();
+        commit_file("other", &head, Some("refs/heads/other"));
+        // 作業ツリーとインデックスをmainに戻してから、ローカルの変更を加える
+        repo.reset(main.as_object(), git2::ResetType::Hard, None)
+            .unwrap();
+        fs::write(dir.join("a.txt"), "local edit").unwrap();
+        drop((head, main));
+        drop(repo);
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        let err = client.checkout_branch("other").unwrap_err();
+        assert!(
+            matches!(&err, GitError::CheckoutConflict(files) if files == &["a.txt"]),
+            "{}",
+            err
+        );
+
+        assert!(client.checkout_branch_stashing("other").is_ok());
+        let current = client.get_current_branch();
+        let stashes = client.get_stashes().len();
+        let content = fs::read_to_string(dir.join("a.txt")).unwrap();
+        assert_eq!(current, "other");
+        assert_eq!(stashes, 1);
+        assert_eq!(content, "other");
+    }
+
+    #[test]
+    fn tracking_branch_is_created_with_custom_name_and_upstream() {
+        assert_eq!(local_name_for_remote("origin/feature/x"), "feature/x");
+        assert_eq!(local_name_for_remote("origin"), "");
+
+        let (dir, repo, oid) = init_test_repo("tracking-branch");
+        repo.remote("origin", "https://example.invalid/repo.git")
+            .unwrap();
+        repo.reference("refs/remotes/origin/feature/x", oid, false, "test")
+            .unwrap();
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        assert!(client
+            .create_tracking_branch("origin/feature/x", "my-x", false)
+            .is_ok());
+        assert!(client
+            .create_tracking_branch("origin/feature/x", "my-x", false)
+            .is_err());
+        let status = client.get_upstream_status("my-x");
+        let current = client.get_current_branch();
+        assert_eq!(
+            status,
+            UpstreamStatus::Tracking {
+                name: "origin/feature/x".into(),
+                ahead: 0,
+                behind: 0
+            }
+        );
+        assert_ne!(current, "my-x");
+    }
+
+    #[test]
+    fn upstream_can_be_set_and_unset() {
+        let (dir, repo, oid) = init_test_repo("set-upstream");
+        repo.branch("topic", &repo.find_commit(oid).unwrap(), false)
+            .unwrap();
+        repo.remote("origin", "https://example.invalid/repo.git")
+            .unwrap();
+        repo.reference("refs/remotes/origin/main", oid, false, "test")
+            .unwrap();
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        assert!(client.set_upstream("topic", "origin/missing").is_err());
+        assert!(client.set_upstream("topic", "origin/main").is_ok());
+        assert_eq!(
+            client.get_upstream_status("topic"),
+            UpstreamStatus::Tracking {
+                name: "origin/main".into(),
+                ahead: 0,
+                behind: 0
+            }
+        );
+        assert!(client.unset_upstream("topic").is_ok());
+        let status = client.get_upstream_status("topic");
+        assert_eq!(status, UpstreamStatus::NotConfigured);
+    }
+
+    #[test]
+    fn upstream_status_distinguishes_gone_from_unconfigured() {
+        let (dir, repo, oid) = init_test_repo("upstream");
+        let commit = repo.find_commit(oid).unwrap();
+        repo.branch("topic", &commit, false).unwrap();
+        repo.branch("local-only", &commit, false).unwrap();
+        repo.remote("origin", "https://example.invalid/repo.git")
+            .unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("branch.topic.remote", "origin").unwrap();
+        config
+            .set_str("branch.topic.merge", "refs/heads/topic")
+            .unwrap();
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        assert_eq!(
+            client.get_upstream_status("local-only"),
+            UpstreamStatus::NotConfigured
+        );
+        // リモートブランチがまだ（もう）存在しない
+        assert_eq!(
+            client.get_upstream_status("topic"),
+            UpstreamStatus::Gone("origin/topic".into())
+        );
+
+        repo.reference("refs/remotes/origin/topic", oid, false, "test")
+            .unwrap();
+        let status = client.get_upstream_status("topic");
+        assert_eq!(
+            status,
+            UpstreamStatus::Tracking {
+                name: "origin/topic".into(),
+                ahead: 0,
+                behind: 0
+            }
+        );
+    }
+
+    #[test]
+    fn undo_restores_discarded_file_and_deleted_branch() {
+        let (dir, repo, oid) = init_test_repo("undo");
+        repo.branch("topic", &repo.find_commit(oid).unwrap(), false)
+            .unwrap();
+        fs::write(dir.join("notes.txt"), "draft").unwrap();
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        assert!(client.discard_file("notes.txt").is_ok());
+        assert!(client.delete_branch("topic", false).is_ok());
+        assert!(!dir.join("notes.txt").exists());
+
+        // 新しい操作から順に戻す
+        assert_eq!(client.undo_last_action().unwrap(), "delete of branch topic");
+        assert!(repo.find_branch("topic", BranchType::Local).is_ok());
+        assert_eq!(client.undo_last_action().unwrap(), "discard of notes.txt");
+        let restored = fs::read_to_string(dir.join("notes.txt")).unwrap();
+        assert_eq!(
+            client.undo_last_action().unwrap_err().to_string(),
+            "Nothing to undo"
+        );
+        assert_eq!(restored, "draft");
+    }
+
+    #[test]
+    fn undo_restores_index_untracked_directory_and_upstream() {
+        let (dir, repo, oid) = init_test_repo("undo-state");
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+        fs::write(dir.join("a.txt"), "v1").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a.txt")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.find_commit(oid).unwrap();
+        let head = repo
+            .commit(Some("HEAD"), &sig, &sig, "add a", &tree, &[&parent])
+            .unwrap();
+        // ステージ済み（v2）とワークツリー（v3）が異なる状態で破棄する
+        fs::write(dir.join("a.txt"), "v2").unwrap();
+        index.add_path(Path::new("a.txt")).unwrap();
+        index.write().unwrap();
+        fs::write(dir.join("a.txt"), "v3").unwrap();
+        fs::create_dir_all(dir.join("build/sub")).unwrap();
+        fs::write(dir.join("build/sub/out.txt"), "out").unwrap();
+        repo.remote("origin", "https://example.com/repo.git")
+            .unwrap();
+        repo.reference("refs/remotes/origin/topic", head, false, "test")
+            .unwrap();
+        let mut topic = repo
+            .branch("topic", &repo.find_commit(head).unwrap(), false)
+            .unwrap();
+        topic.set_upstream(Some("origin/topic")).unwrap();
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        assert!(client.discard_file("a.txt").is_ok());
+        assert!(client.discard_file("build/").is_ok());
+        assert!(client.delete_branch("topic", true).is_ok());
+        assert!(!dir.join("build").exists());
+
+        assert!(client.undo_last_action().is_ok());
+        let topic = repo.find_branch("topic", BranchType::Local).unwrap();
+        assert_eq!(
+            topic.upstream().unwrap().name().unwrap(),
+            Some("origin/topic")
+        );
+        assert!(client.undo_last_action().is_ok());
+        assert_eq!(
+            fs::read_to_string(dir.join("build/sub/out.txt")).unwrap(),
+            "out"
+        );
+        assert!(client.undo_last_action().is_ok());
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "v3");
+        let mut index = repo.index().unwrap();
+        index.read(true).unwrap();
+        let staged = index.get_path(Path::new("a.txt"), 0).unwrap();
+        assert_eq!(repo.find_blob(staged.id).unwrap().content(), b"v2");
+    }
+
+    #[test]
+    fn undo_hard_reset_refuses_to_overwrite_local_changes() {
+        let (dir, repo, oid) = init_test_repo("undo-reset");
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+        fs::write(dir.join("a.txt"), "v1").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a.txt")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.find_commit(oid).unwrap();
+        let head = repo
+            .commit(Some("HEAD"), &sig, &sig, "add a", &tree, &[&parent])
+            .unwrap();
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        assert!(client.reset_to_commit(&oid.to_string(), "hard").is_ok());
+        fs::write(dir.join("b.txt"), "local").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("b.txt")).unwrap();
+        index.write().unwrap();
+        // ローカルの変更がある間は戻さず、取り消し履歴にも残す
+        assert!(client.undo_last_action().is_err());
+        assert_eq!(repo.head().unwrap().target(), Some(oid));
+
+        index.remove_path(Path::new("b.txt")).unwrap();
+        index.write().unwrap();
+        assert!(client.undo_last_action().is_ok());
+        assert_eq!(repo.head().unwrap().target(), Some(head));
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "v1");
+    }
+
+    #[test]
+    fn partially_staged_files_are_marked_in_both_lists() {
+        let (dir, repo, _) = init_test_repo("partial-stage");
+        fs::write(dir.join("partial.txt"), "one\n").unwrap();
+        fs::write(dir.join("staged.txt"), "staged\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("partial.txt")).unwrap();
+        index.add_path(Path::new("staged.txt")).unwrap();
+        index.write().unwrap();
+        fs::write(dir.join("partial.txt"), "one\ntwo\n").unwrap();
+        fs::write(dir.join("untracked.txt"), "new\n").unwrap();
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        let (staged, unstaged) = client.get_status();
+        let flag = |files: &[FileData], name: &str| {
+            files
+                .iter()
+                .find(|f| f.filename == name)
+                .map(|f| f.partially_staged)
+        };
+        assert_eq!(flag(&staged, "partial.txt"), Some(true));
+        assert_eq!(flag(&unstaged, "partial.txt"), Some(true));
+        assert_eq!(flag(&staged, "staged.txt"), Some(false));
+        assert_eq!(flag(&unstaged, "untracked.txt"), Some(false));
+    }
+
+    #[test]
+    fn edited_file_after_staged_rename_is_listed_under_new_path() {
+        let (dir, repo, _) = init_test_repo("staged-rename-edit");
+        let content = "line 1\nline 2\nline 3\nline 4\nline 5\n";
+        fs::write(dir.join("old.rs"), content).unwrap();
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        client.stage_file("old.rs").unwrap();
+        client.commit("Add old.rs", false, None).unwrap();
+
+        fs::rename(dir.join("old.rs"), dir.join("new.rs")).unwrap();
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new("old.rs")).unwrap();
+        index.add_path(Path::new("new.rs")).unwrap();
+        index.write().unwrap();
+        fs::write(dir.join("new.rs"), format!("{}line 6\n", content)).unwrap();
+
+        let (staged, unstaged) = client.get_status();
+        assert_eq!(staged.len(), 1);
+        assert_eq!(
+            (staged[0].filename.as_str(), staged[0].old_filename.as_str()),
+            ("new.rs", "old.rs")
+        );
+        assert_eq!(unstaged.len(), 1);
+        assert_eq!(
+            (unstaged[0].filename.as_str(), unstaged[0].status.as_str()),
+            ("new.rs", "M")
+        );
+        assert!(staged[0].partially_staged && unstaged[0].partially_staged);
+    }
+
+    #[test]
+    fn untracked_directory_is_grouped_staged_and_discarded_as_one_entry() {
+        let (dir, _repo, _) = init_test_repo("untracked-dir");
+        fs::create_dir_all(dir.join("assets/icons")).unwrap();
+        fs::write(dir.join("assets/a.png"), "a").unwrap();
+        fs::write(dir.join("assets/icons/b.png"), "b").unwrap();
+        fs::write(dir.join("top.txt"), "top").unwrap();
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        client.set_group_untracked_dirs(true);
+        let (_, unstaged) = client.get_status();
+        let names: Vec<&str> = unstaged.iter().map(|f| f.filename.as_str()).collect();
+        assert_eq!(names, vec!["assets/", "top.txt"]);
+        assert_eq!(unstaged[0].group_count, 2);
+        assert_eq!(uncommitted_count(&[], &unstaged), 3);
+
+        // 展開すると中のファイルがまとめた行の直後に並ぶ
+        client.toggle_untracked_dir("assets/");
+        let (_, unstaged) = client.get_status();
+        assert_eq!(unstaged.len(), 4);
+        assert!(unstaged[0].expanded);
+        assert!(unstaged[1].in_group && unstaged[2].in_group);
+        assert_eq!(uncommitted_count(&[], &unstaged), 3);
+
+        assert!(client.stage_file("assets/").is_ok());
+        let (staged, unstaged) = client.get_status();
+        assert_eq!(staged.len(), 2);
+        assert_eq!(unstaged.len(), 1);
+
+        client.unstage_all().unwrap();
+        assert!(client.discard_file("assets/").is_ok());
+        assert!(!dir.join("assets").exists());
+        assert!(dir.join("top.txt").exists());
+    }
+
+    #[test]
+    fn pull_request_template_prefers_github_directory() {
+        let (dir, _repo, _) = init_test_repo("pr-template");
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        assert_eq!(client.get_pull_request_template(), None);
+
+        fs::write(dir.join("pull_request_template.md"), "root\n").unwrap();
+        assert_eq!(client.get_pull_request_template().as_deref(), Some("root"));
+        fs::create_dir_all(dir.join(".github")).unwrap();
+        fs::write(
+            dir.join(".github/PULL_REQUEST_TEMPLATE.md"),
+            "## Summary\n\n",
+        )
+        .unwrap();
+        assert_eq!(
+            client.get_pull_request_template().as_deref(),
+            Some("## Summary")
+        );
+    }
+
+    #[test]
+    fn fixup_and_squash_commits_target_the_subject() {
+        let (dir, repo, _) = init_test_repo("fixup");
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let tree = repo.find_tree(head.tree_id()).unwrap();
+        let target = repo
+            .commit(
+                Some("HEAD"),
+                &sig,
+                &sig,
+                "Add parser\n\nDetails",
+                &tree,
+                &[&head],
+            )
+            .unwrap()
+            .to_string();
+
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        let stage = |name: &str| {
+            fs::write(dir.join(name), name).unwrap();
+            client.stage_file(name).unwrap();
+        };
+        stage("a.txt");
+        assert!(client.commit_fixup(&target).is_ok());
+        let message = |repo: &Repository| {
+            let commit = repo.head().unwrap().peel_to_commit().unwrap();
+            commit.message().unwrap_or("").to_string()
+        };
+        assert_eq!(message(&repo), "fixup! Add parser");
+
+        stage("b.txt");
+        assert!(client.commit_squash(&target, "  Also handle b\n").is_ok());
+        assert_eq!(message(&repo), "squash! Add parser\n\nAlso handle b");
+        // ステージした変更がなければコミットしない
+        assert!(client.commit_fixup(&target).is_err());
+    }
+
+    #[test]
+    fn signature_check_treats_good_and_unknown_trust_as_verified() {
+        let output = "aaa G\nbbb U\nccc B\nddd E\n";
+        let verified = parse_signature_check(output);
+        assert_eq!(verified.get("aaa"), Some(&true));
+        assert_eq!(verified.get("bbb"), Some(&true));
+        assert_eq!(verified.get("ccc"), Some(&false));
+        assert_eq!(verified.get("ddd"), Some(&false));
+    }
+
+    #[test]
+    fn autosquash_folds_fixup_into_its_target() {
+        let (dir, repo, _) = init_test_repo("autosquash");
+        let mut client = GitClient::new();
+        assert!(client.open_repo(dir.to_str().unwrap()).is_ok());
+        let stage = |client: &GitClient, name: &str, content: &str| {
+            fs::write(dir.join(name), content).unwrap();
+            client.stage_file(name).unwrap();
+        };
+        stage(&client, "a.txt", "a");
+        client.commit("Add a", false, None).unwrap();
+        let target = repo.head().unwrap().target().unwrap().to_string();
+        stage(&client, "b.txt", "b");
+        client.commit("Add b", false, None).unwrap();
+        stage(&client, "a.txt", "a fixed");
+        client.commit_fixup(&target).unwrap();
+
+        client.rebase_autosquash(&target).unwrap();
+
+        let mut walk = repo.revwalk().unwrap();
+        walk.push_head().unwrap();
+        let subjects: Vec<String> = walk
+            .map(|oid| {
+                let commit = repo.find_commit(oid.unwrap()).unwrap();
+                commit.summary().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(subjects, ["Add b", "Add a", "initial"]);
+        // fixupの変更は対象コミット自体に取り込まれている
+        let squashed = repo
+            .revparse_single("HEAD~1")
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        let blob = squashed
+            .tree()
+            .unwrap()
+            .get_path(Path::new("a.txt"))
+            .unwrap();
+        let content = repo.find_blob(blob.id()).unwrap().content().to_vec();
+        assert_eq!(content, b"a fixed");
+    }
+}
@@ -0,0 +1,1361 @@
+//! コミットグラフの構築とレイアウト（Git Graphのアルゴリズム）
+
+use chrono::{DateTime, Local, TimeZone};
+use git2::{BranchType, Oid, Repository};
+use slint::{Model, SharedString, VecModel};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use crate::{CommitBranchInfo, CommitData, MainWindow, MergeLineData};
+
+// Graph用の色数（線用SVGパスもこの色数分だけ生成する。色自体はui/main.slintのTheme.graph-colors）
+const GRAPH_COLOR_COUNT: usize = 16;
+
+// グラフの作者絞り込みで見るコミット数（候補の収集と、絞り込んだグラフの構築で共通）
+pub const GRAPH_AUTHOR_SCAN_LIMIT: usize = 2000;
+
+// ========== Git Graphのデータ構造 ==========
+
+const NULL_VERTEX_ID: i32 = -1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Line {
+    p1: Point,
+    p2: Point,
+    locked_first: bool, // TRUE => 線はp1に固定, FALSE => 線はp2に固定
+}
+
+#[derive(Clone)]
+struct UnavailablePoint {
+    connects_to: i32, // Vertex ID or NULL_VERTEX_ID
+    on_branch: usize, // Branch index
+}
+
+/// Git GraphのBranchクラス
+struct Branch {
+    colour: usize,
+    end: usize,
+    lines: Vec<Line>,
+    num_uncommitted: usize,
+}
+
+impl Branch {
+    fn new(colour: usize) -> Self {
+        Self {
+            colour,
+            end: 0,
+            lines: Vec::new(),
+            num_uncommitted: 0,
+        }
+    }
+
+    fn add_line(&mut self, p1: Point, p2: Point, is_committed: bool, locked_first: bool) {
+        self.lines.push(Line {
+            p1,
+            p2,
+            locked_first,
+        });
+        if is_committed {
+            if p2.x == 0 && (p2.y as usize) < self.num_uncommitted {
+                self.num_uncommitted = p2.y as usize;
+            }
+        } else {
+            self.num_uncommitted += 1;
+        }
+    }
+
+    fn get_colour(&self) -> usize {
+        self.colour
+    }
+
+    fn set_end(&mut self, end: usize) {
+        self.end = end;
+    }
+}
+
+/// Git GraphのVertexクラス
+struct Vertex {
+    id: i32,
+    x: i32,
+    children: Vec<i32>,
+    parents: Vec<i32>,
+    next_parent: usize,
+    on_branch: Option<usize>, // Branch index
+    is_committed: bool,
+    // HEADの頂点か（描画には使わず、レイアウトのテストで確認する）
+    #[cfg_attr(not(test), allow(dead_code))]
+    is_current: bool,
+    next_x: i32,
+    connections: Vec<UnavailablePoint>,
+}
+
+impl Vertex {
+    fn new(id: i32) -> Self {
+        Self {
+            id,
+            x: 0,
+            children: Vec::new(),
+            parents: Vec::new(),
+            next_parent: 0,
+            on_branch: None,
+            is_committed: true,
+            is_current: false,
+            next_x: 0,
+            connections: Vec::new(),
+        }
+    }
+
+    fn add_child(&mut self, child_id: i32) {
+        self.children.push(child_id);
+    }
+
+    fn add_parent(&mut self, parent_id: i32) {
+        self.parents.push(parent_id);
+    }
+
+    #[allow(dead_code)]
+    fn has_parents(&self) -> bool {
+        !self.parents.is_empty()
+    }
+
+    fn get_next_parent(&self) -> Option<i32> {
+        self.parents.get(self.next_parent).copied()
+    }
+
+    fn register_parent_processed(&mut self) {
+        self.next_parent += 1;
+    }
+
+    fn is_merge(&self) -> bool {
+        self.parents.len() > 1
+    }
+
+    fn add_to_branch(&mut self, branch_idx: usize, x: i32) {
+        if self.on_branch.is_none() {
+            self.on_branch = Some(branch_idx);
+            self.x = x;
+        }
+    }
+
+    fn is_not_on_branch(&self) -> bool {
+        self.on_branch.is_none()
+    }
+
+    #[allow(dead_code)]
+    fn is_on_this_branch(&self, branch_idx: usize) -> bool {
+        self.on_branch == Some(branch_idx)
+    }
+
+    fn get_point(&self) -> Point {
+        Point {
+            x: self.x,
+            y: self.id,
+        }
+    }
+
+    fn get_next_point(&self) -> Point {
+        Point {
+            x: self.next_x,
+            y: self.id,
+        }
+    }
+
+    fn get_point_connecting_to(&self, vertex_id: i32, on_branch: usize) -> Option<Point> {
+        for (i, conn) in self.connections.iter().enumerate() {
+            if conn.connects_to == vertex_id && conn.on_branch == on_branch {
+                return Some(Point {
+                    x: i as i32,
+                    y: self.id,
+                });
+            }
+        }
+        None
+    }
+
+    fn register_unavailable_point(&mut self, x: i32, connects_to: i32, on_branch: usize) {
+        if x == self.next_x {
+            self.next_x = x + 1;
+            // Ensure connections vector is large enough
+            while self.connections.len() <= x as usize {
+                self.connections.push(UnavailablePoint {
+                    connects_to: NULL_VERTEX_ID,
+                    on_branch: 0,
+                });
+            }
+            self.connections[x as usize] = UnavailablePoint {
+                connects_to,
+                on_branch,
+            };
+        }
+    }
+
+    fn get_colour(&self, branches: &[Branch]) -> usize {
+        self.on_branch
+            .map(|b| branches[b].get_colour())
+            .unwrap_or(0)
+    }
+
+    fn set_not_committed(&mut self) {
+        self.is_committed = false;
+    }
+
+    fn set_current(&mut self) {
+        self.is_current = true;
+    }
+}
+
+/// Git Graphのグラフ構築エンジン
+struct GraphBuilder {
+    vertices: Vec<Vertex>,
+    branches: Vec<Branch>,
+    available_colours: Vec<usize>,
+}
+
+impl GraphBuilder {
+    fn new() -> Self {
+        Self {
+            vertices: Vec::new(),
+            branches: Vec::new(),
+            available_colours: Vec::new(),
+        }
+    }
+
+    /// コミットデータからグラフを構築
+    fn load_commits(
+        &mut self,
+        commit_count: usize,
+        parent_map: &[(usize, Vec<i32>)],
+        head_index: Option<usize>,
+        has_uncommitted: bool,
+    ) {
+        self.vertices.clear();
+        self.branches.clear();
+        self.available_colours.clear();
+
+        if commit_count == 0 {
+            return;
+        }
+
+        // 全コミットをVertexとして作成
+        for i in 0..commit_count {
+            self.vertices.push(Vertex::new(i as i32));
+        }
+
+        // 親子関係を設定
+        for (idx, parents) in parent_map {
+            for &parent_id in parents {
+                if parent_id >= 0 && (parent_id as usize) < commit_count {
+                    self.vertices[*idx].add_parent(parent_id);
+                    self.vertices[parent_id as usize].add_child(*idx as i32);
+                } else if parent_id == NULL_VERTEX_ID {
+                    self.vertices[*idx].add_parent(NULL_VERTEX_ID);
+                }
+            }
+        }
+
+        // Uncommitted changesの設定
+        if has_uncommitted && !self.vertices.is_empty() {
+            self.vertices[0].set_not_committed();
+        }
+
+        // HEADの設定
+        if let Some(head_idx) = head_index {
+            if head_idx < self.vertices.len() {
+                self.vertices[head_idx].set_current();
+            }
+        }
+
+        // パスを決定
+        for i in 0..self.vertices.len() {
+            self.route_all_parents(i);
+        }
+    }
+
+    /// 頂点の全ての親へのパスを決定する（オクトパスマージでは3つ目以降の親も順に処理）
+    fn route_all_parents(&mut self, idx: usize) {
+        while self.vertices[idx].get_next_parent().is_some()
+            || self.vertices[idx].is_not_on_branch()
+        {
+            let before = (self.vertices[idx].next_parent, self.vertices[idx].on_branch);
+            self.determine_path(idx);
+            if (self.vertices[idx].next_parent, self.vertices[idx].on_branch) == before {
+                // 親への接続点が見つからなかった場合はスキップして無限ループを防ぐ
+                self.vertices[idx].register_parent_processed();
+            }
+        }
+    }
+
+    /// Git Graphのdetermine_path()相当 - パス決定アルゴリズム
+    fn determine_path(&mut self, start_at: usize) {
+        let parent_id = self.vertices[start_at].get_next_parent();
+
+        let last_point = if self.vertices[start_at].is_not_on_branch() {
+            self.vertices[start_at].get_next_point()
+        } else {
+            self.vertices[start_at].get_point()
+        };
+
+        if let Some(parent_id) = parent_id {
+            if parent_id != NULL_VERTEX_ID
+                && self.vertices[start_at].is_merge()
+                && !self.vertices[start_at].is_not_on_branch()
+                && !self.vertices[parent_id as usize].is_not_on_branch()
+            {
+                // マージ: 両方の頂点が既にブランチ上にある場合
+                self.handle_merge_path(start_at, parent_id, last_point);
+            } else {
+                // 通常のブランチ
+                self.handle_normal_path(start_at, last_point);
+            }
+        } else {
+            // 親がない場合も通常パスとして処理
+            self.handle_normal_path(start_at, last_point);
+        }
+    }
+
+    fn handle_merge_path(&mut self, start_at: usize, parent_id: i32, mut last_point: Point) {
+        let parent_branch = self.vertices[parent_id as usize].on_branch.unwrap();
+        let vertex_is_committed = self.vertices[start_at].is_committed;
+        let mut found_point_to_parent = false;
+
+        for i in (start_at + 1)..self.vertices.len() {
+            let cur_point = if let Some(p) =
+                self.vertices[i].get_point_connecting_to(parent_id, parent_branch)
+            {
+                found_point_to_parent = true;
+                p
+            } else {
+                self.vertices[i].get_next_point()
+            };
+
+            let locked_first =
+                !found_point_to_parent && i != parent_id as usize && last_point.x < cur_point.x;
+            self.branches[parent_branch].add_line(
+                last_point,
+                cur_point,
+                vertex_is_committed,
+                locked_first,
+            );
+            self.vertices[i].register_unavailable_point(cur_point.x, parent_id, parent_branch);
+            last_point = cur_point;
+
+            if found_point_to_parent {
+                self.vertices[start_at].register_parent_processed();
+                break;
+            }
+        }
+    }
+
+    fn handle_normal_path(&mut self, start_at: usize, mut last_point: Point) {
+        let colour = self.get_available_colour(start_at);
+        let branch_idx = self.branches.len();
+        self.branches.push(Branch::new(colour));
+
+        let vertex_id = self.vertices[start_at].id;
+        self.vertices[start_at].add_to_branch(branch_idx, last_point.x);
+        self.vertices[start_at].register_unavailable_point(last_point.x, vertex_id, branch_idx);
+
+        let mut vertex_idx = start_at;
+        let mut i = start_at + 1;
+
+        while i < self.vertices.len() {
+            let parent_id = self.vertices[vertex_idx].get_next_parent();
+
+            if parent_id.is_none() {
+                break;
+            }
+
+            let cur_point = if let Some(pid) = parent_id {
+                if pid != NULL_VERTEX_ID
+                    && pid as usize == i
+                    && !self.vertices[i].is_not_on_branch()
+                {
+                    self.vertices[i].get_point()
+                } else {
+                    self.vertices[i].get_next_point()
+                }
+            } else {
+                self.vertices[i].get_next_point()
+            };
+
+            let vertex_is_committed = self.vertices[vertex_idx].is_committed;
+            let locked_first = last_point.x < cur_point.x;
+            self.branches[branch_idx].add_line(
+                last_point,
+                cur_point,
+                vertex_is_committed,
+                locked_first,
+            );
+
+            if let Some(pid) = parent_id {
+                self.vertices[i].register_unavailable_point(cur_point.x, pid, branch_idx);
+            } else {
+                self.vertices[i].register_unavailable_point(
+                    cur_point.x,
+                    NULL_VERTEX_ID,
+                    branch_idx,
+                );
+            }
+
+            last_point = cur_point;
+
+            // 親に到達したかチェック
+            if let Some(pid) = parent_id {
+                if pid != NULL_VERTEX_ID && pid as usize == i {
+                    self.vertices[vertex_idx].register_parent_processed();
+                    let parent_on_branch = !self.vertices[i].is_not_on_branch();
+                    self.vertices[i].add_to_branch(branch_idx, cur_point.x);
+                    vertex_idx = i;
+
+                    let next_parent = self.vertices[vertex_idx].get_next_parent();
+                    if next_parent.is_none() || parent_on_branch {
+                        break;
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        // 最後の頂点で親がNULL_VERTEX_IDの場合
+        if i == self.vertices.len() {
+            if let Some(pid) = self.vertices[vertex_idx].get_next_parent() {
+                if pid == NULL_VERTEX_ID {
+                    self.vertices[vertex_idx].register_parent_processed();
+                }
+            }
+        }
+
+        // 線を持たないブランチ（親のない単独コミット）は開始行で終了し、次の行から色を再利用できる
+        let end = if self.branches[branch_idx].lines.is_empty() {
+            start_at
+        } else {
+            i
+        };
+        self.branches[branch_idx].set_end(end);
+        self.available_colours[colour] = end;
+    }
+
+    /// 利用可能な色を取得（Git Graphの色再利用ロジック）
+    /// available_coloursには各色を最後に使ったブランチの終了行が入っており、
+    /// その行より後に開始するブランチはその色を再利用する
+    fn get_available_colour(&mut self, start_at: usize) -> usize {
+        for (i, &end) in self.available_colours.iter().enumerate() {
+            if start_at > end {
+                return i;
+            }
+        }
+        self.available_colours.push(0);
+        self.available_colours.len() - 1
+    }
+}
+
+/// グラフレイアウト中の頂点（UIに依存しない）
+#[derive(Clone, Debug, PartialEq)]
+struct LayoutVertex {
+    column: i32,
+    colour: usize,
+    is_merge: bool,
+    // 以下は描画には使わず、レイアウトのテストでのみ確認する
+    #[cfg(test)]
+    branch: Option<usize>,
+    #[cfg(test)]
+    is_current: bool,
+    /// すべての親へのパスを処理済みか
+    #[cfg(test)]
+    parents_processed: bool,
+}
+
+/// グラフレイアウト中のブランチ（同じ色で描画される線の集まり）
+#[derive(Clone, Debug, PartialEq)]
+struct LayoutBranch {
+    colour: usize,
+    end: usize,
+    lines: Vec<Line>,
+}
+
+/// グラフレイアウトの結果（列・色・線の座標のみを持つ）
+#[derive(Clone, Debug, PartialEq)]
+struct GraphLayout {
+    vertices: Vec<LayoutVertex>,
+    branches: Vec<LayoutBranch>,
+}
+
+impl GraphBuilder {
+    fn into_layout(self) -> GraphLayout {
+        let vertices = self
+            .vertices
+            .iter()
+            .map(|v| LayoutVertex {
+                column: v.x,
+                colour: v.get_colour(&self.branches),
+                is_merge: v.is_merge(),
+                #[cfg(test)]
+                branch: v.on_branch,
+                #[cfg(test)]
+                is_current: v.is_current,
+                #[cfg(test)]
+                parents_processed: v.next_parent == v.parents.len(),
+            })
+            .collect();
+        let branches = self
+            .branches
+            .into_iter()
+            .map(|b| LayoutBranch {
+                colour: b.colour,
+                end: b.end,
+                lines: b.lines,
+            })
+            .collect();
+        GraphLayout { vertices, branches }
+    }
+}
+
+/// 親マップからグラフのレイアウト（各頂点の列・色、ブランチごとの線）を計算する
+/// parent_mapは(行, 親の行のリスト)で、グラフ外の親はNULL_VERTEX_IDで表す
+fn layout_graph(
+    commit_count: usize,
+    parent_map: &[(usize, Vec<i32>)],
+    head_index: Option<usize>,
+    has_uncommitted: bool,
+) -> GraphLayout {
+    let mut builder = GraphBuilder::new();
+    builder.load_commits(commit_count, parent_map, head_index, has_uncommitted);
+    builder.into_layout()
+}
+
+impl GraphLayout {
+    /// SVGパスを生成（線用パスとノード用パスを分離）
+    /// 戻り値: (線用パス[GRAPH_COLOR_COUNT], ノード用パス)
+    fn generate_svg_paths(&self, row: usize) -> ([String; GRAPH_COLOR_COUNT], String) {
+        const COL_SPACING: f32 = 16.0;
+        const ROW_HEIGHT: f32 = 28.0;
+        const NODE_CENTER_Y: f32 = ROW_HEIGHT / 2.0;
+        const CURVE_OFFSET: f32 = ROW_HEIGHT * 0.8;
+        const NODE_RADIUS: f32 = 4.0;
+
+        let mut paths: [String; GRAPH_COLOR_COUNT] = Default::default();
+        let mut node_path = String::new();
+
+        // このコミットを通過する全ブランチの線を描画
+        for branch in self.branches.iter() {
+            let color_idx = branch.colour % GRAPH_COLOR_COUNT;
+
+            for line in &branch.lines {
+                // この行に関係する線のみ処理
+                if line.p1.y as usize == row
+                    || line.p2.y as usize == row
+                    || (line.p1.y < row as i32 && line.p2.y > row as i32)
+                {
+                    let x1 = line.p1.x as f32 * COL_SPACING + 7.0;
+                    let y1 = line.p1.y as f32 * ROW_HEIGHT + NODE_CENTER_Y;
+                    let x2 = line.p2.x as f32 * COL_SPACING + 7.0;
+                    let y2 = line.p2.y as f32 * ROW_HEIGHT + NODE_CENTER_Y;
+
+                    // この行の範囲内の部分のみ描画
+                    let row_top = row as f32 * ROW_HEIGHT;
+                    let row_bottom = row_top + ROW_HEIGHT;
+
+                    if x1 == x2 {
+                        // 垂直線
+                        let draw_y1 = y1.max(row_top);
+                        let draw_y2 = y2.min(row_bottom);
+                        if draw_y1 < draw_y2 {
+                            // ローカル座標に変換
+                            let local_y1 = draw_y1 - row_top;
+                            let local_y2 = draw_y2 - row_top;
+                            paths[color_idx]
+                                .push_str(&format!("M {} {} L {} {} ", x1, local_y1, x1, local_y2));
+                        }
+                    } else {
+                        // 曲線（この行が始点または終点の場合のみ）
+                        if line.p1.y as usize == row || line.p2.y as usize == row {
+                            self.draw_curve_segment(
+                                &mut paths[color_idx],
+                                line,
+                                row,
+                                COL_SPACING,
+                                ROW_HEIGHT,
+                                CURVE_OFFSET,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        // ノードをSVGパスとして描画（線と同じ座標系）
+        if let Some(vertex) = self.vertices.get(row) {
+            let node_x = vertex.column as f32 * COL_SPACING + 7.0;
+            let node_y = NODE_CENTER_Y;
+
+            // 円を描画: M (x-r) y a r r 0 1 0 (2r) 0 a r r 0 1 0 (-2r) 0
+            node_path = format!(
+                "M {} {} m -{} 0 a {} {} 0 1 0 {} 0 a {} {} 0 1 0 -{} 0 ",
+                node_x,
+                node_y,
+                NODE_RADIUS,
+                NODE_RADIUS,
+                NODE_RADIUS,
+                NODE_RADIUS * 2.0,
+                NODE_RADIUS,
+                NODE_RADIUS,
+                NODE_RADIUS * 2.0
+            );
+        }
+
+        (paths, node_path)
+    }
+
+    fn draw_curve_segment(
+        &self,
+        path: &mut String,
+        line: &Line,
+        row: usize,
+        col_spacing: f32,
+        row_height: f32,
+        curve_offset: f32,
+    ) {
+        let node_center_y = row_height / 2.0;
+        let x1 = line.p1.x as f32 * col_spacing + 7.0;
+        let x2 = line.p2.x as f32 * col_spacing + 7.0;
+
+        if line.p1.y as usize == row {
+            // この行が始点
+            let local_y1 = node_center_y;
+            let local_y2 = row_height;
+
+            if line.locked_first {
+                // 上に固定: 曲線は下に向かう
+                let ctrl_y = local_y1 + curve_offset.min(row_height - node_center_y);
+                path.push_str(&format!(
+                    "M {} {} C {} {} {} {} {} {} ",
+                    x1, local_y1, x1, ctrl_y, x2, local_y2, x2, local_y2
+                ));
+            } else {
+                // 下に固定: 直線で下へ、次の行で曲がる
+                path.push_str(&format!("M {} {} L {} {} ", x1, local_y1, x1, local_y2));
+            }
+        } else if line.p2.y as usize == row {
+            // この行が終点
+            let local_y1 = 0.0;
+            let local_y2 = node_center_y;
+
+            if line.locked_first {
+                // 上に固定: 直線で上から来る
+                path.push_str(&format!("M {} {} L {} {} ", x2, local_y1, x2, local_y2));
+            } else {
+                // 下に固定: 曲線で終点に向かう
+                let ctrl_y = local_y2 - curve_offset.min(node_center_y);
+                path.push_str(&format!(
+                    "M {} {} C {} {} {} {} {} {} ",
+                    x1, local_y1, x1, local_y1, x2, ctrl_y, x2, local_y2
+                ));
+            }
+        }
+    }
+}
+
+/// コミットに付く参照の種類（グラフのラベル表示用）
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RefKind {
+    Local,
+    Remote,
+    Tag,
+}
+
+impl RefKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RefKind::Local => "local",
+            RefKind::Remote => "remote",
+            RefKind::Tag => "tag",
+        }
+    }
+}
+
+/// コミットグラフのキャッシュキー（これらが変わらなければグラフ構造は同じ）
+/// グラフ計算スレッドへの入力も兼ねる
+#[derive(Clone, PartialEq)]
+pub struct GraphCacheKey {
+    pub repo_path: Option<String>,
+    pub branch_heads: Vec<(String, Vec<(String, RefKind)>)>, // (OID, ブランチ・タグ名と種類) をOID順に
+    pub head_oid: Option<String>,
+    pub current_branch: String,
+    pub has_uncommitted: bool,
+    pub limit: usize,
+    pub author_filter: String,     // 作者メール（小文字、空なら全員）
+    pub branch_filter: String,     // このブランチから到達できるコミットのみ（空なら全ブランチ）
+    pub current_branch_only: bool, // HEADから到達できるコミットのみ（branch_filterより優先）
+}
+
+/// 計算済みのコミットグラフ（SVGパスを含む）
+/// グラフ計算スレッドから書き込むためスレッド間で共有できる形で保持する
+pub struct GraphCache {
+    pub key: GraphCacheKey,
+    pub commits: Vec<CommitRow>,
+    pub merge_lines: Vec<MergeLineData>,
+}
+
+pub type SharedGraphCache = Arc<Mutex<Option<GraphCache>>>;
+
+/// グラフ再構築の前後で引き継ぐコミット選択とスクロール位置
+/// 行番号はUncommitted行の有無などで変わるため、ハッシュで選択を復元する
+#[derive(Default)]
+pub struct GraphSelection {
+    /// 選択中のコミット（Uncommitted行はハッシュが空なので別フラグで持つ）
+    selected_hash: Option<String>,
+    uncommitted_selected: bool,
+    compare_from_hash: Option<String>,
+    scroll_y: f32,
+}
+
+impl GraphSelection {
+    pub fn capture(ui: &MainWindow) -> Self {
+        let non_empty = |s: SharedString| (!s.is_empty()).then(|| s.to_string());
+        let selected = ui.get_selected_commit();
+        Self {
+            selected_hash: non_empty(ui.get_selected_commit_hash()),
+            uncommitted_selected: selected >= 0 && ui.get_selected_commit_hash().is_empty(),
+            compare_from_hash: non_empty(ui.get_compare_from_hash()),
+            scroll_y: ui.get_commit_scroll_y(),
+        }
+    }
+
+    /// 再構築後のコミット一覧で同じコミットを探して選択し直す（見つからなければ選択解除）
+    pub fn restore(&self, ui: &MainWindow) {
+        let commits = ui.get_commits();
+        let find = |hash: &Option<String>| {
+            hash.as_ref().and_then(|hash| {
+                commits
+                    .iter()
+                    .position(|c| !c.is_uncommitted && c.full_hash.as_str() == hash)
+            })
+        };
+        let selected = find(&self.selected_hash).or_else(|| {
+            (self.uncommitted_selected && commits.row_data(0).is_some_and(|c| c.is_uncommitted))
+                .then_some(0)
+        });
+        let compare_from = selected.and(find(&self.compare_from_hash));
+
+        match selected {
+            Some(index) => {
+                ui.set_selected_commit(index as i32);
+                ui.set_selected_commit_hash(self.selected_hash.clone().unwrap_or_default().into());
+            }
+            None => {
+                ui.set_selected_commit(-1);
+                ui.set_selected_commit_hash("".into());
+            }
+        }
+        match compare_from {
+            Some(index) => {
+                ui.set_compare_from_index(index as i32);
+                ui.set_compare_from_hash(self.compare_from_hash.clone().unwrap_or_default().into());
+            }
+            None => {
+                ui.set_compare_from_index(-1);
+                ui.set_compare_from_hash("".into());
+            }
+        }
+        ui.set_commit_scroll_y(self.scroll_y);
+    }
+}
+
+/// .mailmapを適用した作者名（mailmapがない・解決できない場合は記録されたままの名前）
+pub fn mapped_author_name(commit: &git2::Commit, mailmap: Option<&git2::Mailmap>) -> String {
+    mailmap
+        .and_then(|mailmap| commit.author_with_mailmap(mailmap).ok())
+        .and_then(|sig| sig.name().map(str::to_string))
+        .unwrap_or_else(|| commit.author().name().unwrap_or("").to_string())
+}
+
+/// .mailmapを適用した作者メール
+pub fn author_email(commit: &git2::Commit, mailmap: Option<&git2::Mailmap>) -> String {
+    mailmap
+        .and_then(|mailmap| commit.author_with_mailmap(mailmap).ok())
+        .and_then(|sig| sig.email().map(str::to_string))
+        .unwrap_or_else(|| commit.author().email().unwrap_or("").to_string())
+}
+
+/// 表示するコミット（keptの順）の親子関係を作る
+/// 絞り込みで除外したコミットは飛ばして、最も近い表示対象の祖先に親を付け替える。
+/// 走査していない親はグラフ外（NULL_VERTEX_ID）とする
+fn rewrite_filtered_parents(
+    kept: &[Oid],
+    parents_of: &HashMap<Oid, Vec<Oid>>,
+) -> Vec<(usize, Vec<i32>)> {
+    let index: HashMap<Oid, usize> = kept.iter().enumerate().map(|(i, &o)| (o, i)).collect();
+    kept.iter()
+        .enumerate()
+        .map(|(idx, oid)| {
+            let mut parents: Vec<i32> = vec![];
+            let mut stack: Vec<Oid> = parents_of.get(oid).cloned().unwrap_or_default();
+            stack.reverse();
+            let mut visited = std::collections::HashSet::new();
+            while let Some(parent) = stack.pop() {
+                if !visited.insert(parent) {
+                    continue;
+                }
+                let resolved = if let Some(&i) = index.get(&parent) {
+                    i as i32
+                } else if let Some(grandparents) = parents_of.get(&parent) {
+                    // 除外したコミット: その親をたどる（先頭の親から順に）
+                    stack.extend(grandparents.iter().rev());
+                    continue;
+                } else {
+                    NULL_VERTEX_ID
+                };
+                if !parents.contains(&resolved) {
+                    parents.push(resolved);
+                }
+            }
+            (idx, parents)
+        })
+        .collect()
+}
+
+/// 別スレッドで計算したコミット行（ModelRcを含まないためスレッド間で受け渡せる）
+#[derive(Clone)]
+pub struct CommitRow {
+    pub hash: SharedString,
+    pub full_hash: SharedString,
+    pub message: SharedString,
+    pub author: SharedString,
+    pub date: SharedString,
+    pub branches: Vec<CommitBranchInfo>,
+    graph_column: i32,
+    graph_color_idx: i32,
+    is_merge: bool,
+    is_head: bool,
+    pub is_uncommitted: bool,
+    svg_paths: [String; GRAPH_COLOR_COUNT],
+    node_path: String,
+}
+
+impl CommitRow {
+    /// UIスレッドでSlintのCommitDataに変換する
+    pub fn into_commit_data(self) -> CommitData {
+        let [p0, p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11, p12, p13, p14, p15] = self.svg_paths;
+        CommitData {
+            hash: self.hash,
+            full_hash: self.full_hash,
+            message: self.message,
+            author: self.author,
+            date: self.date,
+            branches: Rc::new(VecModel::from(self.branches)).into(),
+            graph_column: self.graph_column,
+            graph_color_idx: self.graph_color_idx,
+            is_merge: self.is_merge,
+            is_head: self.is_head,
+            is_uncommitted: self.is_uncommitted,
+            svg_path_0: p0.into(),
+            svg_path_1: p1.into(),
+            svg_path_2: p2.into(),
+            svg_path_3: p3.into(),
+            svg_path_4: p4.into(),
+            svg_path_5: p5.into(),
+            svg_path_6: p6.into(),
+            svg_path_7: p7.into(),
+            svg_path_8: p8.into(),
+            svg_path_9: p9.into(),
+            svg_path_10: p10.into(),
+            svg_path_11: p11.into(),
+            svg_path_12: p12.into(),
+            svg_path_13: p13.into(),
+            svg_path_14: p14.into(),
+            svg_path_15: p15.into(),
+            node_path: self.node_path.into(),
+            signature: "".into(),
+        }
+    }
+}
+
+pub fn uncommitted_message(count: usize) -> SharedString {
+    SharedString::from(format!("Uncommitted Changes ({})", count))
+}
+
+/// Git Graphのアルゴリズムでコミットグラフを構築（別スレッドで実行、リポジトリはパスから開く）
+pub fn build_commit_graph(
+    repo_path: &str,
+    key: &GraphCacheKey,
+    uncommitted: usize,
+) -> (Vec<CommitRow>, Vec<MergeLineData>) {
+    let Ok(repo) = Repository::open(repo_path) else {
+        return (vec![], vec![]);
+    };
+    let branch_heads: HashMap<String, Vec<(String, RefKind)>> =
+        key.branch_heads.iter().cloned().collect();
+
+    let Ok(mut revwalk) = repo.revwalk() else {
+        return (vec![], vec![]);
+    };
+    revwalk
+        .set_sorting(git2::Sort::TIME | git2::Sort::TOPOLOGICAL)
+        .ok();
+
+    // 現在のブランチのみならHEADだけ（未コミットのリポジトリなら何も追加しない）、
+    // ブランチで絞り込む場合はそのブランチだけ、見つからなければ全ブランチを追加
+    let filtered_branch = (!key.branch_filter.is_empty())
+        .then(|| {
+            repo.revparse_single(&key.branch_filter)
+                .and_then(|o| o.peel_to_commit())
+                .ok()
+        })
+        .flatten();
+    if key.current_branch_only {
+        if let Some(head) = key.head_oid.as_deref().and_then(|h| Oid::from_str(h).ok()) {
+            let _ = revwalk.push(head);
+        }
+    } else if let Some(commit) = filtered_branch {
+        let _ = revwalk.push(commit.id());
+    } else {
+        if let Ok(branches) = repo.branches(Some(BranchType::Local)) {
+            for branch in branches.flatten() {
+                if let Ok(reference) = branch.0.get().peel_to_commit() {
+                    let _ = revwalk.push(reference.id());
+                }
+            }
+        }
+        if let Ok(branches) = repo.branches(Some(BranchType::Remote)) {
+            for branch in branches.flatten() {
+                if let Ok(reference) = branch.0.get().peel_to_commit() {
+                    let _ = revwalk.push(reference.id());
+                }
+            }
+        }
+    }
+
+    // コミットを収集（作者で絞り込む場合は除外したコミットの親も記録して、親を付け替える）
+    // 絞り込み時は該当コミットが少なくても履歴全体を辿らないよう、見るコミット数に上限を設ける
+    let mailmap = repo.mailmap().ok();
+    let scan_limit = if key.author_filter.is_empty() {
+        usize::MAX
+    } else {
+        GRAPH_AUTHOR_SCAN_LIMIT.max(key.limit)
+    };
+    let mut oids = vec![];
+    let mut parents_of: HashMap<Oid, Vec<Oid>> = HashMap::new();
+    for oid in revwalk.take(scan_limit).flatten() {
+        if oids.len() >= key.limit {
+            break;
+        }
+        let Ok(commit) = repo.find_commit(oid) else {
+            continue;
+        };
+        parents_of.insert(oid, commit.parent_ids().collect());
+        if key.author_filter.is_empty()
+            || author_email(&commit, mailmap.as_ref()).to_lowercase() == key.author_filter
+        {
+            oids.push(oid);
+        }
+    }
+
+    // OID -> インデックスのマップを作成
+    let mut oid_to_index: HashMap<String, usize> = HashMap::new();
+    for (idx, &oid) in oids.iter().enumerate() {
+        oid_to_index.insert(oid.to_string(), idx);
+    }
+
+    // HEADのインデックスを取得
+    let head_index = key
+        .head_oid
+        .as_ref()
+        .and_then(|h| oid_to_index.get(h).copied());
+
+    // 親子関係を構築
+    let parent_map = rewrite_filtered_parents(&oids, &parents_of);
+
+    // Uncommittedがある場合、インデックスを1つずらす
+    let has_uncommitted = key.has_uncommitted;
+    let commit_offset = if has_uncommitted { 1 } else { 0 };
+
+    // 親マップを調整（Uncommittedを考慮）
+    let adjusted_parent_map: Vec<(usize, Vec<i32>)> = parent_map
+        .iter()
+        .map(|(idx, parents)| {
+            let new_idx = idx + commit_offset;
+            let new_parents: Vec<i32> = parents
+                .iter()
+                .map(|&p| {
+                    if p == NULL_VERTEX_ID {
+                        NULL_VERTEX_ID
+                    } else {
+                        p + commit_offset as i32
+                    }
+                })
+                .collect();
+            (new_idx, new_parents)
+        })
+        .collect();
+
+    let total_count = oids.len() + commit_offset;
+    let adjusted_head_index = head_index.map(|h| h + commit_offset);
+
+    // Uncommittedの親としてHEADを追加（HEADがグラフ外ならグラフ外の親、未コミットのリポジトリなら親なし）
+    let final_parent_map = if has_uncommitted {
+        let uncommitted_parent = match (adjusted_head_index, &key.head_oid) {
+            (Some(head), _) => vec![head as i32],
+            (None, Some(_)) => vec![NULL_VERTEX_ID],
+            (None, None) => vec![],
+        };
+        let mut map = vec![(0, uncommitted_parent)];
+        map.extend(adjusted_parent_map);
+        map
+    } else {
+        adjusted_parent_map
+    };
+
+    // グラフを構築
+    let layout = layout_graph(
+        total_count,
+        &final_parent_map,
+        adjusted_head_index,
+        has_uncommitted,
+    );
+
+    // コミットデータを生成
+    let mut commits = vec![];
+    let merge_lines = vec![];
+
+    // Uncommitted Changesを先頭に追加
+    if has_uncommitted {
+        let (svg_paths, node_path) = layout.generate_svg_paths(0);
+        let uncommitted_row = CommitRow {
+            hash: "*".into(),
+            full_hash: "".into(),
+            message: uncommitted_message(uncommitted),
+            author: "*".into(),
+            date: chrono::Local::now()
+                .format("%d %b %H:%M")
+                .to_string()
+                .into(),
+            branches: vec![],
+            graph_column: layout.vertices.first().map_or(0, |v| v.column),
+            graph_color_idx: 0,
+            is_merge: false,
+            is_head: true,
+            is_uncommitted: true,
+            svg_paths,
+            node_path,
+        };
+        commits.push(uncommitted_row);
+    }
+
+    // 各コミットのデータを生成
+    for (idx, &oid) in oids.iter().enumerate() {
+        let Ok(commit) = repo.find_commit(oid) else {
+            continue;
+        };
+        let row = idx + commit_offset;
+
+        let time = commit.time();
+        let datetime: DateTime<Local> = Local
+            .timestamp_opt(time.seconds(), 0)
+            .single()
+            .unwrap_or_else(Local::now);
+        let oid_str = oid.to_string();
+
+        // ブランチ名
+        let branch_names = branch_heads.get(&oid_str).cloned().unwrap_or_default();
+        let mut refs: Vec<(bool, RefKind, &String)> = branch_names
+            .iter()
+            .map(|(name, kind)| {
+                let is_current = *kind == RefKind::Local && name == &key.current_branch;
+                (is_current, *kind, name)
+            })
+            .collect();
+        // 現在のブランチ → ローカル → リモート → タグの順
+        refs.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(b.2)));
+        let commit_branches: Vec<CommitBranchInfo> = refs
+            .into_iter()
+            .map(|(is_current, kind, name)| CommitBranchInfo {
+                name: name.clone().into(),
+                is_current,
+                is_remote: kind == RefKind::Remote,
+                ref_kind: kind.as_str().into(),
+            })
+            .collect();
+
+        let vertex = layout.vertices.get(row);
+        let column = vertex.map_or(0, |v| v.column);
+        let color_idx = vertex.map_or(0, |v| v.colour);
+        let is_merge = vertex.is_some_and(|v| v.is_merge);
+        let is_head = branch_names.iter().any(|(_, kind)| *kind != RefKind::Tag);
+        let (svg_paths, node_path) = layout.generate_svg_paths(row);
+
+        commits.push(CommitRow {
+            hash: oid.to_string()[..7].into(),
+            full_hash: oid.to_string().into(),
+            message: commit.summary().unwrap_or("").into(),
+            author: mapped_author_name(&commit, mailmap.as_ref()).into(),
+            date: datetime.format("%d %b %H:%M").to_string().into(),
+            branches: commit_branches,
+            graph_column: column,
+            graph_color_idx: (color_idx % GRAPH_COLOR_COUNT) as i32,
+            is_merge,
+            is_head,
+            is_uncommitted: false,
+            svg_paths,
+            node_path,
+        });
+    }
+
+    (commits, merge_lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::init_test_repo;
+    use std::fs;
+
+    #[test]
+    fn filtered_parents_skip_hidden_commits() {
+        let oid = |n: u8| Oid::from_bytes(&[n; 20]).unwrap();
+        // 4 -> 3 -> 2 -> 1 の直線で、3と2を除外。1の親（0）は走査していない
+        let parents_of: HashMap<Oid, Vec<Oid>> = [
+            (oid(4), vec![oid(3)]),
+            (oid(3), vec![oid(2)]),
+            (oid(2), vec![oid(1)]),
+            (oid(1), vec![oid(0)]),
+        ]
+        .into_iter()
+        .collect();
+        let kept = [oid(4), oid(1)];
+        assert_eq!(
+            rewrite_filtered_parents(&kept, &parents_of),
+            vec![(0, vec![1]), (1, vec![NULL_VERTEX_ID])]
+        );
+
+        // マージ: 5の親は3（除外）と1。3をたどると1なので親は1つにまとまる
+        let mut parents_of = parents_of;
+        parents_of.insert(oid(5), vec![oid(3), oid(1)]);
+        let kept = [oid(5), oid(1)];
+        assert_eq!(
+            rewrite_filtered_parents(&kept, &parents_of),
+            vec![(0, vec![1]), (1, vec![NULL_VERTEX_ID])]
+        );
+    }
+
+    #[test]
+    fn author_name_is_normalized_by_mailmap() {
+        let (dir, repo, oid) = init_test_repo("mailmap");
+        let commit = repo.find_commit(oid).unwrap();
+        assert_eq!(mapped_author_name(&commit, None), "Test");
+
+        fs::write(
+            dir.join(".mailmap"),
+            "Real Name <real@example.com> Test <test@example.com>\n",
+        )
+        .unwrap();
+        let mailmap = repo.mailmap().unwrap();
+        assert_eq!(mapped_author_name(&commit, Some(&mailmap)), "Real Name");
+    }
+
+    fn build_graph(parent_map: &[(usize, Vec<i32>)]) -> GraphLayout {
+        layout_graph(parent_map.len(), parent_map, Some(0), false)
+    }
+
+    fn colour_count(layout: &GraphLayout) -> usize {
+        layout
+            .branches
+            .iter()
+            .map(|b| b.colour + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// マージ頂点から各親の頂点まで線が引かれていることを確認
+    fn assert_parents_routed(layout: &GraphLayout, merge: usize, parents: &[usize]) {
+        for &parent in parents {
+            let target = Point {
+                x: layout.vertices[parent].column,
+                y: parent as i32,
+            };
+            let routed = layout.branches.iter().any(|b| {
+                b.lines.iter().any(|l| l.p1.y == merge as i32)
+                    && b.lines.iter().any(|l| l.p2 == target)
+            });
+            assert!(routed, "parent {} of {} has no line", parent, merge);
+        }
+        assert!(layout.vertices[merge].parents_processed);
+    }
+
+    /// 同時に存在するブランチ同士が同じ色を使っていないことを確認
+    fn assert_colours_disjoint(layout: &GraphLayout) {
+        let span = |b: &LayoutBranch| {
+            let start = b
+                .lines
+                .iter()
+                .map(|l| l.p1.y as usize)
+                .min()
+                .unwrap_or(b.end);
+            (start, b.end)
+        };
+        for (i, a) in layout.branches.iter().enumerate() {
+            for b in layout.branches.iter().skip(i + 1) {
+                if a.colour != b.colour {
+                    continue;
+                }
+                let ((a_start, a_end), (b_start, b_end)) = (span(a), span(b));
+                assert!(
+                    a_end < b_start || b_end < a_start,
+                    "colour {} shared by overlapping branches",
+                    a.colour
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn linear_history_stays_in_first_column() {
+        let parent_map = vec![(0, vec![1]), (1, vec![2]), (2, vec![])];
+        let layout = build_graph(&parent_map);
+        assert_eq!(layout.branches.len(), 1);
+        assert!(layout
+            .vertices
+            .iter()
+            .all(|v| v.column == 0 && v.colour == 0));
+        assert!(layout.vertices.iter().all(|v| v.branch == Some(0)));
+        assert!(layout.vertices[0].is_current);
+        let rows: Vec<(i32, i32)> = layout.branches[0]
+            .lines
+            .iter()
+            .map(|l| (l.p1.y, l.p2.y))
+            .collect();
+        assert_eq!(rows, vec![(0, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn merge_places_second_parent_in_next_column() {
+        // 0: マージ、1: mainのコミット、2: featureのコミット、3: 分岐元
+        let parent_map = vec![(0, vec![1, 2]), (1, vec![3]), (2, vec![3]), (3, vec![])];
+        let layout = build_graph(&parent_map);
+        let columns: Vec<i32> = layout.vertices.iter().map(|v| v.column).collect();
+        assert_eq!(columns, vec![0, 0, 1, 0]);
+        assert!(layout.vertices[0].is_merge);
+        assert_eq!(layout.vertices[2].branch, Some(1));
+        assert_ne!(layout.vertices[2].colour, layout.vertices[1].colour);
+        assert_parents_routed(&layout, 0, &[1, 2]);
+    }
+
+    #[test]
+    fn svg_paths_use_branch_colour_slot() {
+        let parent_map = vec![(0, vec![1, 2]), (1, vec![3]), (2, vec![3]), (3, vec![])];
+        let layout = build_graph(&parent_map);
+        let (paths, node_path) = layout.generate_svg_paths(2);
+        let feature_colour = layout.vertices[2].colour;
+        assert!(!paths[feature_colour].is_empty());
+        assert!(!paths[layout.vertices[1].colour].is_empty());
+        assert!(node_path.starts_with("M 23 14 "));
+    }
+
+    #[test]
+    fn uncommitted_row_connects_to_head() {
+        // 0: 未コミットの変更（親はHEAD）、1: 別ブランチの先頭、2: HEAD、3: 共通の祖先
+        let parent_map = vec![(0, vec![2]), (1, vec![3]), (2, vec![3]), (3, vec![])];
+        let layout = layout_graph(parent_map.len(), &parent_map, Some(2), true);
+        assert_parents_routed(&layout, 0, &[2]);
+        assert_eq!(layout.vertices[0].column, layout.vertices[2].column);
+    }
+
+    #[test]
+    fn merged_feature_branches_recycle_colours() {
+        // mainに短いfeatureブランチが順にマージされる履歴（偶数行: マージ、奇数行: feature）
+        let mut parent_map = Vec::new();
+        for k in 0..10 {
+            let merge = 2 * k;
+            parent_map.push((merge, vec![merge as i32 + 2, merge as i32 + 1]));
+            parent_map.push((merge + 1, vec![merge as i32 + 2]));
+        }
+        parent_map.push((20, vec![]));
+        let layout = build_graph(&parent_map);
+        assert_eq!(layout.branches.len(), 11);
+        // featureはマージ行から始まり、前のfeatureが終わる行と重なるため2色を交互に使う（mainと合わせて3色）
+        assert_eq!(colour_count(&layout), 3);
+        assert_colours_disjoint(&layout);
+    }
+
+    #[test]
+    fn single_commit_branch_frees_colour_on_its_row() {
+        // 0: 親のない単独コミット、1..2: 別の履歴
+        let parent_map = vec![(0, vec![]), (1, vec![2]), (2, vec![])];
+        let layout = build_graph(&parent_map);
+        assert_eq!(layout.vertices[1].colour, 0);
+        assert_eq!(colour_count(&layout), 1);
+    }
+
+    #[test]
+    fn consecutive_root_commits_share_one_colour() {
+        // 0..1: 親のない単独コミットが続く、2..3: 別の履歴
+        // 線を持たないブランチが次の行まで色を占有すると、行ごとに色が増えてしまう
+        let parent_map = vec![(0, vec![]), (1, vec![]), (2, vec![3]), (3, vec![])];
+        let layout = build_graph(&parent_map);
+        assert!(layout.vertices.iter().all(|v| v.colour == 0));
+        assert_eq!(colour_count(&layout), 1);
+    }
+
+    #[test]
+    fn long_lived_branches_keep_distinct_colours() {
+        // 2本の長寿命ブランチが途中で合流し、その後に新しいブランチが開始する
+        let parent_map = vec![
+            (0, vec![1, 2]),
+            (1, vec![3]),
+            (2, vec![4]),
+            (3, vec![4, 5]),
+            (4, vec![6]),
+            (5, vec![6]),
+            (6, vec![7]),
+            (7, vec![]),
+        ];
+        let layout = build_graph(&parent_map);
+        assert_colours_disjoint(&layout);
+        assert_eq!(colour_count(&layout), 3);
+    }
+
+    #[test]
+    fn octopus_merge_routes_every_parent() {
+        // 0: 3親のオクトパスマージ、1..3: 各親、4: 共通の祖先
+        let parent_map = vec![
+            (0, vec![1, 2, 3]),
+            (1, vec![4]),
+            (2, vec![4]),
+            (3, vec![4]),
+            (4, vec![]),
+        ];
+        let layout = build_graph(&parent_map);
+        assert_parents_routed(&layout, 0, &[1, 2, 3]);
+        // 各親は別の列に配置される
+        let columns: Vec<i32> = (1..=3).map(|i| layout.vertices[i].column).collect();
+        assert_eq!(columns, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn octopus_merge_into_existing_branches() {
+        // 親がすべて他のブランチ上にある場合はマージ線として合流する
+        let parent_map = vec![
+            (0, vec![3]),
+            (1, vec![4]),
+            (2, vec![3, 4, 5]),
+            (3, vec![5]),
+            (4, vec![5]),
+            (5, vec![]),
+        ];
+        let layout = build_graph(&parent_map);
+        assert_parents_routed(&layout, 2, &[3, 4, 5]);
+    }
+
+    #[test]
+    fn octopus_merge_with_parent_outside_graph() {
+        let parent_map = vec![(0, vec![1, NULL_VERTEX_ID, 2]), (1, vec![2]), (2, vec![])];
+        let layout = build_graph(&parent_map);
+        assert_parents_routed(&layout, 0, &[1, 2]);
+    }
+}
@@ -3,2421 +3,2783 @@
 use arboard::Clipboard;
 #[cfg(target_os = "linux")]
 use arboard::SetExtLinux;
-use chrono::{DateTime, Local, TimeZone};
-use git2::{BranchType, DiffOptions, Oid, Repository, StatusOptions};
-use slint::{Color, Model, ModelRc, SharedString, VecModel};
-use std::cell::RefCell;
-use std::collections::HashMap;
+use chrono::Local;
+use git2::{Oid, Repository};
+use slint::{Model, ModelRc, SharedString, VecModel};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+mod diff;
+mod file_tree;
+mod git_client;
+mod graph;
+mod persistence;
+mod remote;
+
+use crate::diff::{
+    binary_file_placeholder, compare_title, compute_commit_diff_in_thread, diff_path_at,
+    file_content_to_lines, filter_collapsed_hunks, find_text_matches, DiffCache, DiffCacheKey,
+    DiffStats, SharedDiffCache, TreeDiff, DIFF_CACHE_CAPACITY,
+};
+use crate::file_tree::{build_file_tree, file_tree_rows, files_under_folder};
+use crate::git_client::{
+    build_clone_args, check_commit_signatures, create_git_command, fetch_all, git_cli_available,
+    is_auth_failure, local_name_for_remote, patch_file_name, pull_repo, push_branch,
+    remote_command_error, run_git_with_progress, run_mergetool, split_stash_message,
+    uncommitted_count, BisectProgress, BranchLists, ChildSlot, ConflictSide, GitClient, GitError,
+    StashApplyOutcome, DEFAULT_DIFF_CONTEXT_LINES,
+};
+use crate::graph::{
+    build_commit_graph, uncommitted_message, CommitRow, GraphCache, GraphSelection,
+};
+use crate::persistence::{
+    add_recent_repo, clamp_window_geometry, load_commit_history, load_recent_repos,
+    load_repo_state, load_settings, recent_repos_display_order, record_fetch_time,
+    remove_missing_recent_repos, save_commit_history, save_repo_state_value, save_settings,
+    save_window_geometry, setting_str, toggle_pin_repo, RecentRepo, RecentRepoSort, WindowGeometry,
+};
+use crate::remote::RemoteTransport;
 
 slint::include_modules!();
 
-// Windowsでコンソールウィンドウを非表示にしてgitコマンドを作成するヘルパー
-#[cfg(target_os = "windows")]
-fn create_git_command() -> std::process::Command {
-    use std::os::windows::process::CommandExt;
-    const CREATE_NO_WINDOW: u32 = 0x08000000;
-    let mut cmd = std::process::Command::new("git");
-    cmd.creation_flags(CREATE_NO_WINDOW);
-    cmd
+/// クローン可能なgit URLに見えるか（https / ssh / git プロトコル、scp形式）
+fn is_git_url(text: &str) -> bool {
+    let text = text.trim();
+    !text.is_empty()
+        && !text.contains(char::is_whitespace)
+        && ["https://", "http://", "ssh://", "git://", "git@"]
+            .iter()
+            .any(|prefix| text.starts_with(prefix))
 }
 
-#[cfg(not(target_os = "windows"))]
-fn create_git_command() -> std::process::Command {
-    std::process::Command::new("git")
+/// ドロップされたファイル（URLを書いたテキストや .url ショートカット）からgit URLを取り出す
+fn dropped_clone_url(path: &Path) -> Option<String> {
+    const MAX_DROPPED_FILE_SIZE: u64 = 64 * 1024;
+    if fs::metadata(path).ok()?.len() > MAX_DROPPED_FILE_SIZE {
+        return None;
+    }
+    let content = fs::read_to_string(path).ok()?;
+    content
+        .lines()
+        .map(|line| line.trim())
+        .map(|line| line.strip_prefix("URL=").unwrap_or(line))
+        .find(|line| is_git_url(line))
+        .map(|line| line.to_string())
 }
 
-// ========== 別スレッドでのDiff計算 ==========
-
-/// 別スレッドでコミットのDiffファイル一覧とDiff内容を計算する
-fn compute_commit_diff_in_thread(
-    repo_path: String,
-    commit_hash: String,
-) -> (Vec<DiffFileData>, Vec<DiffLineData>, usize) {
-    let Ok(repo) = Repository::open(&repo_path) else {
-        return (vec![], vec![], 0);
-    };
-
-    if commit_hash.is_empty() {
-        return (vec![], vec![], 0);
+/// Cloneダイアログを開く（クローン実行中なら何もしない）
+fn open_clone_dialog(ui: &MainWindow, url: &str, path: &str) {
+    if ui.get_is_cloning() {
+        return;
     }
+    ui.set_show_repo_sidebar(false);
+    ui.set_clone_url(url.into());
+    ui.set_clone_path(path.into());
+    ui.set_clone_error("".into());
+    ui.set_show_clone_dialog(true);
+}
 
-    let Ok(commit) = repo.find_commit(Oid::from_str(&commit_hash).unwrap_or(Oid::zero())) else {
-        return (vec![], vec![], 0);
-    };
-    let Ok(tree) = commit.tree() else {
-        return (vec![], vec![], 0);
-    };
-
-    let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
-
-    let mut opts = DiffOptions::new();
-    let Ok(diff) = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut opts))
-    else {
-        return (vec![], vec![], 0);
-    };
-
-    // ファイル一覧を取得
-    let mut files = vec![];
-    for delta in diff.deltas() {
-        let status = match delta.status() {
-            git2::Delta::Added => "A",
-            git2::Delta::Deleted => "D",
-            git2::Delta::Modified => "M",
-            git2::Delta::Renamed => "R",
-            _ => "?",
-        };
-        let path = delta
-            .new_file()
-            .path()
-            .or_else(|| delta.old_file().path())
-            .map(|p| p.to_string_lossy().to_string())
-            .unwrap_or_default();
-
-        files.push(DiffFileData {
-            filename: path.into(),
-            status: status.into(),
-        });
-    }
-
-    // 最初のファイルのDiff内容を取得
-    let (diff_lines, total_count) = if !files.is_empty() {
-        let target_path = files[0].filename.to_string();
-        let mut opts = DiffOptions::new();
-        opts.pathspec(&target_path);
-        opts.context_lines(3);
-
-        if let Ok(diff) = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut opts))
-        {
-            parse_diff_standalone(&diff)
+/// ウィンドウにドロップされたパスを処理する
+/// リポジトリのフォルダは開き、それ以外のフォルダはクローン先に、git URLを含むファイルはクローン元にする
+fn handle_dropped_path(ui: &MainWindow, path: &Path) {
+    if path.is_dir() {
+        if Repository::discover(path).is_ok() {
+            ui.invoke_open_repo(path.to_string_lossy().to_string().into());
         } else {
-            (vec![], 0)
+            open_clone_dialog(ui, "", &path.to_string_lossy());
         }
-    } else {
-        (vec![], 0)
-    };
-
-    (files, diff_lines, total_count)
+        return;
+    }
+    match dropped_clone_url(path) {
+        Some(url) => open_clone_dialog(ui, &url, ""),
+        None => ui.set_status_message("Drop a repository folder to open it".into()),
+    }
 }
 
-/// Diff行数の上限（パフォーマンス対策）
-const MAX_DIFF_LINES: usize = 200;
-/// カウント上限（これ以上は計算しない）
-const MAX_COUNT_LINES: usize = 100000;
-
-/// Diffをパースするスタンドアロン関数
-fn parse_diff_standalone(diff: &git2::Diff) -> (Vec<DiffLineData>, usize) {
-    use std::cell::Cell;
-    let lines = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
-    let current_hunk_index = Cell::new(-1i32);
-    let truncated = Cell::new(false);
-    let total_lines = Cell::new(0usize);
-    let stop_processing = Cell::new(false);
-
-    let lines_clone = lines.clone();
-    let _ = diff.print(git2::DiffFormat::Patch, |delta, _hunk, line| {
-        if stop_processing.get() {
-            return false;
-        }
-
-        // カウント上限チェック
-        if total_lines.get() >= MAX_COUNT_LINES {
-            stop_processing.set(true);
-            return false;
-        }
-        total_lines.set(total_lines.get() + 1);
-
-        // 表示上限チェック
-        if lines_clone.borrow().len() >= MAX_DIFF_LINES {
-            truncated.set(true);
-            return true; // カウントのために継続
-        }
-
-        let line_type = match line.origin() {
-            '+' => "+",
-            '-' => "-",
-            ' ' => " ",
-            'H' | 'F' => "@@",
-            _ => "",
-        };
+/// 経過時間を表示用に整形（"5m ago"）
+fn format_elapsed_ago(elapsed_secs: i64) -> String {
+    let secs = elapsed_secs.max(0);
+    if secs < 60 {
+        "just now".into()
+    } else if secs < 3600 {
+        format!("{}m ago", secs / 60)
+    } else if secs < 86400 {
+        format!("{}h ago", secs / 3600)
+    } else {
+        format!("{}d ago", secs / 86400)
+    }
+}
 
-        if line.origin() == 'H' {
-            current_hunk_index.set(current_hunk_index.get() + 1);
-        }
+/// 最終fetchからの経過時間を表示用に整形（"Fetched 5m ago"）
+fn format_fetched_ago(elapsed_secs: i64) -> String {
+    format!("Fetched {}", format_elapsed_ago(elapsed_secs))
+}
 
-        let old_line_num = line.old_lineno().map(|n| n as i32).unwrap_or(0);
-        let new_line_num = line.new_lineno().map(|n| n as i32).unwrap_or(0);
-
-        if let Ok(content) = std::str::from_utf8(line.content()) {
-            if line.origin() == 'F' {
-                if let Some(path) = delta.new_file().path() {
-                    lines_clone.borrow_mut().push(DiffLineData {
-                        content: format!("--- {}", path.display()).into(),
-                        line_type: "diff".into(),
-                        old_line_num: 0,
-                        new_line_num: 0,
-                        hunk_index: -1,
-                    });
-                }
-            } else {
-                let text = content.trim_end_matches('\n');
-                if !text.is_empty() || line_type == " " {
-                    lines_clone.borrow_mut().push(DiffLineData {
-                        content: text.into(),
-                        line_type: line_type.into(),
-                        old_line_num,
-                        new_line_num,
-                        hunk_index: current_hunk_index.get(),
-                    });
-                }
-            }
-        }
-        true
-    });
+/// 現在のリポジトリの最終fetch時刻をUIに反映（記録がなければ非表示）
+fn update_last_fetched_text(ui: &MainWindow, repo_path: Option<&str>) {
+    let text = repo_path
+        .and_then(|path| load_repo_state(path).get("last_fetched")?.as_i64())
+        .map(|fetched_at| format_fetched_ago(Local::now().timestamp() - fetched_at))
+        .unwrap_or_default();
+    ui.set_last_fetched_text(text.into());
+}
 
-    let mut result = lines.borrow_mut().clone();
+/// 開いたリポジトリの保存済みUI状態をUIとGitClientに反映
+fn apply_repo_state(ui: &MainWindow, client: &mut GitClient, repo_path: &str) {
+    let state = load_repo_state(repo_path);
+    let context_lines = state
+        .get("diff_context_lines")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32)
+        .unwrap_or(DEFAULT_DIFF_CONTEXT_LINES);
+    let show_ignored = state
+        .get("show_ignored")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let group_untracked_dirs = state
+        .get("group_untracked_dirs")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let current_branch_only = state
+        .get("current_branch_only")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let show_repo_sidebar = state
+        .get("show_repo_sidebar")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(ui.get_show_repo_sidebar());
+
+    client.set_diff_context_lines(context_lines);
+    client.set_show_ignored(show_ignored);
+    client.set_group_untracked_dirs(group_untracked_dirs);
+    client.set_current_branch_only(current_branch_only);
+    ui.set_current_branch_only(current_branch_only);
+    // グラフの絞り込みはリポジトリごとに解除し、作者の候補を読み直す
+    client.set_graph_filter("", "");
+    ui.set_graph_author_index(0);
+    ui.set_graph_branch_index(0);
+    let mut authors = vec![SharedString::from("All authors")];
+    authors.extend(
+        client
+            .get_graph_authors()
+            .into_iter()
+            .map(SharedString::from),
+    );
+    ui.set_graph_author_options(Rc::new(VecModel::from(authors)).into());
+    ui.set_diff_context_lines(context_lines as i32);
+    ui.set_show_ignored_files(show_ignored);
+    ui.set_group_untracked_dirs(group_untracked_dirs);
+    ui.set_show_repo_sidebar(show_repo_sidebar);
+}
 
-    // 切り捨てメッセージを追加
-    if truncated.get() {
-        result.push(DiffLineData {
-            content: format!(
-                "... (truncated: diff exceeds {} lines, view on GitHub for full diff)",
-                MAX_DIFF_LINES
-            )
-            .into(),
-            line_type: "@@".into(),
-            old_line_num: 0,
-            new_line_num: 0,
-            hunk_index: -1,
-        });
+/// 件名と本文からコミットメッセージを組み立てる（本文があれば空行で区切る）
+fn assemble_commit_message(subject: &str, body: &str) -> String {
+    let subject = subject.trim();
+    let body = body.trim_start_matches(['\r', '\n']).trim_end();
+    if body.is_empty() {
+        subject.to_string()
+    } else {
+        format!("{}\n\n{}", subject, body)
     }
-
-    (result, total_lines.get())
 }
 
-// ========== リポジトリ履歴管理 ==========
-
-const MAX_RECENT_REPOS: usize = 10;
+/// コミットメッセージを件名（1行目）と本文に分ける（間の空行は除く）
+fn split_commit_message(message: &str) -> (String, String) {
+    let (subject, body) = message.split_once('\n').unwrap_or((message, ""));
+    (
+        subject.trim().to_string(),
+        body.trim_start_matches(['\r', '\n']).trim_end().to_string(),
+    )
+}
 
-fn get_config_path() -> std::path::PathBuf {
-    dirs::config_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("."))
-        .join("git-client")
-        .join("recent_repos.json")
+/// 本文で72文字を超える最初の行番号（1始まり、なければ0）
+fn first_long_body_line(body: &str) -> i32 {
+    body.lines()
+        .position(|line| line.chars().count() > 72)
+        .map(|i| i as i32 + 1)
+        .unwrap_or(0)
 }
 
-fn get_commit_history_path() -> std::path::PathBuf {
-    dirs::config_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("."))
-        .join("git-client")
-        .join("commit_history.json")
+/// コミットメッセージを件名と本文に分けてUIに設定
+fn set_commit_message_ui(ui: &MainWindow, message: &str) {
+    let (subject, body) = split_commit_message(message);
+    ui.set_commit_subject(subject.into());
+    ui.set_commit_body(body.into());
 }
 
-fn load_commit_history() -> Vec<String> {
-    let path = get_commit_history_path();
-    if let Ok(content) = fs::read_to_string(&path) {
-        serde_json::from_str(&content).unwrap_or_default()
-    } else {
-        Vec::new()
+/// ブランチ名からチケットIDを抜き出す既定のパターン（例: feature/ABC-123-login → ABC-123）
+const DEFAULT_TICKET_PATTERN: &str = r"[A-Z][A-Z0-9]+-[0-9]+";
+
+/// ブランチ名からチケットIDを抽出（パターンが空・不正、または一致しなければNone）
+fn extract_ticket_id(branch: &str, pattern: &str) -> Option<String> {
+    if pattern.trim().is_empty() {
+        return None;
     }
+    let re = regex::Regex::new(pattern).ok()?;
+    re.find(branch).map(|m| m.as_str().to_string())
 }
 
-fn save_commit_history(history: &[String]) {
-    let path = get_commit_history_path();
-    if let Some(parent) = path.parent() {
-        let _ = fs::create_dir_all(parent);
-    }
-    if let Ok(json) = serde_json::to_string_pretty(history) {
-        let _ = fs::write(&path, json);
+/// 件名の先頭にチケットIDを付ける（既に含まれていればそのまま）
+fn prepend_ticket_id(subject: &str, ticket: &str) -> String {
+    if subject.contains(ticket) {
+        subject.to_string()
+    } else {
+        format!("{} {}", ticket, subject).trim_end().to_string()
     }
 }
 
-fn load_recent_repos() -> Vec<String> {
-    let path = get_config_path();
-    if let Ok(content) = fs::read_to_string(&path) {
-        serde_json::from_str(&content).unwrap_or_default()
+/// コミットメッセージを設定の正規表現で検証（パターンが空なら常にOK）
+fn validate_commit_message(message: &str, pattern: &str) -> Result<(), String> {
+    if pattern.trim().is_empty() {
+        return Ok(());
+    }
+    let re =
+        regex::Regex::new(pattern).map_err(|e| format!("Invalid commit message pattern: {}", e))?;
+    if re.is_match(message) {
+        Ok(())
     } else {
-        Vec::new()
+        Err(format!(
+            "Commit message does not match the required pattern: {}",
+            pattern
+        ))
     }
 }
 
-fn save_recent_repos(repos: &[String]) {
-    let path = get_config_path();
-    if let Some(parent) = path.parent() {
-        let _ = fs::create_dir_all(parent);
+/// "Name <email>" 形式の作者指定を解析（空ならNone＝設定済みの作者を使用）
+fn parse_author(input: &str) -> Result<Option<(String, String)>, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
     }
-    if let Ok(json) = serde_json::to_string_pretty(repos) {
-        let _ = fs::write(&path, json);
+    let invalid = || format!("Invalid author \"{}\": expected \"Name <email>\"", input);
+    let (name, rest) = input.split_once('<').ok_or_else(invalid)?;
+    let email = rest.strip_suffix('>').ok_or_else(invalid)?;
+    let (name, email) = (name.trim(), email.trim());
+    if name.is_empty() || email.is_empty() {
+        return Err(invalid());
     }
+    Ok(Some((name.to_string(), email.to_string())))
 }
 
-fn add_recent_repo(path: &str) -> Vec<String> {
-    let mut repos = load_recent_repos();
-    // 既存のエントリを削除
-    repos.retain(|p| p != path);
-    // 先頭に追加
-    repos.insert(0, path.to_string());
-    // 最大数を超えたら削除
-    repos.truncate(MAX_RECENT_REPOS);
-    save_recent_repos(&repos);
-    repos
+/// Diff行を表示する（hunkの折りたたみ状態はリセット）
+fn show_diff_lines(ui: &MainWindow, lines: Vec<DiffLineData>) {
+    let model: ModelRc<DiffLineData> = Rc::new(VecModel::from(lines)).into();
+    ui.set_hunk_collapsed(ModelRc::default());
+    ui.set_all_hunks_collapsed(false);
+    ui.set_diff_source_lines(model.clone());
+    ui.set_diff_lines(model);
 }
 
-// クリップボードにテキストをコピー（クロスプラットフォーム対応・非同期）
-// Linux: 別スレッドで.wait()を使用してクリップボードマネージャーに内容が渡されるまで待機
-// Windows/macOS: クリップボードは同期的に動作するため、通常のset_text()を使用
-#[cfg(target_os = "linux")]
-fn copy_to_clipboard_async(text: String) {
-    std::thread::spawn(move || {
-        if let Ok(mut clipboard) = Clipboard::new() {
-            let _ = clipboard.set().wait().text(&text);
-        }
-    });
+/// 開いた直後に別スレッドで読み込んだブランチ一覧とコミットグラフ
+type LoadedHistory = (BranchLists, Option<(Vec<CommitRow>, Vec<MergeLineData>)>);
+
+/// ブランチ・ワークツリー一覧と現在のブランチの状態を表示する
+fn show_branch_lists(ui: &MainWindow, client: &GitClient, lists: BranchLists) {
+    let current_branch = client.get_current_branch();
+    ui.set_branch_ticket(
+        extract_ticket_id(&current_branch, &ui.get_ticket_pattern())
+            .unwrap_or_default()
+            .into(),
+    );
+    ui.set_current_branch(current_branch.into());
+    let (ahead, behind) = client.get_ahead_behind();
+    ui.set_current_ahead(ahead as i32);
+    ui.set_current_behind(behind as i32);
+    ui.set_head_detached_at(client.get_detached_head().unwrap_or_default().into());
+    // bisect中は現在テスト中のコミットとbadのコミットをグラフで強調する
+    let bisect = client.bisect_hashes();
+    ui.set_bisecting(bisect.is_some());
+    if bisect.is_none() {
+        ui.set_bisect_status("".into());
+    }
+    let (bisect_current, bisect_bad) = bisect.unwrap_or_default();
+    ui.set_bisect_current_hash(bisect_current.into());
+    ui.set_bisect_bad_hash(bisect_bad.into());
+    ui.set_local_branches(Rc::new(slint::VecModel::from(lists.local)).into());
+    let remote_names: Vec<SharedString> = lists.remote.iter().map(|b| b.name.clone()).collect();
+    ui.set_remote_branch_names(Rc::new(slint::VecModel::from(remote_names)).into());
+    ui.set_remote_branches(Rc::new(slint::VecModel::from(lists.remote)).into());
+    ui.set_worktrees(Rc::new(slint::VecModel::from(lists.worktrees)).into());
+    // グラフのブランチ絞り込みの候補（先頭は絞り込みなし）
+    let branch_options: Vec<SharedString> = std::iter::once("All branches".into())
+        .chain(ui.get_local_branches().iter().map(|b| b.name))
+        .chain(ui.get_remote_branches().iter().map(|b| b.name))
+        .collect();
+    // ブランチの追加・削除で位置がずれるので名前で選択し直す（消えていれば絞り込みなしと同じ表示）
+    let filter = client.graph_branch_filter();
+    let branch_index = branch_options
+        .iter()
+        .skip(1)
+        .position(|name| !filter.is_empty() && name.as_str() == filter)
+        .map_or(0, |i| i + 1);
+    ui.set_graph_branch_options(Rc::new(slint::VecModel::from(branch_options)).into());
+    ui.set_graph_branch_index(branch_index as i32);
+    update_last_fetched_text(ui, client.get_repo_path().as_deref());
 }
 
-#[cfg(not(target_os = "linux"))]
-fn copy_to_clipboard_async(text: String) {
-    // Windows/macOSではクリップボードが同期的に動作するため、
-    // オブジェクトがドロップされてもデータは保持される
-    if let Ok(mut clipboard) = Clipboard::new() {
-        let _ = clipboard.set_text(&text);
+/// 作業ツリーのファイルのDiffを取得する
+/// 結合表示がオンで一部だけステージされたファイルなら、StagedとUnstagedを続けて返す
+fn working_file_diff(
+    ui: &MainWindow,
+    client: &GitClient,
+    filename: &str,
+    staged: bool,
+) -> (Vec<DiffLineData>, usize) {
+    let combined = ui.get_combined_file_diff()
+        && ui
+            .get_staged_files()
+            .iter()
+            .any(|f| f.filename == filename && f.partially_staged);
+    ui.set_current_diff_is_combined(combined);
+    if combined {
+        client.get_combined_file_diff(filename)
+    } else {
+        client.get_file_diff(filename, staged)
     }
 }
 
-// Graph用の色パレット
-const GRAPH_COLORS: [(u8, u8, u8); 16] = [
-    (53, 132, 228),  // Blue
-    (46, 194, 126),  // Green
-    (245, 194, 17),  // Yellow
-    (224, 27, 36),   // Red
-    (145, 65, 172),  // Purple
-    (255, 120, 0),   // Orange
-    (0, 184, 212),   // Cyan
-    (233, 30, 99),   // Pink
-    (79, 195, 247),  // Light Blue
-    (129, 199, 132), // Light Green
-    (255, 183, 77),  // Light Orange
-    (240, 98, 146),  // Light Pink
-    (186, 104, 200), // Light Purple
-    (77, 182, 172),  // Teal
-    (174, 213, 129), // Lime
-    (144, 164, 174), // Blue Grey
-];
-
-fn get_color(idx: usize) -> Color {
-    let (r, g, b) = GRAPH_COLORS[idx % GRAPH_COLORS.len()];
-    Color::from_rgb_u8(r, g, b)
+/// 表示中のStaged / Unstaged一覧からUncommittedの変更数を数える
+fn shown_uncommitted_count(ui: &MainWindow) -> usize {
+    let staged: Vec<FileData> = ui.get_staged_files().iter().collect();
+    let unstaged: Vec<FileData> = ui.get_unstaged_files().iter().collect();
+    uncommitted_count(&staged, &unstaged)
+}
+
+/// ツリー表示で折りたたんだディレクトリ（Staged側, Unstaged側）
+type CollapsedTreeDirs = Rc<RefCell<(HashSet<String>, HashSet<String>)>>;
+
+/// ツリー表示がオンならStaged / Unstagedのファイル一覧からツリーの行を作り直す
+fn show_file_trees(ui: &MainWindow, collapsed: &CollapsedTreeDirs) {
+    if !ui.get_file_tree_view() {
+        ui.set_staged_tree(ModelRc::default());
+        ui.set_unstaged_tree(ModelRc::default());
+        return;
+    }
+    let collapsed = collapsed.borrow();
+    let rows = |files: ModelRc<FileData>, collapsed: &HashSet<String>| {
+        let files: Vec<FileData> = files.iter().collect();
+        let tree_rows = file_tree_rows(&build_file_tree(&files), collapsed);
+        ModelRc::from(Rc::new(VecModel::from(tree_rows)))
+    };
+    ui.set_staged_tree(rows(ui.get_staged_files(), &collapsed.0));
+    ui.set_unstaged_tree(rows(ui.get_unstaged_files(), &collapsed.1));
 }
 
-// ========== Git Graphのデータ構造 ==========
+/// コミットのDiff（ファイル一覧・内容・統計）を表示する
+fn show_commit_diff(ui: &MainWindow, (diff_files, diff_lines, total_count, stats): TreeDiff) {
+    ui.set_diff_files(Rc::new(VecModel::from(diff_files)).into());
+    ui.set_selected_diff_file(-1);
+    show_diff_lines(ui, diff_lines);
+    ui.set_diff_total_lines(total_count as i32);
+    stats.apply_to(ui);
+}
 
-const NULL_VERTEX_ID: i32 = -1;
+/// 折りたたみ状態を反映してDiff行を表示し直す
+fn apply_hunk_collapse(ui: &MainWindow, collapsed: std::collections::HashSet<i32>) {
+    let source = ui.get_diff_source_lines();
+    let hunk_count = source
+        .iter()
+        .map(|line| line.hunk_index + 1)
+        .max()
+        .unwrap_or(0)
+        .max(0);
+    let flags: Vec<bool> = (0..hunk_count).map(|i| collapsed.contains(&i)).collect();
+    ui.set_all_hunks_collapsed(hunk_count > 0 && flags.iter().all(|&c| c));
+    ui.set_hunk_collapsed(Rc::new(VecModel::from(flags)).into());
+    let lines = filter_collapsed_hunks(source.iter(), &collapsed);
+    ui.set_diff_lines(Rc::new(VecModel::from(lines)).into());
+}
 
-#[derive(Clone, Copy)]
-struct Point {
-    x: i32,
-    y: i32,
+/// push済みの履歴を書き換える操作の前に確認ダイアログを表示（actionは確認後に再実行する操作）
+fn show_rewrite_warning(ui: &MainWindow, action: &str, message: &str) {
+    ui.set_rewrite_action(action.into());
+    ui.set_rewrite_warning_text(message.into());
+    ui.set_show_rewrite_warning(true);
 }
 
-#[derive(Clone)]
-struct Line {
-    p1: Point,
-    p2: Point,
-    locked_first: bool, // TRUE => 線はp1に固定, FALSE => 線はp2に固定
+/// チェックアウトが未コミットの変更と衝突した場合にStash / Force / Cancelを選ぶダイアログを出す
+/// 衝突以外のエラーならfalseを返す（呼び出し側で通常のエラー表示をする）
+fn show_checkout_conflict(ui: &MainWindow, branch: &str, error: &GitError) -> bool {
+    let GitError::CheckoutConflict(files) = error else {
+        return false;
+    };
+    ui.set_checkout_conflict_branch(branch.into());
+    ui.set_checkout_conflict_files(files.join(", ").into());
+    ui.set_show_checkout_conflict(true);
+    true
 }
 
-#[derive(Clone)]
-struct UnavailablePoint {
-    connects_to: i32, // Vertex ID or NULL_VERTEX_ID
-    on_branch: usize, // Branch index
+/// 設定の「組み込みgitを使う」からリモート操作の実行方法を決める
+fn remote_transport(use_embedded_git: bool) -> RemoteTransport {
+    if use_embedded_git {
+        RemoteTransport::Embedded
+    } else {
+        RemoteTransport::Cli
+    }
 }
 
-/// Git GraphのBranchクラス
-struct Branch {
-    colour: usize,
-    end: usize,
-    lines: Vec<Line>,
-    num_uncommitted: usize,
+/// 確認ダイアログを出す破壊的操作の種類（種類ごとに「次回から確認しない」を設定に保存）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ConfirmKind {
+    Discard,
+    HardReset,
+    ForceDelete,
+    StashDrop,
 }
 
-impl Branch {
-    fn new(colour: usize) -> Self {
-        Self {
-            colour,
-            end: 0,
-            lines: Vec::new(),
-            num_uncommitted: 0,
+impl ConfirmKind {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "discard" => Some(Self::Discard),
+            "hard-reset" => Some(Self::HardReset),
+            "force-delete" => Some(Self::ForceDelete),
+            "stash-drop" => Some(Self::StashDrop),
+            _ => None,
         }
     }
 
-    fn add_line(&mut self, p1: Point, p2: Point, is_committed: bool, locked_first: bool) {
-        self.lines.push(Line {
-            p1,
-            p2,
-            locked_first,
-        });
-        if is_committed {
-            if p2.x == 0 && (p2.y as usize) < self.num_uncommitted {
-                self.num_uncommitted = p2.y as usize;
-            }
-        } else {
-            self.num_uncommitted += 1;
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Discard => "discard",
+            Self::HardReset => "hard-reset",
+            Self::ForceDelete => "force-delete",
+            Self::StashDrop => "stash-drop",
         }
     }
 
-    fn get_colour(&self) -> usize {
-        self.colour
+    fn setting_key(self) -> &'static str {
+        match self {
+            Self::Discard => "confirm_discard",
+            Self::HardReset => "confirm_hard_reset",
+            Self::ForceDelete => "confirm_force_delete",
+            Self::StashDrop => "confirm_stash_drop",
+        }
     }
+}
+
+/// 確認が必要か（未設定なら安全側に倒して確認する）
+fn confirm_enabled(
+    settings: &serde_json::Map<String, serde_json::Value>,
+    kind: ConfirmKind,
+) -> bool {
+    settings
+        .get(kind.setting_key())
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true)
+}
 
-    fn set_end(&mut self, end: usize) {
-        self.end = end;
+/// 確認設定をUIに反映
+fn set_confirm_preference_ui(ui: &MainWindow, kind: ConfirmKind, enabled: bool) {
+    match kind {
+        ConfirmKind::Discard => ui.set_confirm_discard(enabled),
+        ConfirmKind::HardReset => ui.set_confirm_hard_reset(enabled),
+        ConfirmKind::ForceDelete => ui.set_confirm_force_delete(enabled),
+        ConfirmKind::StashDrop => ui.set_confirm_stash_drop(enabled),
     }
 }
 
-/// Git GraphのVertexクラス
-struct Vertex {
-    id: i32,
-    x: i32,
-    children: Vec<i32>,
-    parents: Vec<i32>,
-    next_parent: usize,
-    on_branch: Option<usize>, // Branch index
-    is_committed: bool,
-    is_current: bool,
-    next_x: i32,
-    connections: Vec<UnavailablePoint>,
+/// 確認待ちの破壊的操作（確認後に同じコールバックを再実行する）
+#[derive(Clone, Debug)]
+enum PendingConfirm {
+    DiscardFile(String),
+    DiscardSelected,
+    DiscardFolder(String),
+    ResetToCommit { hash: String, mode: String },
+    ResetToReflogEntry { hash: String, mode: String },
+    ForceDelete(String),
+    StashDrop(i32),
 }
 
-impl Vertex {
-    fn new(id: i32) -> Self {
-        Self {
-            id,
-            x: 0,
-            children: Vec::new(),
-            parents: Vec::new(),
-            next_parent: 0,
-            on_branch: None,
-            is_committed: true,
-            is_current: false,
-            next_x: 0,
-            connections: Vec::new(),
+impl PendingConfirm {
+    fn kind(&self) -> ConfirmKind {
+        match self {
+            Self::DiscardFile(_) | Self::DiscardSelected | Self::DiscardFolder(_) => {
+                ConfirmKind::Discard
+            }
+            Self::ResetToCommit { .. } | Self::ResetToReflogEntry { .. } => ConfirmKind::HardReset,
+            Self::ForceDelete(_) => ConfirmKind::ForceDelete,
+            Self::StashDrop(_) => ConfirmKind::StashDrop,
         }
     }
+}
 
-    fn add_child(&mut self, child_id: i32) {
-        self.children.push(child_id);
-    }
+/// 共通の確認ダイアログを表示し、確認後に実行する操作を保持する
+fn request_confirm(
+    ui: &MainWindow,
+    pending: &RefCell<Option<PendingConfirm>>,
+    action: PendingConfirm,
+    title: &str,
+    detail: &str,
+    button: &str,
+) {
+    *pending.borrow_mut() = Some(action);
+    ui.set_confirm_action_title(title.into());
+    ui.set_confirm_action_detail(detail.into());
+    ui.set_confirm_action_button(button.into());
+    ui.set_confirm_action_dont_ask(false);
+    ui.set_show_confirm_action(true);
+}
 
-    fn add_parent(&mut self, parent_id: i32) {
-        self.parents.push(parent_id);
-    }
+/// チェックしたUnstagedファイルをステージし、ステージできた件数を返す
+fn stage_checked_files(ui: &MainWindow, client: &GitClient) -> usize {
+    let files = ui.get_unstaged_files();
+    let checked = ui.get_unstaged_checked();
+    let mut staged_count = 0;
 
-    #[allow(dead_code)]
-    fn has_parents(&self) -> bool {
-        !self.parents.is_empty()
+    for i in 0..files.row_count() {
+        if let (Some(file), Some(is_checked)) = (files.row_data(i), checked.row_data(i)) {
+            if is_checked && client.stage_file(&file.filename).is_ok() {
+                staged_count += 1;
+            }
+        }
     }
+    staged_count
+}
 
-    fn get_next_parent(&self) -> Option<i32> {
-        self.parents.get(self.next_parent).copied()
+/// コミットメッセージ履歴に残す件名の数
+const MAX_COMMIT_HISTORY: usize = 10;
+
+/// コミット欄の内容でコミットする（Commit / Commit and Push で共用）
+/// 成功したら件名を履歴に追加して入力欄を空にする
+/// 失敗時はステータスバーに出すメッセージを返す（件名が空なら何も出さないので空文字）
+fn prepare_and_commit(
+    ui: &MainWindow,
+    client: &GitClient,
+    history: &RefCell<Vec<String>>,
+) -> Result<Oid, String> {
+    let subject = ui.get_commit_subject().trim().to_string();
+    if subject.is_empty() {
+        return Err(String::new());
     }
-
-    fn register_parent_processed(&mut self) {
-        self.next_parent += 1;
+    let message = assemble_commit_message(&subject, &ui.get_commit_body());
+    validate_commit_message(&message, &ui.get_commit_message_pattern())?;
+    if !client.has_identity() {
+        ui.set_show_identity_dialog(true);
+        return Err("Set your git identity first".to_string());
     }
-
-    fn is_merge(&self) -> bool {
-        self.parents.len() > 1
+    let author = parse_author(&ui.get_commit_author())?;
+    let author = author.as_ref().map(|(n, e)| (n.as_str(), e.as_str()));
+    // 設定が有効なら、チェックしたUnstagedファイルをステージしてからコミットする
+    if ui.get_stage_checked_on_commit() {
+        stage_checked_files(ui, client);
     }
+    let oid = client
+        .commit(&message, ui.get_commit_allow_empty(), author)
+        .map_err(|e| format!("Commit error: {}", e))?;
+
+    // 件名を履歴に記録（既に存在する場合は削除してから先頭に追加）し、ファイルにも保存
+    let mut hist = history.borrow_mut();
+    hist.retain(|m| m != &subject);
+    hist.insert(0, subject);
+    hist.truncate(MAX_COMMIT_HISTORY);
+    let model: Vec<SharedString> = hist
+        .iter()
+        .map(|s| SharedString::from(s.as_str()))
+        .collect();
+    ui.set_commit_message_history(ModelRc::new(VecModel::from(model)));
+    save_commit_history(&hist);
 
-    fn add_to_branch(&mut self, branch_idx: usize, x: i32) {
-        if self.on_branch.is_none() {
-            self.on_branch = Some(branch_idx);
-            self.x = x;
-        }
-    }
+    set_commit_message_ui(ui, "");
+    ui.set_commit_history_index(-1);
+    ui.set_commit_allow_empty(false);
+    Ok(oid)
+}
 
-    fn is_not_on_branch(&self) -> bool {
-        self.on_branch.is_none()
-    }
+/// 最近使用したリポジトリ一覧をUIに反映（存在しないものはmissingとしてマーク）
+/// 並び順と検索文字列はUIの状態から取得する
+fn set_recent_repos_ui(ui: &MainWindow, repos: &[RecentRepo]) {
+    let sort = RecentRepoSort::from_index(ui.get_recent_repos_sort());
+    let filter = ui.get_recent_repos_filter();
+    let now = Local::now().timestamp();
+    let model: Vec<RecentRepoData> = recent_repos_display_order(repos, sort, filter.as_str())
+        .into_iter()
+        .map(|r| RecentRepoData {
+            missing: !Path::new(&r.path).exists(),
+            last_opened: if r.last_opened > 0 {
+                format_elapsed_ago(now - r.last_opened).into()
+            } else {
+                SharedString::default()
+            },
+            path: r.path.into(),
+            pinned: r.pinned,
+        })
+        .collect();
+    ui.set_has_missing_repos(repos.iter().any(|r| !Path::new(&r.path).exists()));
+    ui.set_recent_repos(ModelRc::new(VecModel::from(model)));
+}
 
-    #[allow(dead_code)]
-    fn is_on_this_branch(&self, branch_idx: usize) -> bool {
-        self.on_branch == Some(branch_idx)
-    }
+/// 表示中の一覧でのリポジトリの位置を選択状態にする（絞り込みで非表示なら-1）
+fn select_recent_repo(ui: &MainWindow, path: &str) {
+    let index = ui
+        .get_recent_repos()
+        .iter()
+        .position(|r| r.path.as_str() == path)
+        .map_or(-1, |i| i as i32);
+    ui.set_selected_repo_index(index);
+}
 
-    fn get_point(&self) -> Point {
-        Point {
-            x: self.x,
-            y: self.id,
-        }
-    }
+// ========== ブランチのあいまい検索 ==========
 
-    fn get_next_point(&self) -> Point {
-        Point {
-            x: self.next_x,
-            y: self.id,
+/// queryの文字がcandidateに順番通り含まれていればスコアを返す（大文字小文字は無視）
+/// 連続一致・区切り文字（/ - _）直後・先頭での一致を高く評価し、短い名前を優先する
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let query: Vec<char> = query.to_lowercase().chars().collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let chars: Vec<char> = candidate.to_lowercase().chars().collect();
+
+    let mut score = 0;
+    let mut qi = 0;
+    let mut prev_match: Option<usize> = None;
+    for (ci, &c) in chars.iter().enumerate() {
+        if qi >= query.len() {
+            break;
         }
+        if c != query[qi] {
+            continue;
+        }
+        score += 1;
+        if prev_match == Some(ci.wrapping_sub(1)) {
+            score += 5; // 連続一致
+        }
+        if ci == 0 {
+            score += 8; // 先頭一致
+        } else if matches!(chars[ci - 1], '/' | '-' | '_' | '.') {
+            score += 6; // 区切り直後
+        }
+        prev_match = Some(ci);
+        qi += 1;
     }
 
-    fn get_point_connecting_to(&self, vertex_id: i32, on_branch: usize) -> Option<Point> {
-        for (i, conn) in self.connections.iter().enumerate() {
-            if conn.connects_to == vertex_id && conn.on_branch == on_branch {
-                return Some(Point {
-                    x: i as i32,
-                    y: self.id,
-                });
-            }
-        }
-        None
+    if qi < query.len() {
+        return None;
     }
+    Some(score * 10 - chars.len() as i32)
+}
 
-    fn register_unavailable_point(&mut self, x: i32, connects_to: i32, on_branch: usize) {
-        if x == self.next_x {
-            self.next_x = x + 1;
-            // Ensure connections vector is large enough
-            while self.connections.len() <= x as usize {
-                self.connections.push(UnavailablePoint {
-                    connects_to: NULL_VERTEX_ID,
-                    on_branch: 0,
-                });
-            }
-            self.connections[x as usize] = UnavailablePoint {
-                connects_to,
-                on_branch,
-            };
+/// ローカル・リモートブランチをあいまい検索し、スコア順に並べて返す
+fn fuzzy_match_branches(
+    query: &str,
+    local: &[LocalBranchData],
+    remote: &[RemoteBranchData],
+) -> Vec<BranchMatchData> {
+    let candidates = local
+        .iter()
+        .map(|b| (b.name.to_string(), false))
+        .chain(remote.iter().map(|b| (b.name.to_string(), true)));
+
+    let mut matches: Vec<(i32, BranchMatchData)> = candidates
+        .filter_map(|(name, is_remote)| {
+            let score = fuzzy_score(query, &name)?;
+            Some((
+                score,
+                BranchMatchData {
+                    name: name.into(),
+                    is_remote,
+                },
+            ))
+        })
+        .collect();
+    // 同点ならローカルブランチを優先
+    matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.is_remote.cmp(&b.1.is_remote)));
+    matches.into_iter().map(|(_, m)| m).collect()
+}
+
+// クリップボードにテキストをコピー（クロスプラットフォーム対応・非同期）
+// Linux: 別スレッドで.wait()を使用してクリップボードマネージャーに内容が渡されるまで待機
+// Windows/macOS: クリップボードは同期的に動作するため、通常のset_text()を使用
+#[cfg(target_os = "linux")]
+fn copy_to_clipboard_async(text: String) {
+    std::thread::spawn(move || {
+        if let Ok(mut clipboard) = Clipboard::new() {
+            let _ = clipboard.set().wait().text(&text);
         }
-    }
+    });
+}
 
-    fn get_colour(&self, branches: &[Branch]) -> usize {
-        self.on_branch
-            .map(|b| branches[b].get_colour())
-            .unwrap_or(0)
+#[cfg(not(target_os = "linux"))]
+fn copy_to_clipboard_async(text: String) {
+    // Windows/macOSではクリップボードが同期的に動作するため、
+    // オブジェクトがドロップされてもデータは保持される
+    if let Ok(mut clipboard) = Clipboard::new() {
+        let _ = clipboard.set_text(&text);
     }
+}
 
-    fn set_not_committed(&mut self) {
-        self.is_committed = false;
+// ファイルをOSのファイルマネージャーで表示（Windows/macOSはファイルを選択した状態、その他は親フォルダを開く）
+// ファイルが削除済みの場合は存在する最も近い親フォルダを開く
+#[cfg(target_os = "windows")]
+fn reveal_in_file_manager(path: &Path) -> std::io::Result<()> {
+    if !path.exists() {
+        return open_nearest_dir(path);
     }
+    std::process::Command::new("explorer")
+        .arg(format!(
+            "/select,{}",
+            path.display().to_string().replace('/', "\\")
+        ))
+        .spawn()
+        .map(|_| ())
+}
 
-    fn set_current(&mut self) {
-        self.is_current = true;
+#[cfg(target_os = "macos")]
+fn reveal_in_file_manager(path: &Path) -> std::io::Result<()> {
+    if !path.exists() {
+        return open_nearest_dir(path);
     }
+    std::process::Command::new("open")
+        .arg("-R")
+        .arg(path)
+        .spawn()
+        .map(|_| ())
 }
 
-/// Git Graphのグラフ構築エンジン
-struct GraphBuilder {
-    vertices: Vec<Vertex>,
-    branches: Vec<Branch>,
-    available_colours: Vec<usize>,
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn reveal_in_file_manager(path: &Path) -> std::io::Result<()> {
+    open_nearest_dir(path)
 }
 
-impl GraphBuilder {
-    fn new() -> Self {
-        Self {
-            vertices: Vec::new(),
-            branches: Vec::new(),
-            available_colours: Vec::new(),
-        }
+fn open_nearest_dir(path: &Path) -> std::io::Result<()> {
+    match path.ancestors().skip(1).find(|p| p.is_dir()) {
+        Some(dir) => open::that(dir),
+        None => Err(std::io::ErrorKind::NotFound.into()),
     }
+}
 
-    /// コミットデータからグラフを構築
-    fn load_commits(
-        &mut self,
-        commit_count: usize,
-        parent_map: &[(usize, Vec<i32>)],
-        head_index: Option<usize>,
-        has_uncommitted: bool,
-    ) {
-        self.vertices.clear();
-        self.branches.clear();
-        self.available_colours.clear();
-
-        if commit_count == 0 {
-            return;
-        }
-
-        // 全コミットをVertexとして作成
-        for i in 0..commit_count {
-            self.vertices.push(Vertex::new(i as i32));
-        }
+fn main() -> Result<(), slint::PlatformError> {
+    let ui = MainWindow::new()?;
+    let git_client = Rc::new(RefCell::new(GitClient::new()));
 
-        // 親子関係を設定
-        for (idx, parents) in parent_map {
-            for &parent_id in parents {
-                if parent_id >= 0 && (parent_id as usize) < commit_count {
-                    self.vertices[*idx].add_parent(parent_id);
-                    self.vertices[parent_id as usize].add_child(*idx as i32);
-                } else if parent_id == NULL_VERTEX_ID {
-                    self.vertices[*idx].add_parent(NULL_VERTEX_ID);
-                }
+    // フォルダ・ファイルのドロップ（winitはドロップされたテキストを通知しないためファイルのみ）
+    {
+        use slint::winit_030::{winit::event::WindowEvent, EventResult, WinitWindowAccessor};
+        let ui_weak = ui.as_weak();
+        ui.window().on_winit_window_event(move |_, event| {
+            let WindowEvent::DroppedFile(path) = event else {
+                return EventResult::Propagate;
+            };
+            if let Some(ui) = ui_weak.upgrade() {
+                handle_dropped_path(&ui, path);
             }
-        }
+            EventResult::PreventDefault
+        });
+    }
 
-        // Uncommitted changesの設定
-        if has_uncommitted && !self.vertices.is_empty() {
-            self.vertices[0].set_not_committed();
+    // コミットメッセージ履歴を読み込み（最大10件保持）
+    // 履歴は件名のみ保持する（以前の形式のメッセージ全体は件名に変換）
+    let mut loaded_history: Vec<String> = vec![];
+    for message in load_commit_history() {
+        let (subject, _) = split_commit_message(&message);
+        if !subject.is_empty() && !loaded_history.contains(&subject) {
+            loaded_history.push(subject);
         }
+    }
+    let history_model: Vec<SharedString> = loaded_history
+        .iter()
+        .map(|s| SharedString::from(s.as_str()))
+        .collect();
+    ui.set_commit_message_history(ModelRc::new(VecModel::from(history_model)));
 
-        // HEADの設定
-        if let Some(head_idx) = head_index {
-            if head_idx < self.vertices.len() {
-                self.vertices[head_idx].set_current();
-            }
-        }
+    let commit_message_history: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(loaded_history));
 
-        // パスを決定
-        let mut i = 0;
-        while i < self.vertices.len() {
-            if self.vertices[i].get_next_parent().is_some() || self.vertices[i].is_not_on_branch() {
-                self.determine_path(i);
-            } else {
-                i += 1;
-            }
-        }
+    // アプリ設定を読み込み
+    let settings = load_settings();
+    ui.set_commit_message_pattern(setting_str(&settings, "commit_message_pattern").into());
+    ui.set_ticket_pattern(
+        settings
+            .get("ticket_pattern")
+            .and_then(|v| v.as_str())
+            .unwrap_or(DEFAULT_TICKET_PATTERN)
+            .into(),
+    );
+    ui.set_show_whitespace(
+        settings
+            .get("show_whitespace")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+    );
+    ui.set_stage_checked_on_commit(
+        settings
+            .get("stage_checked_on_commit")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+    );
+    ui.set_combined_file_diff(
+        settings
+            .get("combined_file_diff")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+    );
+    ui.set_file_tree_view(
+        settings
+            .get("file_tree_view")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+    );
+    // gitコマンドがなければCLIに頼る機能を無効化し、バナーで知らせる
+    let git_available = git_cli_available();
+    ui.set_git_available(git_available);
+    git_client.borrow_mut().set_git_available(git_available);
+    let run_commit_hooks = settings
+        .get("run_commit_hooks")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    ui.set_run_commit_hooks(run_commit_hooks);
+    git_client
+        .borrow_mut()
+        .set_run_commit_hooks(run_commit_hooks && git_available);
+    let use_embedded_git = settings
+        .get("use_embedded_git")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    ui.set_use_embedded_git(use_embedded_git);
+    git_client
+        .borrow_mut()
+        .set_remote_transport(remote_transport(use_embedded_git));
+    let theme = setting_str(&settings, "theme");
+    if matches!(theme.as_str(), "dark" | "light" | "system") {
+        ui.set_theme(theme.into());
+    }
+    let pull_mode = setting_str(&settings, "pull_mode");
+    if matches!(pull_mode.as_str(), "merge" | "ff-only" | "rebase") {
+        ui.set_pull_mode(pull_mode.into());
+    }
+    for kind in [
+        ConfirmKind::Discard,
+        ConfirmKind::HardReset,
+        ConfirmKind::ForceDelete,
+        ConfirmKind::StashDrop,
+    ] {
+        set_confirm_preference_ui(&ui, kind, confirm_enabled(&settings, kind));
     }
 
-    /// Git Graphのdetermine_path()相当 - パス決定アルゴリズム
-    fn determine_path(&mut self, start_at: usize) {
-        let parent_id = self.vertices[start_at].get_next_parent();
-
-        let last_point = if self.vertices[start_at].is_not_on_branch() {
-            self.vertices[start_at].get_next_point()
-        } else {
-            self.vertices[start_at].get_point()
-        };
-
-        if let Some(parent_id) = parent_id {
-            if parent_id != NULL_VERTEX_ID
-                && self.vertices[start_at].is_merge()
-                && !self.vertices[start_at].is_not_on_branch()
-                && !self.vertices[parent_id as usize].is_not_on_branch()
-            {
-                // マージ: 両方の頂点が既にブランチ上にある場合
-                self.handle_merge_path(start_at, parent_id, last_point);
-            } else {
-                // 通常のブランチ
-                self.handle_normal_path(start_at, last_point);
+    // 確認待ちの破壊的操作と、確認後の再実行中かどうか（再実行時は確認をスキップ）
+    let pending_confirm: Rc<RefCell<Option<PendingConfirm>>> = Rc::new(RefCell::new(None));
+    let confirm_bypass = Rc::new(Cell::new(false));
+
+    // 前回終了時のウィンドウ位置・サイズを復元
+    let saved_geometry = WindowGeometry::from_settings(&settings);
+    if let Some(geometry) = saved_geometry {
+        ui.window()
+            .set_size(slint::PhysicalSize::new(geometry.width, geometry.height));
+        ui.window()
+            .set_position(slint::PhysicalPosition::new(geometry.x, geometry.y));
+    }
+    let restore_maximized = settings
+        .get("window_maximized")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    // モニター情報はウィンドウ生成後にしか取れないため、イベントループ開始後に画面外なら補正する
+    {
+        let ui_weak = ui.as_weak();
+        slint::Timer::single_shot(std::time::Duration::ZERO, move || {
+            use slint::winit_030::WinitWindowAccessor;
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            if let Some(geometry) = saved_geometry {
+                let monitors: Vec<(i32, i32, u32, u32)> = ui
+                    .window()
+                    .with_winit_window(|window| {
+                        // プライマリモニターを先頭にする（補正先になる）
+                        let primary = window.primary_monitor();
+                        let mut monitors: Vec<_> = window.available_monitors().collect();
+                        monitors.sort_by_key(|m| Some(m) != primary.as_ref());
+                        monitors
+                            .iter()
+                            .map(|m| {
+                                let (pos, size) = (m.position(), m.size());
+                                (pos.x, pos.y, size.width, size.height)
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let clamped = clamp_window_geometry(geometry, &monitors);
+                if clamped != geometry {
+                    ui.window()
+                        .set_size(slint::PhysicalSize::new(clamped.width, clamped.height));
+                    ui.window()
+                        .set_position(slint::PhysicalPosition::new(clamped.x, clamped.y));
+                }
             }
-        } else {
-            // 親がない場合も通常パスとして処理
-            self.handle_normal_path(start_at, last_point);
-        }
+            if restore_maximized {
+                ui.window().set_maximized(true);
+            }
+        });
+    }
+    {
+        let ui_weak = ui.as_weak();
+        ui.window().on_close_requested(move || {
+            if let Some(ui) = ui_weak.upgrade() {
+                save_window_geometry(ui.window());
+            }
+            slint::CloseRequestResponse::HideWindow
+        });
     }
 
-    fn handle_merge_path(&mut self, start_at: usize, parent_id: i32, mut last_point: Point) {
-        let parent_branch = self.vertices[parent_id as usize].on_branch.unwrap();
-        let vertex_is_committed = self.vertices[start_at].is_committed;
-        let mut found_point_to_parent = false;
+    // 最近使用したリポジトリを読み込み
+    ui.set_recent_repos_sort(
+        settings
+            .get("recent_repos_sort")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0) as i32,
+    );
+    let recent_repos = load_recent_repos();
+    set_recent_repos_ui(&ui, &recent_repos);
 
-        for i in (start_at + 1)..self.vertices.len() {
-            let cur_point = if let Some(p) =
-                self.vertices[i].get_point_connecting_to(parent_id, parent_branch)
-            {
-                found_point_to_parent = true;
-                p
-            } else {
-                self.vertices[i].get_next_point()
-            };
+    // 履歴があれば最後に使用したリポジトリを選択、なければホームディレクトリ
+    let initial_repo = if let Some(last) = recent_repos.first() {
+        ui.set_repo_path(last.path.clone().into());
+        select_recent_repo(&ui, &last.path);
+        Some(last.path.clone())
+    } else if let Some(home) = dirs::home_dir() {
+        ui.set_repo_path(home.to_string_lossy().to_string().into());
+        None
+    } else {
+        None
+    };
 
-            let locked_first =
-                !found_point_to_parent && i != parent_id as usize && last_point.x < cur_point.x;
-            self.branches[parent_branch].add_line(
-                last_point,
-                cur_point,
-                vertex_is_committed,
-                locked_first,
-            );
-            self.vertices[i].register_unavailable_point(cur_point.x, parent_id, parent_branch);
-            last_point = cur_point;
+    // Fetchの実行中フラグ（手動Refreshと定期fetchの重複実行を防ぐ）
+    let fetch_running = Arc::new(AtomicBool::new(false));
 
-            if found_point_to_parent {
-                self.vertices[start_at].register_parent_processed();
-                break;
+    // 定期バックグラウンドfetch（間隔0で無効）
+    let auto_fetch_timer = Rc::new(slint::Timer::default());
+    let schedule_auto_fetch = {
+        let timer = auto_fetch_timer.clone();
+        let git_client = git_client.clone();
+        let ui_weak = ui.as_weak();
+        let fetch_running = fetch_running.clone();
+        move |minutes: i32| {
+            timer.stop();
+            if minutes <= 0 {
+                return;
             }
+            let git_client = git_client.clone();
+            let ui_weak = ui_weak.clone();
+            let fetch_running = fetch_running.clone();
+            timer.start(
+                slint::TimerMode::Repeated,
+                std::time::Duration::from_secs(minutes as u64 * 60),
+                move || {
+                    let Some(ui) = ui_weak.upgrade() else {
+                        return;
+                    };
+                    if ui.get_auto_fetch_paused() || !git_client.borrow().remote_available() {
+                        return;
+                    }
+                    // ユーザーが実行したPull / Pushの最中は重ねてfetchしない
+                    if !ui.get_remote_operation().is_empty() {
+                        return;
+                    }
+                    let Some(path) = git_client.borrow().get_repo_path() else {
+                        return;
+                    };
+                    let transport = git_client.borrow().remote_transport();
+                    // 前回のfetchがまだ終わっていなければスキップ
+                    if fetch_running
+                        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                        .is_err()
+                    {
+                        return;
+                    }
+                    let ui_weak = ui_weak.clone();
+                    let fetch_running = fetch_running.clone();
+                    std::thread::spawn(move || {
+                        let result = fetch_all(&path, transport, |_, _| {});
+                        fetch_running.store(false, Ordering::SeqCst);
+                        let _ = slint::invoke_from_event_loop(move || {
+                            if let Some(ui) = ui_weak.upgrade() {
+                                match result {
+                                    Ok(()) => record_fetch_time(&path),
+                                    Err(e) => ui.set_status_message(SharedString::from(format!(
+                                        "Auto fetch: {}",
+                                        e
+                                    ))),
+                                }
+                                ui.invoke_update_local_state();
+                            }
+                        });
+                    });
+                },
+            );
         }
+    };
+    let auto_fetch_minutes = settings
+        .get("auto_fetch_minutes")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(0) as i32;
+    ui.set_auto_fetch_interval(auto_fetch_minutes);
+    schedule_auto_fetch(auto_fetch_minutes);
+
+    // 「Fetched 5m ago」の表示を1分ごとに更新
+    let fetched_ago_timer = slint::Timer::default();
+    {
+        let git_client = git_client.clone();
+        let ui_weak = ui.as_weak();
+        fetched_ago_timer.start(
+            slint::TimerMode::Repeated,
+            std::time::Duration::from_secs(60),
+            move || {
+                if let Some(ui) = ui_weak.upgrade() {
+                    update_last_fetched_text(&ui, git_client.borrow().get_repo_path().as_deref());
+                }
+            },
+        );
     }
+    let schedule_auto_fetch = Rc::new(schedule_auto_fetch);
 
-    fn handle_normal_path(&mut self, start_at: usize, mut last_point: Point) {
-        let colour = self.get_available_colour(start_at);
-        let branch_idx = self.branches.len();
-        self.branches.push(Branch::new(colour));
+    // コミットグラフ計算の世代（古い計算結果でUIを上書きしないため）
+    let graph_generation = Arc::new(AtomicU64::new(0));
 
-        let vertex_id = self.vertices[start_at].id;
-        self.vertices[start_at].add_to_branch(branch_idx, last_point.x);
-        self.vertices[start_at].register_unavailable_point(last_point.x, vertex_id, branch_idx);
+    // Insights集計の世代（集計中にやり直した・別のリポジトリを開いた場合は古い結果を捨てる）
+    let stats_generation = Arc::new(AtomicU64::new(0));
 
-        let mut vertex_idx = start_at;
-        let mut i = start_at + 1;
+    // 最近計算したコミットDiff（HEADやブランチが変わったらクリア）
+    let diff_cache: SharedDiffCache = Arc::new(Mutex::new(DiffCache::new(DIFF_CACHE_CAPACITY)));
 
-        while i < self.vertices.len() {
-            let parent_id = self.vertices[vertex_idx].get_next_parent();
-
-            if parent_id.is_none() {
-                break;
-            }
+    // 開いた直後の読み込み中に要求されたブランチ一覧・グラフの更新（読み込み完了後にやり直す）
+    let history_refresh_pending = Rc::new(Cell::new(false));
 
-            let cur_point = if let Some(pid) = parent_id {
-                if pid != NULL_VERTEX_ID
-                    && pid as usize == i
-                    && !self.vertices[i].is_not_on_branch()
-                {
-                    self.vertices[i].get_point()
-                } else {
-                    self.vertices[i].get_next_point()
-                }
-            } else {
-                self.vertices[i].get_next_point()
+    // ブランチ・ワークツリー一覧を更新
+    let refresh_branches = {
+        let ui_weak = ui.as_weak();
+        let git_client = git_client.clone();
+        let history_refresh_pending = history_refresh_pending.clone();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
             };
-
-            let vertex_is_committed = self.vertices[vertex_idx].is_committed;
-            let locked_first = last_point.x < cur_point.x;
-            self.branches[branch_idx].add_line(
-                last_point,
-                cur_point,
-                vertex_is_committed,
-                locked_first,
-            );
-
-            if let Some(pid) = parent_id {
-                self.vertices[i].register_unavailable_point(cur_point.x, pid, branch_idx);
-            } else {
-                self.vertices[i].register_unavailable_point(
-                    cur_point.x,
-                    NULL_VERTEX_ID,
-                    branch_idx,
-                );
-            }
-
-            last_point = cur_point;
-
-            // 親に到達したかチェック
-            if let Some(pid) = parent_id {
-                if pid != NULL_VERTEX_ID && pid as usize == i {
-                    self.vertices[vertex_idx].register_parent_processed();
-                    let parent_on_branch = !self.vertices[i].is_not_on_branch();
-                    self.vertices[i].add_to_branch(branch_idx, cur_point.x);
-                    vertex_idx = i;
-
-                    let next_parent = self.vertices[vertex_idx].get_next_parent();
-                    if next_parent.is_none() || parent_on_branch {
-                        break;
-                    }
-                }
+            // 開いた直後の読み込み中は、完了時にまとめて反映する
+            if ui.get_loading_history() {
+                history_refresh_pending.set(true);
+                return;
             }
-            i += 1;
+            let client = git_client.borrow();
+            show_branch_lists(&ui, &client, client.branch_lists());
         }
+    };
 
-        // 最後の頂点で親がNULL_VERTEX_IDの場合
-        if i == self.vertices.len() {
-            if let Some(pid) = self.vertices[vertex_idx].get_next_parent() {
-                if pid == NULL_VERTEX_ID {
-                    self.vertices[vertex_idx].register_parent_processed();
-                }
-            }
+    // Stash一覧を更新
+    let refresh_stashes = {
+        let ui_weak = ui.as_weak();
+        let git_client = git_client.clone();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let mut client = git_client.borrow_mut();
+            let stashes = client.get_stashes();
+            ui.set_stash_count(stashes.len() as i32);
+            ui.set_stashes(Rc::new(slint::VecModel::from(stashes)).into());
         }
+    };
 
-        self.branches[branch_idx].set_end(i);
-        self.available_colours[colour] = i;
-    }
-
-    /// 利用可能な色を取得（Git Graphの色再利用ロジック）
-    fn get_available_colour(&mut self, start_at: usize) -> usize {
-        for (i, &end) in self.available_colours.iter().enumerate() {
-            if start_at > end {
-                return i;
-            }
-        }
-        self.available_colours.push(0);
-        self.available_colours.len() - 1
-    }
-
-    /// SVGパスを生成（線用パスとノード用パスを分離）
-    /// 戻り値: (線用パス[8], ノード用パス)
-    fn generate_svg_paths(&self, row: usize) -> ([String; 8], String) {
-        const COL_SPACING: f32 = 16.0;
-        const ROW_HEIGHT: f32 = 28.0;
-        const NODE_CENTER_Y: f32 = ROW_HEIGHT / 2.0;
-        const CURVE_OFFSET: f32 = ROW_HEIGHT * 0.8;
-        const NODE_RADIUS: f32 = 4.0;
-
-        let mut paths: [String; 8] = Default::default();
-        let mut node_path = String::new();
-
-        // このコミットを通過する全ブランチの線を描画
-        for branch in self.branches.iter() {
-            let color_idx = branch.get_colour() % 8;
-
-            for line in &branch.lines {
-                // この行に関係する線のみ処理
-                if line.p1.y as usize == row
-                    || line.p2.y as usize == row
-                    || (line.p1.y < row as i32 && line.p2.y > row as i32)
-                {
-                    let x1 = line.p1.x as f32 * COL_SPACING + 7.0;
-                    let y1 = line.p1.y as f32 * ROW_HEIGHT + NODE_CENTER_Y;
-                    let x2 = line.p2.x as f32 * COL_SPACING + 7.0;
-                    let y2 = line.p2.y as f32 * ROW_HEIGHT + NODE_CENTER_Y;
-
-                    // この行の範囲内の部分のみ描画
-                    let row_top = row as f32 * ROW_HEIGHT;
-                    let row_bottom = row_top + ROW_HEIGHT;
-
-                    if x1 == x2 {
-                        // 垂直線
-                        let draw_y1 = y1.max(row_top);
-                        let draw_y2 = y2.min(row_bottom);
-                        if draw_y1 < draw_y2 {
-                            // ローカル座標に変換
-                            let local_y1 = draw_y1 - row_top;
-                            let local_y2 = draw_y2 - row_top;
-                            paths[color_idx]
-                                .push_str(&format!("M {} {} L {} {} ", x1, local_y1, x1, local_y2));
-                        }
-                    } else {
-                        // 曲線（この行が始点または終点の場合のみ）
-                        if line.p1.y as usize == row || line.p2.y as usize == row {
-                            self.draw_curve_segment(
-                                &mut paths[color_idx],
-                                line,
-                                row,
-                                COL_SPACING,
-                                ROW_HEIGHT,
-                                CURVE_OFFSET,
-                            );
-                        }
-                    }
-                }
+    // コミットグラフを更新（同じリポジトリならコミット選択とスクロール位置を引き継ぐ）
+    // uncommittedは直前に表示したStaged/Unstaged一覧から数えた変更数（状態を数え直さないため）
+    let refresh_graph = {
+        let ui_weak = ui.as_weak();
+        let git_client = git_client.clone();
+        let graph_generation = graph_generation.clone();
+        let diff_cache = diff_cache.clone();
+        let last_repo_path: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
+        let history_refresh_pending = history_refresh_pending.clone();
+        move |uncommitted: usize| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            // 開いた直後の読み込み中は、完了時にまとめて反映する
+            if ui.get_loading_history() {
+                history_refresh_pending.set(true);
+                return;
             }
-        }
-
-        // ノードをSVGパスとして描画（線と同じ座標系）
-        if row < self.vertices.len() {
-            let vertex = &self.vertices[row];
-            let node_x = vertex.x as f32 * COL_SPACING + 7.0;
-            let node_y = NODE_CENTER_Y;
-
-            // 円を描画: M (x-r) y a r r 0 1 0 (2r) 0 a r r 0 1 0 (-2r) 0
-            node_path = format!(
-                "M {} {} m -{} 0 a {} {} 0 1 0 {} 0 a {} {} 0 1 0 -{} 0 ",
-                node_x,
-                node_y,
-                NODE_RADIUS,
-                NODE_RADIUS,
-                NODE_RADIUS,
-                NODE_RADIUS * 2.0,
-                NODE_RADIUS,
-                NODE_RADIUS,
-                NODE_RADIUS * 2.0
-            );
-        }
+            let client = git_client.borrow();
 
-        (paths, node_path)
-    }
-
-    fn draw_curve_segment(
-        &self,
-        path: &mut String,
-        line: &Line,
-        row: usize,
-        col_spacing: f32,
-        row_height: f32,
-        curve_offset: f32,
-    ) {
-        let node_center_y = row_height / 2.0;
-        let x1 = line.p1.x as f32 * col_spacing + 7.0;
-        let x2 = line.p2.x as f32 * col_spacing + 7.0;
-
-        if line.p1.y as usize == row {
-            // この行が始点
-            let local_y1 = node_center_y;
-            let local_y2 = row_height;
-
-            if line.locked_first {
-                // 上に固定: 曲線は下に向かう
-                let ctrl_y = local_y1 + curve_offset.min(row_height - node_center_y);
-                path.push_str(&format!(
-                    "M {} {} C {} {} {} {} {} {} ",
-                    x1, local_y1, x1, ctrl_y, x2, local_y2, x2, local_y2
-                ));
+            // リポジトリが切り替わった場合は選択とスクロールをリセット
+            let repo_path = client.get_repo_path();
+            let same_repo = *last_repo_path.borrow() == repo_path;
+            *last_repo_path.borrow_mut() = repo_path;
+            let selection = if same_repo {
+                GraphSelection::capture(&ui)
             } else {
-                // 下に固定: 直線で下へ、次の行で曲がる
-                path.push_str(&format!("M {} {} L {} {} ", x1, local_y1, x1, local_y2));
-            }
-        } else if line.p2.y as usize == row {
-            // この行が終点
-            let local_y1 = 0.0;
-            let local_y2 = node_center_y;
+                GraphSelection::default()
+            };
+
+            // コミットグラフ（トポロジーが変わっていなければキャッシュ、変わっていれば別スレッドで計算）
+            let generation = graph_generation.fetch_add(1, Ordering::SeqCst) + 1;
+            if let Some(key) = client.graph_cache_key(300, uncommitted) {
+                if let Some((commits, merge_lines)) = client.cached_graph(&key, uncommitted) {
+                    ui.set_commits(Rc::new(slint::VecModel::from(commits)).into());
+                    ui.set_merge_lines(Rc::new(slint::VecModel::from(merge_lines)).into());
+                    selection.restore(&ui);
+                    ui.invoke_request_visible_signatures();
+                } else if let Some(repo_path) = key.repo_path.clone() {
+                    // HEADやブランチが変わったのでDiffキャッシュも作り直す
+                    if let Ok(mut diff_cache) = diff_cache.lock() {
+                        diff_cache.clear();
+                    }
+                    if !same_repo {
+                        selection.restore(&ui);
+                    }
+                    let cache = client.graph_cache_handle();
+                    let graph_generation = graph_generation.clone();
+                    let ui_weak = ui.as_weak();
+                    std::thread::spawn(move || {
+                        let (rows, merge_lines) = build_commit_graph(&repo_path, &key, uncommitted);
+                        if let Ok(mut cache) = cache.lock() {
+                            *cache = Some(GraphCache {
+                                key,
+                                commits: rows.clone(),
+                                merge_lines: merge_lines.clone(),
+                            });
+                        }
 
-            if line.locked_first {
-                // 上に固定: 直線で上から来る
-                path.push_str(&format!("M {} {} L {} {} ", x2, local_y1, x2, local_y2));
+                        let _ = slint::invoke_from_event_loop(move || {
+                            // 計算中に再度refreshされた・リポジトリが切り替わった場合は破棄
+                            if graph_generation.load(Ordering::SeqCst) != generation {
+                                return;
+                            }
+                            let Some(ui) = ui_weak.upgrade() else {
+                                return;
+                            };
+                            // 計算中に選択し直されたコミットを上書きしないよう、置き換える直前の選択を引き継ぐ
+                            let selection = if same_repo {
+                                GraphSelection::capture(&ui)
+                            } else {
+                                selection
+                            };
+                            let commits: Vec<CommitData> =
+                                rows.into_iter().map(CommitRow::into_commit_data).collect();
+                            ui.set_commits(Rc::new(slint::VecModel::from(commits)).into());
+                            ui.set_merge_lines(Rc::new(slint::VecModel::from(merge_lines)).into());
+                            selection.restore(&ui);
+                            ui.invoke_request_visible_signatures();
+                        });
+                    });
+                }
             } else {
-                // 下に固定: 曲線で終点に向かう
-                let ctrl_y = local_y2 - curve_offset.min(node_center_y);
-                path.push_str(&format!(
-                    "M {} {} C {} {} {} {} {} {} ",
-                    x1, local_y1, x1, local_y1, x2, ctrl_y, x2, local_y2
-                ));
+                ui.set_commits(ModelRc::default());
+                ui.set_merge_lines(ModelRc::default());
+                GraphSelection::default().restore(&ui);
             }
         }
-    }
-
-    fn get_vertex_column(&self, row: usize) -> i32 {
-        if row < self.vertices.len() {
-            self.vertices[row].x
-        } else {
-            0
-        }
-    }
-
-    fn get_vertex_colour(&self, row: usize) -> usize {
-        if row < self.vertices.len() {
-            self.vertices[row].get_colour(&self.branches)
-        } else {
-            0
-        }
-    }
-
-    fn is_vertex_merge(&self, row: usize) -> bool {
-        if row < self.vertices.len() {
-            self.vertices[row].is_merge()
-        } else {
-            false
-        }
-    }
+    };
 
-    #[allow(dead_code)]
-    fn is_vertex_current(&self, row: usize) -> bool {
-        if row < self.vertices.len() {
-            self.vertices[row].is_current
-        } else {
-            false
-        }
-    }
-}
+    // Staged/Unstaged一覧を表示し直す
+    // グラフはUncommitted行の件数のみ書き換える
+    // 戻り値: (Uncommittedの変更数, 行の有無が変わってグラフの再計算が必要か)
+    let collapsed_tree_dirs: CollapsedTreeDirs = Rc::new(RefCell::new(Default::default()));
+    let show_status = {
+        let ui_weak = ui.as_weak();
+        let git_client = git_client.clone();
+        let collapsed_tree_dirs = collapsed_tree_dirs.clone();
+        move || -> (usize, bool) {
+            let Some(ui) = ui_weak.upgrade() else {
+                return (0, false);
+            };
+            let client = git_client.borrow();
 
-// ========== GitClient ==========
+            let (staged, unstaged) = client.get_status();
+            ui.set_undo_description(client.undo_description().into());
+            drop(client);
+            let uncommitted = uncommitted_count(&staged, &unstaged);
+            let staged_len = staged.len();
+            let unstaged_len = unstaged.len();
+            ui.set_staged_files(Rc::new(slint::VecModel::from(staged)).into());
+            ui.set_unstaged_files(Rc::new(slint::VecModel::from(unstaged)).into());
+            show_file_trees(&ui, &collapsed_tree_dirs);
 
-struct GitClient {
-    repo: Option<Repository>,
-    repo_path: Option<String>,
-}
+            // チェック状態をリセット
+            ui.set_staged_checked(Rc::new(slint::VecModel::from(vec![false; staged_len])).into());
+            ui.set_unstaged_checked(
+                Rc::new(slint::VecModel::from(vec![false; unstaged_len])).into(),
+            );
+            ui.set_staged_checked_count(0);
+            ui.set_unstaged_checked_count(0);
+            // キーボード操作中のフォーカス位置は範囲内に収めて維持する
+            ui.set_last_clicked_staged(ui.get_last_clicked_staged().min(staged_len as i32 - 1));
+            ui.set_last_clicked_unstaged(
+                ui.get_last_clicked_unstaged().min(unstaged_len as i32 - 1),
+            );
 
-impl GitClient {
-    fn new() -> Self {
-        Self {
-            repo: None,
-            repo_path: None,
+            ui.set_selected_file(-1);
+            show_diff_lines(&ui, vec![]);
+            ui.set_conflict_view_file("".into());
+
+            let commits = ui.get_commits();
+            let stale = match commits.row_data(0) {
+                Some(mut row) if row.is_uncommitted && uncommitted > 0 => {
+                    row.message = uncommitted_message(uncommitted);
+                    commits.set_row_data(0, row);
+                    false
+                }
+                Some(row) if !row.is_uncommitted && uncommitted == 0 => false,
+                None if uncommitted == 0 => false,
+                _ => true,
+            };
+            (uncommitted, stale)
         }
-    }
+    };
 
-    fn open_repo(&mut self, path: &str) -> Result<(), String> {
-        match Repository::open(path) {
-            Ok(repo) => {
-                self.repo = Some(repo);
-                self.repo_path = Some(path.to_string());
-                Ok(())
+    // Staged/Unstaged一覧を更新（ステージ操作はこれだけで済む）
+    // Uncommitted行の有無が変わった場合だけグラフを再計算する
+    let refresh_status = {
+        let show_status = show_status.clone();
+        let refresh_graph = refresh_graph.clone();
+        move || {
+            let (uncommitted, stale) = show_status();
+            if stale {
+                refresh_graph(uncommitted);
             }
-            Err(e) => Err(format!("Failed to open repository: {}", e)),
         }
-    }
-
-    fn get_repo_path(&self) -> Option<String> {
-        self.repo_path.clone()
-    }
-
-    fn get_current_branch(&self) -> String {
-        self.repo.as_ref().map_or("".to_string(), |repo| {
-            repo.head()
-                .ok()
-                .and_then(|h| h.shorthand().map(|s| s.to_string()))
-                .unwrap_or_default()
-        })
-    }
+    };
 
-    fn get_local_branches(&self) -> Vec<LocalBranchData> {
-        let Some(repo) = &self.repo else {
-            return vec![];
-        };
-        let current = self.get_current_branch();
+    // 全体を更新（Fetch後・ブランチ操作後など）
+    let refresh_ui = {
+        let refresh_branches = refresh_branches.clone();
+        let refresh_stashes = refresh_stashes.clone();
+        let refresh_graph = refresh_graph.clone();
+        let show_status = show_status.clone();
+        move || {
+            refresh_branches();
+            refresh_stashes();
+            // グラフは別スレッドで作り直すため、Uncommitted行は古いモデルで判定せずグラフ側に任せる
+            let (uncommitted, _) = show_status();
+            refresh_graph(uncommitted);
+        }
+    };
 
-        let mut branches = vec![];
+    // リポジトリを開いた直後の読み込み
+    // refが多いリポジトリでも最初の描画を止めないよう、ブランチ一覧とグラフは別スレッドで計算し、
+    // 結果はloaded_historyに置いてhistory-loadedでUIスレッドに反映する
+    let loaded_history: Arc<Mutex<Option<LoadedHistory>>> = Arc::new(Mutex::new(None));
+    let load_repo = {
+        let ui_weak = ui.as_weak();
+        let git_client = git_client.clone();
+        let graph_generation = graph_generation.clone();
+        let stats_generation = stats_generation.clone();
+        let diff_cache = diff_cache.clone();
+        let loaded_history = loaded_history.clone();
+        let refresh_stashes = refresh_stashes.clone();
+        let refresh_status = refresh_status.clone();
+        let history_refresh_pending = history_refresh_pending.clone();
+        move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            // 読み込みが終わるまで前のリポジトリの一覧は出さない
+            ui.set_commits(ModelRc::default());
+            ui.set_merge_lines(ModelRc::default());
+            ui.set_local_branches(ModelRc::default());
+            ui.set_remote_branches(ModelRc::default());
+            ui.set_remote_branch_names(ModelRc::default());
+            ui.set_worktrees(ModelRc::default());
+            GraphSelection::default().restore(&ui);
+            if let Ok(mut diff_cache) = diff_cache.lock() {
+                diff_cache.clear();
+            }
+            refresh_stashes();
+            stats_generation.fetch_add(1, Ordering::SeqCst);
 
-        if let Ok(branch_iter) = repo.branches(Some(BranchType::Local)) {
-            for branch in branch_iter.flatten() {
-                if let Some(name) = branch.0.name().ok().flatten() {
-                    branches.push(LocalBranchData {
-                        name: name.into(),
-                        is_current: name == current,
+            let Some(background) = git_client.borrow().reopen() else {
+                refresh_status();
+                return;
+            };
+            ui.set_loading_history(true);
+            history_refresh_pending.set(false);
+            // 計算中の古いグラフや、読み込み中に別のリポジトリを開いた場合の結果は破棄する
+            let generation = graph_generation.fetch_add(1, Ordering::SeqCst) + 1;
+            let graph_generation = graph_generation.clone();
+            let loaded_history = loaded_history.clone();
+            let ui_weak = ui.as_weak();
+            std::thread::spawn(move || {
+                let lists = background.branch_lists();
+                let (staged, unstaged) = background.get_status();
+                let uncommitted = uncommitted_count(&staged, &unstaged);
+                let graph = background
+                    .graph_cache_key(300, uncommitted)
+                    .and_then(|key| {
+                        let repo_path = key.repo_path.clone()?;
+                        let (rows, merge_lines) = build_commit_graph(&repo_path, &key, uncommitted);
+                        // 以降のrefreshではキャッシュから表示できるようにしておく
+                        if let Ok(mut cache) = background.graph_cache_handle().lock() {
+                            *cache = Some(GraphCache {
+                                key,
+                                commits: rows.clone(),
+                                merge_lines: merge_lines.clone(),
+                            });
+                        }
+                        Some((rows, merge_lines))
                     });
+                if let Ok(mut loaded) = loaded_history.lock() {
+                    if graph_generation.load(Ordering::SeqCst) != generation {
+                        return;
+                    }
+                    *loaded = Some((lists, graph));
                 }
-            }
+
+                let _ = slint::invoke_from_event_loop(move || {
+                    if graph_generation.load(Ordering::SeqCst) != generation {
+                        return;
+                    }
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.invoke_history_loaded();
+                    }
+                });
+            });
         }
+    };
 
-        branches.sort_by(|a, b| b.is_current.cmp(&a.is_current));
-        branches
+    // 別スレッドで読み込んだブランチ一覧とグラフを反映
+    {
+        let git_client = git_client.clone();
+        let refresh_status = refresh_status.clone();
+        let refresh_ui = refresh_ui.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_history_loaded(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let Some((lists, graph)) = loaded_history.lock().ok().and_then(|mut l| l.take()) else {
+                return;
+            };
+            ui.set_loading_history(false);
+            show_branch_lists(&ui, &git_client.borrow(), lists);
+            if let Some((rows, merge_lines)) = graph {
+                let commits: Vec<CommitData> =
+                    rows.into_iter().map(CommitRow::into_commit_data).collect();
+                ui.set_commits(Rc::new(slint::VecModel::from(commits)).into());
+                ui.set_merge_lines(Rc::new(slint::VecModel::from(merge_lines)).into());
+                ui.invoke_request_visible_signatures();
+            }
+            if history_refresh_pending.replace(false) {
+                // 読み込み中にチェックアウトやコミットがあった場合は、読み込み前の一覧を最新にし直す
+                refresh_ui();
+            } else {
+                // 読み込み中に変わった作業ツリーの状態（Uncommitted行）もここで揃える
+                refresh_status();
+            }
+        });
     }
 
-    fn get_remote_branches(&self) -> Vec<RemoteBranchData> {
-        let Some(repo) = &self.repo else {
-            return vec![];
-        };
+    // Open repository
+    {
+        let git_client = git_client.clone();
+        let load_repo = load_repo.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_open_repo(move |path| {
+            let mut client = git_client.borrow_mut();
+            match client.open_repo(&path) {
+                Ok(()) => {
+                    // サブフォルダが指定された場合も検出したルートで記録する
+                    let path = client.get_repo_path().unwrap_or_else(|| path.to_string());
+                    if let Some(ui) = ui_weak.upgrade() {
+                        apply_repo_state(&ui, &mut client, &path);
+                    }
+                    drop(client);
+                    // 履歴を更新
+                    let repos = add_recent_repo(&path);
+                    if let Some(ui) = ui_weak.upgrade() {
+                        set_recent_repos_ui(&ui, &repos);
+                        select_recent_repo(&ui, &path);
+                        ui.set_repo_path(SharedString::from(path.as_str()));
 
-        let mut branches = vec![];
+                        // リポジトリ名を設定
+                        let repo_name = Path::new(&path)
+                            .file_name()
+                            .and_then(|n| n.to_str())
+                            .unwrap_or(&path)
+                            .to_string();
+                        ui.set_repo_name(SharedString::from(repo_name));
 
-        if let Ok(branch_iter) = repo.branches(Some(BranchType::Remote)) {
-            for branch in branch_iter.flatten() {
-                if let Some(name) = branch.0.name().ok().flatten() {
-                    if !name.ends_with("/HEAD") {
-                        branches.push(RemoteBranchData { name: name.into() });
+                        ui.set_status_message("Repository opened".into());
+                    }
+                    load_repo();
+                }
+                Err(GitError::NotARepository) => {
+                    // 初期化するか確認するダイアログを表示
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_init_repo_path(path.clone());
+                        ui.set_show_init_repo_dialog(true);
+                    }
+                }
+                Err(e) => {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_message(SharedString::from(format!("Error: {}", e)));
                     }
                 }
             }
-        }
+        });
+    }
 
-        branches
+    // Pin / unpin recent repository
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_toggle_pin_repo(move |path| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let repos = toggle_pin_repo(&path);
+            set_recent_repos_ui(&ui, &repos);
+            select_recent_repo(&ui, &ui.get_repo_path());
+        });
     }
 
-    /// Git Graphのアルゴリズムでコミットグラフを構築
-    fn get_commits_with_graph(&mut self, limit: usize) -> (Vec<CommitData>, Vec<MergeLineData>) {
-        let Some(repo) = &self.repo else {
-            return (vec![], vec![]);
-        };
-        let current_branch = self.get_current_branch();
+    // Sort / filter the recent repository list (sort mode is saved as an app setting)
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_recent_repos_view_changed(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let mut settings = load_settings();
+            settings.insert(
+                "recent_repos_sort".into(),
+                ui.get_recent_repos_sort().into(),
+            );
+            save_settings(&settings);
+            set_recent_repos_ui(&ui, &load_recent_repos());
+            select_recent_repo(&ui, &ui.get_repo_path());
+        });
+    }
 
-        // ブランチごとのHEADを取得
-        let mut branch_heads: HashMap<String, Vec<String>> = HashMap::new();
+    // Remove missing repositories from the recent list
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_remove_missing_repos(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let before = ui.get_recent_repos().row_count();
+            let repos = remove_missing_recent_repos();
+            set_recent_repos_ui(&ui, &repos);
+            ui.set_status_message(SharedString::from(format!(
+                "Removed {} missing repositories",
+                before.saturating_sub(repos.len())
+            )));
+        });
+    }
 
-        if let Ok(branches) = repo.branches(Some(BranchType::Local)) {
-            for branch in branches.flatten() {
-                if let (Some(name), Ok(reference)) = (
-                    branch.0.name().ok().flatten(),
-                    branch.0.get().peel_to_commit(),
-                ) {
-                    branch_heads
-                        .entry(reference.id().to_string())
-                        .or_default()
-                        .push(name.to_string());
+    // Initialize repository
+    {
+        let git_client = git_client.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_init_repo(move |path| {
+            let result = git_client.borrow_mut().init_repo(&path);
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            match result {
+                Ok(()) => {
+                    // 作成したリポジトリを通常のフローで開く
+                    ui.invoke_open_repo(path);
+                    ui.set_status_message("Initialized empty repository".into());
                 }
-            }
-        }
-        if let Ok(branches) = repo.branches(Some(BranchType::Remote)) {
-            for branch in branches.flatten() {
-                if let (Some(name), Ok(reference)) = (
-                    branch.0.name().ok().flatten(),
-                    branch.0.get().peel_to_commit(),
-                ) {
-                    if !name.ends_with("/HEAD") {
-                        branch_heads
-                            .entry(reference.id().to_string())
-                            .or_default()
-                            .push(name.to_string());
-                    }
+                Err(e) => {
+                    ui.set_status_message(SharedString::from(format!("Init error: {}", e)));
                 }
             }
-        }
-
-        let Ok(mut revwalk) = repo.revwalk() else {
-            return (vec![], vec![]);
-        };
-        revwalk
-            .set_sorting(git2::Sort::TIME | git2::Sort::TOPOLOGICAL)
-            .ok();
+        });
+    }
 
-        // 全ブランチを追加
-        if let Ok(branches) = repo.branches(Some(BranchType::Local)) {
-            for branch in branches.flatten() {
-                if let Ok(reference) = branch.0.get().peel_to_commit() {
-                    let _ = revwalk.push(reference.id());
-                }
-            }
-        }
-        if let Ok(branches) = repo.branches(Some(BranchType::Remote)) {
-            for branch in branches.flatten() {
-                if let Ok(reference) = branch.0.get().peel_to_commit() {
-                    let _ = revwalk.push(reference.id());
+    // Browse destination path for clone
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_browse_clone_path(move || {
+            if let Some(path) = rfd::FileDialog::new()
+                .set_title("Select Destination Folder")
+                .pick_folder()
+            {
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_clone_path(path.to_string_lossy().to_string().into());
                 }
             }
-        }
-
-        // コミットを収集
-        let oids: Vec<_> = revwalk.take(limit).flatten().collect();
+        });
+    }
 
-        // OID -> インデックスのマップを作成
-        let mut oid_to_index: HashMap<String, usize> = HashMap::new();
-        for (idx, &oid) in oids.iter().enumerate() {
-            oid_to_index.insert(oid.to_string(), idx);
-        }
+    // 実行中のgit cloneプロセス（キャンセル用）
+    let clone_child: Arc<ChildSlot> = Arc::default();
 
-        // HEADのインデックスを取得
-        let head_oid = repo
-            .head()
-            .ok()
-            .and_then(|h| h.peel_to_commit().ok())
-            .map(|c| c.id().to_string());
-        let head_index = head_oid.as_ref().and_then(|h| oid_to_index.get(h).copied());
+    // Clone repository
+    {
+        let ui_weak = ui.as_weak();
+        let clone_child = clone_child.clone();
+        ui.on_clone_repo(move |url, path| {
+            let url = url.to_string();
+            let mut path_str = path.to_string();
+            let ui_weak_clone = ui_weak.clone();
+            let clone_child = clone_child.clone();
+            clone_child.reset();
 
-        // 親子関係を構築
-        let mut parent_map: Vec<(usize, Vec<i32>)> = Vec::new();
-        for (idx, &oid) in oids.iter().enumerate() {
-            let Ok(commit) = repo.find_commit(oid) else {
-                continue;
+            // Shallow / Single-branchオプション
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
             };
-            let mut parents = Vec::new();
-
-            for i in 0..commit.parent_count() {
-                if let Ok(parent) = commit.parent(i) {
-                    let parent_id_str = parent.id().to_string();
-                    if let Some(&parent_idx) = oid_to_index.get(&parent_id_str) {
-                        parents.push(parent_idx as i32);
-                    } else {
-                        // 親がグラフ外
-                        parents.push(NULL_VERTEX_ID);
-                    }
+            let extra_args = match build_clone_args(
+                ui.get_clone_shallow(),
+                &ui.get_clone_depth(),
+                ui.get_clone_single_branch(),
+            ) {
+                Ok(args) => args,
+                Err(e) => {
+                    ui.set_is_cloning(false);
+                    ui.set_clone_error(e.into());
+                    return;
                 }
-            }
-            parent_map.push((idx, parents));
-        }
+            };
 
-        // Uncommitted changesをチェック
-        let (staged, unstaged) = self.get_status();
-        let has_uncommitted = !staged.is_empty() || !unstaged.is_empty();
+            std::thread::spawn(move || {
+                // スマートパス補完: 指定されたパスが存在し、かつ空でない場合
+                let path = Path::new(&path_str);
+                if path.exists()
+                    && path
+                        .read_dir()
+                        .map(|mut i| i.next().is_some())
+                        .unwrap_or(false)
+                {
+                    // URLからリポジトリ名を抽出 (e.g. https://github.com/user/repo.git -> repo)
+                    let repo_name = url
+                        .split('/')
+                        .next_back()
+                        .map(|s| s.trim_end_matches(".git"))
+                        .unwrap_or("repository");
 
-        // グラフを構築
-        let mut graph_builder = GraphBuilder::new();
+                    // パスにリポジトリ名を追加
+                    let new_path = path.join(repo_name);
+                    path_str = new_path.to_string_lossy().to_string();
+                }
+                // キャンセル時の後始末用に、クローン前の状態を記録
+                // （存在しなかった / 空だった場合のみ、途中まで作られた内容を削除してよい）
+                let dest = Path::new(&path_str);
+                let dest_existed = dest.exists();
+                let dest_was_empty = !dest_existed
+                    || dest
+                        .read_dir()
+                        .map(|mut i| i.next().is_none())
+                        .unwrap_or(false);
 
-        // Uncommittedがある場合、インデックスを1つずらす
-        let commit_offset = if has_uncommitted { 1 } else { 0 };
+                // git cloneコマンドを実行（push/pull/fetchと同様にシステムのgitを使用）
+                // --progressで進捗をstderrに出力させ、UIのプログレスバーに反映する
+                let mut cmd = create_git_command();
+                cmd.args(["clone", "--progress"])
+                    .args(&extra_args)
+                    .args([&url, &path_str])
+                    .env("GIT_TERMINAL_PROMPT", "0");
+                let ui_weak_progress = ui_weak_clone.clone();
+                let output = run_git_with_progress(cmd, Some(&clone_child), |phase, percent| {
+                    let ui_weak = ui_weak_progress.clone();
+                    let text = format!("{}: {}%", phase, percent);
+                    let _ = slint::invoke_from_event_loop(move || {
+                        if let Some(ui) = ui_weak.upgrade() {
+                            ui.set_clone_progress(percent as f32 / 100.0);
+                            ui.set_clone_progress_text(text.into());
+                        }
+                    });
+                });
 
-        // 親マップを調整（Uncommittedを考慮）
-        let adjusted_parent_map: Vec<(usize, Vec<i32>)> = parent_map
-            .iter()
-            .map(|(idx, parents)| {
-                let new_idx = idx + commit_offset;
-                let new_parents: Vec<i32> = parents
-                    .iter()
-                    .map(|&p| {
-                        if p == NULL_VERTEX_ID {
-                            NULL_VERTEX_ID
+                match output {
+                    Ok((true, _)) => {
+                        let _ = slint::invoke_from_event_loop(move || {
+                            if let Some(ui) = ui_weak_clone.upgrade() {
+                                ui.set_is_cloning(false);
+                                ui.set_show_clone_dialog(false);
+                                ui.set_status_message("Clone successful".into());
+                                // Open the new repo using existing logic
+                                ui.invoke_open_repo(path_str.into());
+                            }
+                        });
+                    }
+                    Ok((false, stderr)) => {
+                        let error_msg = if is_auth_failure(&stderr) {
+                            remote_command_error("Clone", &stderr).to_string()
                         } else {
-                            p + commit_offset as i32
+                            stderr
+                        };
+                        let _ = slint::invoke_from_event_loop(move || {
+                            if let Some(ui) = ui_weak_clone.upgrade() {
+                                ui.set_is_cloning(false);
+                                ui.set_clone_error(error_msg.into());
+                            }
+                        });
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                        // キャンセルされた: 途中まで作成されたディレクトリを削除
+                        // 元々中身があったディレクトリはユーザーのファイルなので触らない
+                        if dest_was_empty {
+                            let dest = Path::new(&path_str);
+                            let _ = fs::remove_dir_all(dest);
+                            if dest_existed {
+                                // 元々存在した空ディレクトリは残す
+                                let _ = fs::create_dir_all(dest);
+                            }
                         }
+                        let _ = slint::invoke_from_event_loop(move || {
+                            if let Some(ui) = ui_weak_clone.upgrade() {
+                                ui.set_status_message("Clone canceled".into());
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        let error_msg = e.to_string();
+                        let _ = slint::invoke_from_event_loop(move || {
+                            if let Some(ui) = ui_weak_clone.upgrade() {
+                                ui.set_is_cloning(false);
+                                ui.set_clone_error(error_msg.into());
+                            }
+                        });
+                    }
+                }
+            });
+        });
+    }
+
+    // Cancel clone
+    {
+        let ui_weak = ui.as_weak();
+        let clone_child = clone_child.clone();
+        ui.on_cancel_clone(move || {
+            clone_child.cancel();
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_is_cloning(false);
+                ui.set_clone_error("".into());
+                ui.set_clone_progress(0.0);
+                ui.set_clone_progress_text("".into());
+            }
+        });
+    }
+
+    // Browse repository (folder dialog)
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_browse_repo(move || {
+            if let Some(path) = rfd::FileDialog::new()
+                .set_title("Select Git Repository")
+                .pick_folder()
+            {
+                if let Some(ui) = ui_weak.upgrade() {
+                    let path_str = path.to_string_lossy().to_string();
+                    ui.set_repo_path(path_str.clone().into());
+                    ui.invoke_open_repo(path_str.into());
+                }
+            }
+        });
+    }
+
+    // Refresh (非同期Fetch後にUI更新)
+    {
+        let git_client = git_client.clone();
+        let ui_weak = ui.as_weak();
+        let fetch_running = fetch_running.clone();
+        ui.on_refresh(move || {
+            // gitコマンドがなければfetchはせずに画面だけ更新する
+            if !git_client.borrow().remote_available() {
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_message("Refreshed (fetch skipped: git not found)".into());
+                    ui.invoke_update_local_state();
+                }
+                return;
+            }
+            // 定期fetchなど別のfetchが実行中なら重ねて走らせず、画面だけ更新する
+            if fetch_running
+                .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                .is_err()
+            {
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_message("Refreshed (fetch already running)".into());
+                    ui.invoke_update_local_state();
+                }
+                return;
+            }
+            let ui_weak_clone = ui_weak.clone();
+            // 「Refreshing...」を表示
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_status_message("Refresh & Fetch: Fetching...".into());
+            }
+
+            // リポジトリパスを取得（別スレッドで使用するため）
+            let repo_path = git_client.borrow().get_repo_path();
+            let transport = git_client.borrow().remote_transport();
+            let fetch_running = fetch_running.clone();
+
+            // 別スレッドでFetchを実行
+            std::thread::spawn(move || {
+                let fetch_result = if let Some(path) = &repo_path {
+                    let ui_weak_progress = ui_weak_clone.clone();
+                    fetch_all(path, transport, |phase, percent| {
+                        let ui_weak = ui_weak_progress.clone();
+                        let text = format!("Refresh & Fetch: {} {}%", phase, percent);
+                        let _ = slint::invoke_from_event_loop(move || {
+                            if let Some(ui) = ui_weak.upgrade() {
+                                ui.set_status_message(text.into());
+                            }
+                        });
                     })
-                    .collect();
-                (new_idx, new_parents)
-            })
-            .collect();
-
-        // Uncommittedの親を追加
-        let final_parent_map = if has_uncommitted {
-            let uncommitted_parent = vec![];
-            let mut map = vec![(0, uncommitted_parent)];
-            map.extend(adjusted_parent_map);
-            map
-        } else {
-            adjusted_parent_map
-        };
+                } else {
+                    Err(GitError::NoRepository)
+                };
+                fetch_running.store(false, Ordering::SeqCst);
+
+                // メインスレッドに戻ってUI更新
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_weak_clone.upgrade() {
+                        match fetch_result {
+                            Ok(()) => {
+                                if let Some(path) = &repo_path {
+                                    record_fetch_time(path);
+                                }
+                                ui.set_status_message("Refresh & Fetch: Updating...".into());
+                                ui.invoke_update_local_state();
+                            }
+                            Err(e) => {
+                                ui.set_status_message(SharedString::from(e.to_string()));
+                                // エラーでもローカル状態は更新
+                                ui.invoke_update_local_state();
+                            }
+                        }
+                    }
+                });
+            });
+        });
+    }
 
-        let total_count = oids.len() + commit_offset;
-        let adjusted_head_index = head_index.map(|h| h + commit_offset);
+    // Update local state (内部リフレッシュ用コールバック)
+    {
+        let refresh = refresh_ui.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_update_local_state(move || {
+            refresh();
+            if let Some(ui) = ui_weak.upgrade() {
+                // Fetchingメッセージをクリア（既にセットされていなければ）
+                let current_msg = ui.get_status_message();
+                if current_msg == "Refresh & Fetch: Updating..." {
+                    ui.set_status_message("".into());
+                }
+            }
+        });
+    }
 
-        graph_builder.load_commits(
-            total_count,
-            &final_parent_map,
-            adjusted_head_index,
-            has_uncommitted,
-        );
+    // Stage file
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_stage_file(move |filename| {
+            let client = git_client.borrow();
+            if let Err(e) = client.stage_file(&filename) {
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_message(SharedString::from(format!("Stage error: {}", e)));
+                }
+            }
+            drop(client);
+            refresh();
+        });
+    }
 
-        // コミットデータを生成
-        let mut commits = vec![];
-        let merge_lines = vec![];
-
-        // Uncommitted Changesを先頭に追加
-        if has_uncommitted {
-            let (svg_paths, node_path) = graph_builder.generate_svg_paths(0);
-            let uncommitted = CommitData {
-                hash: "*".into(),
-                full_hash: "".into(),
-                message: SharedString::from(format!(
-                    "Uncommitted Changes ({})",
-                    staged.len() + unstaged.len()
-                )),
-                author: "*".into(),
-                date: chrono::Local::now()
-                    .format("%d %b %H:%M")
-                    .to_string()
-                    .into(),
-                branches: std::rc::Rc::new(slint::VecModel::default()).into(),
-                graph_column: graph_builder.get_vertex_column(0),
-                graph_color: get_color(0),
-                is_merge: false,
-                is_head: true,
-                is_uncommitted: true,
-                svg_path_0: svg_paths[0].clone().into(),
-                svg_path_1: svg_paths[1].clone().into(),
-                svg_path_2: svg_paths[2].clone().into(),
-                svg_path_3: svg_paths[3].clone().into(),
-                svg_path_4: svg_paths[4].clone().into(),
-                svg_path_5: svg_paths[5].clone().into(),
-                svg_path_6: svg_paths[6].clone().into(),
-                svg_path_7: svg_paths[7].clone().into(),
-                node_path: node_path.into(),
-            };
-            commits.push(uncommitted);
-        }
+    // Resolve conflict with ours / theirs
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_resolve_conflict(move |filename, side| {
+            let side = if side == "theirs" {
+                ConflictSide::Theirs
+            } else {
+                ConflictSide::Ours
+            };
+            let client = git_client.borrow();
+            let result = client.resolve_conflict(&filename, side);
+            drop(client);
+            if let Some(ui) = ui_weak.upgrade() {
+                match result {
+                    Ok(()) => {
+                        ui.set_status_message(SharedString::from(format!("Resolved: {}", filename)))
+                    }
+                    Err(e) => {
+                        ui.set_status_message(SharedString::from(format!("Resolve error: {}", e)))
+                    }
+                }
+            }
+            refresh();
+        });
+    }
 
-        // 各コミットのデータを生成
-        for (idx, &oid) in oids.iter().enumerate() {
-            let Ok(commit) = repo.find_commit(oid) else {
-                continue;
-            };
-            let row = idx + commit_offset;
-
-            let time = commit.time();
-            let datetime: DateTime<Local> = Local
-                .timestamp_opt(time.seconds(), 0)
-                .single()
-                .unwrap_or_else(Local::now);
-            let oid_str = oid.to_string();
-
-            // ブランチ名
-            let branch_names = branch_heads.get(&oid_str).cloned().unwrap_or_default();
-            let mut commit_branches = vec![];
-            for name in &branch_names {
-                let is_current = name == &current_branch;
-                let is_remote = name.contains('/');
-                commit_branches.push(CommitBranchInfo {
-                    name: name.clone().into(),
-                    is_current,
-                    is_remote,
+    // Launch external mergetool
+    {
+        let git_client = git_client.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_launch_mergetool(move |filename| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let Some(path) = git_client.borrow().get_repo_path() else {
+                return;
+            };
+            ui.set_status_message(SharedString::from(format!(
+                "Waiting for mergetool: {}",
+                filename
+            )));
+            let ui_weak = ui_weak.clone();
+            std::thread::spawn(move || {
+                let result = run_mergetool(&path, &filename);
+                let _ = slint::invoke_from_event_loop(move || {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        match result {
+                            Ok(()) => ui.set_status_message(SharedString::from(format!(
+                                "Mergetool finished: {}",
+                                filename
+                            ))),
+                            Err(e) => ui.set_status_message(SharedString::from(e.to_string())),
+                        }
+                        ui.invoke_update_local_state();
+                    }
                 });
+            });
+        });
+    }
+
+    // Mark conflict resolved
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_mark_resolved(move |filename| {
+            let client = git_client.borrow();
+            let result = client.mark_resolved(&filename);
+            drop(client);
+            if let Some(ui) = ui_weak.upgrade() {
+                match result {
+                    Ok(()) => ui.set_status_message(SharedString::from(format!(
+                        "Marked resolved: {}",
+                        filename
+                    ))),
+                    Err(e) => {
+                        ui.set_status_message(SharedString::from(format!("Resolve error: {}", e)))
+                    }
+                }
+            }
+            refresh();
+        });
+    }
+
+    // Stage all
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_stage_all(move || {
+            let client = git_client.borrow();
+            if let Err(e) = client.stage_all() {
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_message(SharedString::from(format!("Stage all error: {}", e)));
+                }
+            }
+            drop(client);
+            refresh();
+        });
+    }
+
+    // Unstage file
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_unstage_file(move |filename| {
+            let client = git_client.borrow();
+            if let Err(e) = client.unstage_file(&filename) {
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_message(SharedString::from(format!("Unstage error: {}", e)));
+                }
+            }
+            drop(client);
+            refresh();
+        });
+    }
+
+    // Discard file changes
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        let ui_weak = ui.as_weak();
+        let pending_confirm = pending_confirm.clone();
+        let confirm_bypass = confirm_bypass.clone();
+        ui.on_discard_file(move |filename| {
+            if !confirm_bypass.get() {
+                if let Some(ui) = ui_weak.upgrade() {
+                    if ui.get_confirm_discard() {
+                        request_confirm(
+                            &ui,
+                            &pending_confirm,
+                            PendingConfirm::DiscardFile(filename.to_string()),
+                            "Discard changes?",
+                            &filename,
+                            "Discard",
+                        );
+                        return;
+                    }
+                }
             }
-            commit_branches.sort_by(|a, b| {
-                if a.is_current != b.is_current {
-                    return b.is_current.cmp(&a.is_current);
+            let client = git_client.borrow();
+            match client.discard_file(&filename) {
+                Ok(()) => {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_message(SharedString::from(format!(
+                            "Discarded changes: {}",
+                            filename
+                        )));
+                    }
                 }
-                if a.is_remote != b.is_remote {
-                    return a.is_remote.cmp(&b.is_remote);
+                Err(e) => {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_message(SharedString::from(format!("Discard error: {}", e)));
+                    }
                 }
-                a.name.cmp(&b.name)
-            });
-            let branches_model = std::rc::Rc::new(slint::VecModel::from(commit_branches));
-
-            let column = graph_builder.get_vertex_column(row);
-            let color_idx = graph_builder.get_vertex_colour(row);
-            let is_merge = graph_builder.is_vertex_merge(row);
-            let is_head = !branch_names.is_empty();
-            let (svg_paths, node_path) = graph_builder.generate_svg_paths(row);
-
-            commits.push(CommitData {
-                hash: oid.to_string()[..7].into(),
-                full_hash: oid.to_string().into(),
-                message: commit.summary().unwrap_or("").into(),
-                author: commit.author().name().unwrap_or("").into(),
-                date: datetime.format("%d %b %H:%M").to_string().into(),
-                branches: branches_model.into(),
-                graph_column: column,
-                graph_color: get_color(color_idx),
-                is_merge,
-                is_head,
-                is_uncommitted: false,
-                svg_path_0: svg_paths[0].clone().into(),
-                svg_path_1: svg_paths[1].clone().into(),
-                svg_path_2: svg_paths[2].clone().into(),
-                svg_path_3: svg_paths[3].clone().into(),
-                svg_path_4: svg_paths[4].clone().into(),
-                svg_path_5: svg_paths[5].clone().into(),
-                svg_path_6: svg_paths[6].clone().into(),
-                svg_path_7: svg_paths[7].clone().into(),
-                node_path: node_path.into(),
-            });
-        }
-
-        (commits, merge_lines)
+            }
+            drop(client);
+            refresh();
+        });
     }
 
-    fn get_status(&self) -> (Vec<FileData>, Vec<FileData>) {
-        let Some(repo) = &self.repo else {
-            return (vec![], vec![]);
-        };
-
-        let mut staged = vec![];
-        let mut unstaged = vec![];
-
-        let mut opts = StatusOptions::new();
-        opts.include_untracked(true);
-        opts.recurse_untracked_dirs(true);
-
-        if let Ok(statuses) = repo.statuses(Some(&mut opts)) {
-            for entry in statuses.iter() {
-                let path = entry.path().unwrap_or("").to_string();
-                let status = entry.status();
-
-                if status.is_index_new() {
-                    staged.push(FileData {
-                        filename: path.clone().into(),
-                        status: "A".into(),
-                        staged: true,
-                    });
-                } else if status.is_index_modified() {
-                    staged.push(FileData {
-                        filename: path.clone().into(),
-                        status: "M".into(),
-                        staged: true,
-                    });
-                } else if status.is_index_deleted() {
-                    staged.push(FileData {
-                        filename: path.clone().into(),
-                        status: "D".into(),
-                        staged: true,
-                    });
-                } else if status.is_index_renamed() {
-                    staged.push(FileData {
-                        filename: path.clone().into(),
-                        status: "R".into(),
-                        staged: true,
-                    });
-                }
-
-                if status.is_wt_new() {
-                    unstaged.push(FileData {
-                        filename: path.clone().into(),
-                        status: "?".into(),
-                        staged: false,
-                    });
-                } else if status.is_wt_modified() {
-                    unstaged.push(FileData {
-                        filename: path.clone().into(),
-                        status: "M".into(),
-                        staged: false,
-                    });
-                } else if status.is_wt_deleted() {
-                    unstaged.push(FileData {
-                        filename: path.into(),
-                        status: "D".into(),
-                        staged: false,
-                    });
+    // Add to .gitignore
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_add_to_gitignore(move |filename| {
+            let client = git_client.borrow();
+            let result = client.add_to_gitignore(&filename);
+            drop(client);
+            if let Some(ui) = ui_weak.upgrade() {
+                match result {
+                    Ok(()) => ui.set_status_message(SharedString::from(format!(
+                        "Added to .gitignore: {}",
+                        filename
+                    ))),
+                    Err(e) => ui.set_status_message(SharedString::from(format!(
+                        "Add to .gitignore error: {}",
+                        e
+                    ))),
                 }
             }
-        }
-        (staged, unstaged)
-    }
-
-    fn stage_file(&self, filename: &str) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-        let mut index = repo.index().map_err(|e| e.to_string())?;
-
-        let path = Path::new(filename);
-        if path.exists()
-            || repo
-                .workdir()
-                .map(|w| w.join(path).exists())
-                .unwrap_or(false)
-        {
-            index.add_path(path).map_err(|e| e.to_string())?;
-        } else {
-            index.remove_path(path).map_err(|e| e.to_string())?;
-        }
-        index.write().map_err(|e| e.to_string())?;
-        Ok(())
+            refresh();
+        });
     }
 
-    fn stage_all(&self) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-        let mut index = repo.index().map_err(|e| e.to_string())?;
-        index
-            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
-            .map_err(|e| e.to_string())?;
-        index.write().map_err(|e| e.to_string())?;
-        Ok(())
+    // Show/hide ignored files
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        ui.on_set_show_ignored(move |show| {
+            let mut client = git_client.borrow_mut();
+            client.set_show_ignored(show);
+            if let Some(path) = client.get_repo_path() {
+                save_repo_state_value(&path, "show_ignored", show.into());
+            }
+            drop(client);
+            refresh();
+        });
     }
 
-    fn unstage_file(&self, filename: &str) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-        let head = repo.head().map_err(|e| e.to_string())?;
-        let obj = head
-            .peel(git2::ObjectType::Commit)
-            .map_err(|e| e.to_string())?;
-        repo.reset_default(Some(&obj), [Path::new(filename)])
-            .map_err(|e| e.to_string())?;
-        Ok(())
+    // 未追跡ディレクトリを1行にまとめるか（リポジトリごとに保存）
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        ui.on_set_group_untracked_dirs(move |group| {
+            let mut client = git_client.borrow_mut();
+            client.set_group_untracked_dirs(group);
+            if let Some(path) = client.get_repo_path() {
+                save_repo_state_value(&path, "group_untracked_dirs", group.into());
+            }
+            drop(client);
+            refresh();
+        });
     }
 
-    fn unstage_all(&self) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-        let head = repo.head().map_err(|e| e.to_string())?;
-        let obj = head
-            .peel(git2::ObjectType::Commit)
-            .map_err(|e| e.to_string())?;
-        repo.reset_default(Some(&obj), ["*"])
-            .map_err(|e| e.to_string())?;
-        Ok(())
+    // まとめた未追跡ディレクトリの展開・折りたたみ
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        ui.on_toggle_untracked_dir(move |dir| {
+            git_client.borrow_mut().toggle_untracked_dir(&dir);
+            refresh();
+        });
     }
 
-    fn commit(&self, message: &str) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-
-        let mut index = repo.index().map_err(|e| e.to_string())?;
-        let oid = index.write_tree().map_err(|e| e.to_string())?;
-        let tree = repo.find_tree(oid).map_err(|e| e.to_string())?;
-
-        let sig = repo.signature().map_err(|e| e.to_string())?;
-        let head = repo.head().map_err(|e| e.to_string())?;
-        let parent = head.peel_to_commit().map_err(|e| e.to_string())?;
-
-        repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &[&parent])
-            .map_err(|e| e.to_string())?;
-        Ok(())
+    // Staged / Unstagedをディレクトリツリーで表示するか（アプリ設定として保存）
+    {
+        let ui_weak = ui.as_weak();
+        let collapsed_tree_dirs = collapsed_tree_dirs.clone();
+        ui.on_set_file_tree_view(move |tree| {
+            let mut settings = load_settings();
+            settings.insert("file_tree_view".into(), tree.into());
+            save_settings(&settings);
+            if let Some(ui) = ui_weak.upgrade() {
+                show_file_trees(&ui, &collapsed_tree_dirs);
+            }
+        });
     }
 
-    fn checkout_branch(&self, name: &str) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-
-        let obj = repo
-            .revparse_single(&format!("refs/heads/{}", name))
-            .map_err(|e| e.to_string())?;
-
-        let mut opts = git2::build::CheckoutBuilder::new();
-        opts.safe();
-
-        repo.checkout_tree(&obj, Some(&mut opts))
-            .map_err(|e| e.to_string())?;
-        repo.set_head(&format!("refs/heads/{}", name))
-            .map_err(|e| e.to_string())?;
-        Ok(())
+    // ツリー表示のディレクトリの展開・折りたたみ
+    {
+        let ui_weak = ui.as_weak();
+        let collapsed_tree_dirs = collapsed_tree_dirs.clone();
+        ui.on_toggle_tree_dir(move |staged, path| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            {
+                let mut collapsed = collapsed_tree_dirs.borrow_mut();
+                let set = if staged {
+                    &mut collapsed.0
+                } else {
+                    &mut collapsed.1
+                };
+                if !set.remove(path.as_str()) {
+                    set.insert(path.to_string());
+                }
+            }
+            show_file_trees(&ui, &collapsed_tree_dirs);
+        });
     }
 
-    fn checkout_remote_branch(&self, remote_name: &str) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-
-        // リモートブランチ名から "origin/branch-name" の形式でローカルブランチ名を抽出
-        let local_name = remote_name.split('/').skip(1).collect::<Vec<_>>().join("/");
-        if local_name.is_empty() {
-            return Err("Invalid remote branch name".into());
-        }
-
-        // 既にローカルブランチが存在するかチェック
-        if repo.find_branch(&local_name, BranchType::Local).is_ok() {
-            // 既存のローカルブランチにチェックアウト
-            return self.checkout_branch(&local_name);
-        }
-
-        // リモートブランチのコミットを取得
-        let remote_ref = format!("refs/remotes/{}", remote_name);
-        let obj = repo
-            .revparse_single(&remote_ref)
-            .map_err(|e| e.to_string())?;
-        let commit = obj.peel_to_commit().map_err(|e| e.to_string())?;
-
-        // 新しいローカルブランチを作成
-        repo.branch(&local_name, &commit, false)
-            .map_err(|e| e.to_string())?;
-
-        // 作成したブランチにチェックアウト
-        self.checkout_branch(&local_name)
+    // フォルダ単位のStage / Unstage（配下のファイルごとに既存の操作を行う）
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_stage_folder(move |folder, stage| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let files: Vec<FileData> = if stage {
+                ui.get_unstaged_files().iter().collect()
+            } else {
+                ui.get_staged_files().iter().collect()
+            };
+            let client = git_client.borrow();
+            let mut count = 0;
+            let mut error = None;
+            for filename in files_under_folder(&files, &folder) {
+                let result = if stage {
+                    client.stage_file(&filename)
+                } else {
+                    client.unstage_file(&filename)
+                };
+                match result {
+                    Ok(()) => count += 1,
+                    Err(e) => error = Some(e),
+                }
+            }
+            drop(client);
+            let verb = if stage { "Staged" } else { "Unstaged" };
+            ui.set_status_message(SharedString::from(match error {
+                Some(e) => format!("{} {} files in {}/ ({})", verb, count, folder, e),
+                None => format!("{} {} files in {}/", verb, count, folder),
+            }));
+            refresh();
+        });
     }
 
-    fn create_branch(&self, name: &str) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-
-        let head = repo.head().map_err(|e| e.to_string())?;
-        let commit = head.peel_to_commit().map_err(|e| e.to_string())?;
-
-        repo.branch(name, &commit, false)
-            .map_err(|e| e.to_string())?;
-        self.checkout_branch(name)?;
-        Ok(())
+    // フォルダ単位の破棄（設定に応じて確認してから配下のファイルを破棄）
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        let ui_weak = ui.as_weak();
+        let pending_confirm = pending_confirm.clone();
+        let confirm_bypass = confirm_bypass.clone();
+        ui.on_discard_folder(move |folder| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let files: Vec<FileData> = ui.get_unstaged_files().iter().collect();
+            let filenames = files_under_folder(&files, &folder);
+            if !confirm_bypass.get() && ui.get_confirm_discard() {
+                request_confirm(
+                    &ui,
+                    &pending_confirm,
+                    PendingConfirm::DiscardFolder(folder.to_string()),
+                    &format!("Discard changes in {} files?", filenames.len()),
+                    &format!("Uncommitted changes under {}/ will be lost.", folder),
+                    "Discard",
+                );
+                return;
+            }
+            let client = git_client.borrow();
+            let discarded = filenames
+                .iter()
+                .filter(|f| client.discard_file(f).is_ok())
+                .count();
+            drop(client);
+            ui.set_status_message(SharedString::from(format!(
+                "Discarded {} files in {}/",
+                discarded, folder
+            )));
+            refresh();
+        });
     }
 
-    fn delete_branch(&self, name: &str) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-
-        let mut branch = repo
-            .find_branch(name, BranchType::Local)
-            .map_err(|e| e.to_string())?;
-        branch.delete().map_err(|e| e.to_string())?;
-        Ok(())
+    // Staged / Unstagedの結合表示を切り替え（アプリ設定として保存し、表示中のDiffを読み直す）
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_set_combined_file_diff(move |combined| {
+            let mut settings = load_settings();
+            settings.insert("combined_file_diff".into(), combined.into());
+            save_settings(&settings);
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let filename = ui.get_current_diff_filename();
+            if ui.get_commit_mode() && !filename.is_empty() {
+                ui.invoke_select_file(filename, ui.get_current_diff_is_staged());
+            }
+        });
     }
 
-    fn merge_branch(&self, name: &str) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-
-        let branch = repo
-            .find_branch(name, BranchType::Local)
-            .map_err(|e| e.to_string())?;
-        let reference = branch.get();
-        let annotated = repo
-            .reference_to_annotated_commit(reference)
-            .map_err(|e| e.to_string())?;
-
-        let (analysis, _) = repo
-            .merge_analysis(&[&annotated])
-            .map_err(|e| e.to_string())?;
-
-        if analysis.is_up_to_date() {
-            return Ok(());
-        }
-
-        if analysis.is_fast_forward() {
-            let refname = format!("refs/heads/{}", self.get_current_branch());
-            let mut reference = repo.find_reference(&refname).map_err(|e| e.to_string())?;
-            reference
-                .set_target(annotated.id(), "Fast-forward")
-                .map_err(|e| e.to_string())?;
-            repo.set_head(&refname).map_err(|e| e.to_string())?;
-            repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))
-                .map_err(|e| e.to_string())?;
-        } else {
-            return Err("Merge requires manual resolution".into());
-        }
-
-        Ok(())
+    // Save per-repository UI state (パネル表示状態)
+    {
+        let git_client = git_client.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_save_repo_ui_state(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            if let Some(path) = git_client.borrow().get_repo_path() {
+                save_repo_state_value(
+                    &path,
+                    "show_repo_sidebar",
+                    ui.get_show_repo_sidebar().into(),
+                );
+            }
+        });
     }
 
-    fn get_stashes(&mut self) -> Vec<StashData> {
-        let Some(repo) = &mut self.repo else {
-            return vec![];
-        };
-        let mut stashes = vec![];
-        let mut stash_idx = 0;
-        let _ = repo.stash_foreach(|index, name, _oid| {
-            stashes.push(StashData {
-                index: index as i32,
-                message: name.into(),
-            });
-            stash_idx += 1;
-            true
+    // Search within the current diff (一致箇所の計算のみ、前後の移動とスクロールはSlint側)
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_search_diff(move |query| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let lines: Vec<SharedString> = ui.get_diff_lines().iter().map(|l| l.content).collect();
+            let matches: Vec<DiffMatchData> =
+                find_text_matches(lines.iter().map(|l| l.as_str()), &query)
+                    .into_iter()
+                    .map(|(line, start, length)| DiffMatchData {
+                        line: line as i32,
+                        start: start as i32,
+                        length: length as i32,
+                    })
+                    .collect();
+            ui.set_diff_match_index(if matches.is_empty() { -1 } else { 0 });
+            ui.set_diff_matches(Rc::new(slint::VecModel::from(matches)).into());
         });
-        stashes
     }
 
-    fn stash_save(&mut self, message: &str, include_untracked: bool) -> Result<(), String> {
-        let Some(repo) = &mut self.repo else {
-            return Err("No repository".into());
-        };
-        let signature = repo.signature().map_err(|e| e.to_string())?;
-        let mut flags = git2::StashFlags::DEFAULT;
-        if include_untracked {
-            flags.insert(git2::StashFlags::INCLUDE_UNTRACKED);
-        }
-        repo.stash_save(&signature, message, Some(flags))
-            .map(|_| ())
-            .map_err(|e| e.to_string())
-    }
+    // Set diff context lines
+    {
+        let git_client = git_client.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_set_diff_context_lines(move |lines| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let lines = lines.max(0) as u32;
+            let mut client = git_client.borrow_mut();
+            client.set_diff_context_lines(lines);
+            if let Some(path) = client.get_repo_path() {
+                save_repo_state_value(&path, "diff_context_lines", lines.into());
+            }
+            drop(client);
 
-    fn stash_apply(&mut self, index: usize) -> Result<(), String> {
-        let Some(repo) = &mut self.repo else {
-            return Err("No repository".into());
-        };
-        let mut options = git2::StashApplyOptions::default();
-        repo.stash_apply(index, Some(&mut options))
-            .map_err(|e| e.to_string())
+            // 表示中のDiffを新しいコンテキスト行数で再読み込み
+            let filename = ui.get_current_diff_filename();
+            if ui.get_commit_mode() && !filename.is_empty() {
+                let (diff_lines, total_count) = working_file_diff(
+                    &ui,
+                    &git_client.borrow(),
+                    &filename,
+                    ui.get_current_diff_is_staged(),
+                );
+                show_diff_lines(&ui, diff_lines);
+                ui.set_diff_total_lines(total_count as i32);
+            } else if ui.get_selected_diff_file() >= 0 {
+                ui.invoke_select_diff_file(ui.get_selected_diff_file());
+            }
+        });
     }
 
-    fn stash_pop(&mut self, index: usize) -> Result<(), String> {
-        let Some(repo) = &mut self.repo else {
-            return Err("No repository".into());
-        };
-        let mut options = git2::StashApplyOptions::default();
-        repo.stash_pop(index, Some(&mut options))
-            .map_err(|e| e.to_string())
+    // Unstage all
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_unstage_all(move || {
+            let client = git_client.borrow();
+            if let Err(e) = client.unstage_all() {
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_message(SharedString::from(format!("Unstage all error: {}", e)));
+                }
+            }
+            drop(client);
+            refresh();
+        });
     }
 
-    fn stash_drop(&mut self, index: usize) -> Result<(), String> {
-        let Some(repo) = &mut self.repo else {
-            return Err("No repository".into());
-        };
-        repo.stash_drop(index).map_err(|e| e.to_string())
+    // Toggle staged check
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_toggle_staged_check(move |idx, checked| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let checked_model = ui.get_staged_checked();
+            let idx = idx as usize;
+            if idx < checked_model.row_count() {
+                checked_model.set_row_data(idx, checked);
+                // カウント更新
+                let count = (0..checked_model.row_count())
+                    .filter(|&i| checked_model.row_data(i).unwrap_or(false))
+                    .count();
+                ui.set_staged_checked_count(count as i32);
+            }
+        });
     }
 
-    fn get_commit_file_diff(&self, oid: &str, file_index: usize) -> (Vec<DiffLineData>, usize) {
-        let Some(repo) = &self.repo else {
-            return (vec![], 0);
-        };
-
-        if oid.is_empty() {
-            return (vec![], 0);
-        }
-
-        let Ok(commit) = repo.find_commit(Oid::from_str(oid).unwrap_or(Oid::zero())) else {
-            return (vec![], 0);
-        };
-        let Ok(tree) = commit.tree() else {
-            return (vec![], 0);
-        };
-
-        let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
-
-        let mut opts = DiffOptions::new();
-        let Ok(diff) = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut opts))
-        else {
-            return (vec![], 0);
-        };
-
-        let deltas: Vec<_> = diff.deltas().collect();
-        if file_index >= deltas.len() {
-            return (vec![], 0);
-        }
-
-        let target_path = deltas[file_index]
-            .new_file()
-            .path()
-            .or_else(|| deltas[file_index].old_file().path())
-            .map(|p| p.to_string_lossy().to_string())
-            .unwrap_or_default();
-
-        let mut opts = DiffOptions::new();
-        opts.pathspec(&target_path);
-        opts.context_lines(3);
-
-        let Ok(diff) = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut opts))
-        else {
-            return (vec![], 0);
-        };
+    // Toggle unstaged check
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_toggle_unstaged_check(move |idx, checked| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let checked_model = ui.get_unstaged_checked();
+            let idx = idx as usize;
+            if idx < checked_model.row_count() {
+                checked_model.set_row_data(idx, checked);
+                // カウント更新
+                let count = (0..checked_model.row_count())
+                    .filter(|&i| checked_model.row_data(i).unwrap_or(false))
+                    .count();
+                ui.set_unstaged_checked_count(count as i32);
+            }
+        });
+    }
 
-        self.parse_diff(&diff)
+    // Staged range select (Shift+Click)
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_staged_range_select(move |idx| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let last = ui.get_last_clicked_staged();
+            if last < 0 {
+                // 前回クリックがない場合は単一選択
+                ui.invoke_toggle_staged_check(idx, true);
+                return;
+            }
+            let checked_model = ui.get_staged_checked();
+            let start = last.min(idx) as usize;
+            let end = last.max(idx) as usize;
+            for i in start..=end {
+                if i < checked_model.row_count() {
+                    checked_model.set_row_data(i, true);
+                }
+            }
+            let count = (0..checked_model.row_count())
+                .filter(|&i| checked_model.row_data(i).unwrap_or(false))
+                .count();
+            ui.set_staged_checked_count(count as i32);
+        });
     }
 
-    fn get_file_diff(&self, filename: &str, staged: bool) -> (Vec<DiffLineData>, usize) {
-        let Some(repo) = &self.repo else {
-            return (vec![], 0);
-        };
-
-        let mut opts = DiffOptions::new();
-        opts.pathspec(filename);
-        opts.context_lines(3);
-
-        let diff = if staged {
-            let Ok(head_tree) = repo.head().and_then(|h| h.peel_to_tree()) else {
-                return (vec![], 0);
+    // Unstaged range select (Shift+Click)
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_unstaged_range_select(move |idx| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
             };
-            repo.diff_tree_to_index(Some(&head_tree), None, Some(&mut opts))
-        } else {
-            // Include untracked files in diff
-            opts.include_untracked(true);
-
-            repo.diff_index_to_workdir(None, Some(&mut opts))
-        };
-
-        match diff {
-            Ok(d) => {
-                let (lines, total_lines) = self.parse_diff(&d);
-                // If no diff lines but it's an unstaged file, it might be untracked (new file)
-                // Read the file content directly and show as all additions
-                if lines.is_empty() && !staged {
-                    let lines = self.get_new_file_diff(repo, filename);
-                    let count = lines.len();
-                    return (lines, count);
-                }
-                (lines, total_lines)
+            let last = ui.get_last_clicked_unstaged();
+            if last < 0 {
+                ui.invoke_toggle_unstaged_check(idx, true);
+                return;
             }
-            Err(_) => {
-                // If diff failed and it's unstaged, try reading as new file
-                if !staged {
-                    let lines = self.get_new_file_diff(repo, filename);
-                    let count = lines.len();
-                    return (lines, count);
+            let checked_model = ui.get_unstaged_checked();
+            let start = last.min(idx) as usize;
+            let end = last.max(idx) as usize;
+            for i in start..=end {
+                if i < checked_model.row_count() {
+                    checked_model.set_row_data(i, true);
                 }
-                (vec![], 0)
             }
-        }
-    }
-
-    /// Get diff for a new (untracked) file by reading its contents
-    fn get_new_file_diff(&self, repo: &Repository, filename: &str) -> Vec<DiffLineData> {
-        let workdir = match repo.workdir() {
-            Some(w) => w,
-            None => return vec![],
-        };
-
-        let file_path = workdir.join(filename);
-        let content = match fs::read_to_string(&file_path) {
-            Ok(c) => c,
-            Err(_) => {
-                // Try reading as binary and show a placeholder message
-                match fs::read(&file_path) {
-                    Ok(_) => {
-                        return vec![DiffLineData {
-                            content: "(Binary file)".into(),
-                            line_type: " ".into(),
-                            old_line_num: 0,
-                            new_line_num: 0,
-                            hunk_index: 0,
-                        }]
-                    }
-                    Err(_) => return vec![],
-                }
-            }
-        };
-
-        let mut lines = vec![];
-
-        // Add file header
-        lines.push(DiffLineData {
-            content: format!("--- /dev/null").into(),
-            line_type: "diff".into(),
-            old_line_num: 0,
-            new_line_num: 0,
-            hunk_index: -1,
-        });
-        lines.push(DiffLineData {
-            content: format!("+++ {}", filename).into(),
-            line_type: "diff".into(),
-            old_line_num: 0,
-            new_line_num: 0,
-            hunk_index: -1,
-        });
-
-        // Add hunk header
-        let line_count = content.lines().count();
-        lines.push(DiffLineData {
-            content: format!("@@ -0,0 +1,{} @@", line_count).into(),
-            line_type: "@@".into(),
-            old_line_num: 0,
-            new_line_num: 0,
-            hunk_index: 0,
-        });
-
-        // Add all lines as additions
-        for (i, line) in content.lines().enumerate() {
-            lines.push(DiffLineData {
-                content: format!("+{}", line).into(),
-                line_type: "+".into(),
-                old_line_num: 0,
-                new_line_num: (i + 1) as i32,
-                hunk_index: 0,
-            });
-        }
-
-        lines
+            let count = (0..checked_model.row_count())
+                .filter(|&i| checked_model.row_data(i).unwrap_or(false))
+                .count();
+            ui.set_unstaged_checked_count(count as i32);
+        });
     }
 
-    fn parse_diff(&self, diff: &git2::Diff) -> (Vec<DiffLineData>, usize) {
-        use std::cell::Cell;
-        let lines = Rc::new(RefCell::new(vec![]));
-        let current_hunk_index = Cell::new(-1i32);
-        let truncated = Cell::new(false);
-        let total_lines = Cell::new(0usize);
-        let stop_processing = Cell::new(false);
-
-        let lines_clone = lines.clone();
-        let _ = diff.print(git2::DiffFormat::Patch, |delta, _hunk, line| {
-            if stop_processing.get() {
-                return false;
-            }
-
-            // カウント上限チェック
-            if total_lines.get() >= MAX_COUNT_LINES {
-                stop_processing.set(true);
-                return false;
-            }
-            total_lines.set(total_lines.get() + 1);
-
-            // 表示上限チェック
-            if lines_clone.borrow().len() >= MAX_DIFF_LINES {
-                truncated.set(true);
-                return true; // カウントのために継続
-            }
-
-            let line_type = match line.origin() {
-                '+' => "+",
-                '-' => "-",
-                ' ' => " ",
-                'H' | 'F' => "@@",
-                _ => "",
+    // Stage selected files
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_stage_selected(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
             };
-
-            if line.origin() == 'H' {
-                current_hunk_index.set(current_hunk_index.get() + 1);
-            }
-
-            let old_line_num = line.old_lineno().map(|n| n as i32).unwrap_or(0);
-            let new_line_num = line.new_lineno().map(|n| n as i32).unwrap_or(0);
-
-            if let Ok(content) = std::str::from_utf8(line.content()) {
-                if line.origin() == 'F' {
-                    if let Some(path) = delta.new_file().path() {
-                        lines_clone.borrow_mut().push(DiffLineData {
-                            content: format!("--- {}", path.display()).into(),
-                            line_type: "diff".into(),
-                            old_line_num: 0,
-                            new_line_num: 0,
-                            hunk_index: -1,
-                        });
-                    }
-                } else {
-                    let text = content.trim_end_matches('\n');
-                    if !text.is_empty() || line_type == " " {
-                        lines_clone.borrow_mut().push(DiffLineData {
-                            content: text.into(),
-                            line_type: line_type.into(),
-                            old_line_num,
-                            new_line_num,
-                            hunk_index: current_hunk_index.get(),
-                        });
-                    }
-                }
+            let client = git_client.borrow();
+            let staged_count = stage_checked_files(&ui, &client);
+            drop(client);
+            if staged_count > 0 {
+                ui.set_status_message(SharedString::from(format!("Staged {} files", staged_count)));
             }
-            true
+            refresh();
         });
+    }
 
-        let mut result = lines.borrow_mut().clone();
-
-        // 切り捨てメッセージを追加
-        if truncated.get() {
-            result.push(DiffLineData {
-                content: format!(
-                    "... (truncated: diff exceeds {} lines, view on GitHub for full diff)",
-                    MAX_DIFF_LINES
-                )
-                .into(),
-                line_type: "@@".into(),
-                old_line_num: 0,
-                new_line_num: 0,
-                hunk_index: -1,
-            });
-        }
+    // Unstage selected files
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_unstage_selected(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let client = git_client.borrow();
+            let files = ui.get_staged_files();
+            let checked = ui.get_staged_checked();
+            let mut unstaged_count = 0;
 
-        (result, total_lines.get())
-    }
-
-    /// 特定のHunkをステージングする
-    fn stage_hunk(&self, filename: &str, hunk_index: usize) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-
-        // Unstaged diffを取得
-        let mut opts = DiffOptions::new();
-        opts.pathspec(filename);
-        opts.context_lines(3);
-
-        let diff = repo
-            .diff_index_to_workdir(None, Some(&mut opts))
-            .map_err(|e| e.to_string())?;
-
-        // Hunkを数えて対象のHunkを特定
-        let mut current_hunk = 0;
-        let mut target_hunk_header = String::new();
-        let mut target_hunk_lines: Vec<String> = vec![];
-        let mut in_target_hunk = false;
-
-        let _ = diff.print(git2::DiffFormat::Patch, |_delta, hunk, line| {
-            match line.origin() {
-                'H' => {
-                    // Hunkヘッダー
-                    if current_hunk == hunk_index {
-                        in_target_hunk = true;
-                        if let Some(h) = hunk {
-                            if let Ok(header) = std::str::from_utf8(h.header()) {
-                                target_hunk_header = header.trim_end().to_string();
-                            }
-                        }
-                    } else if in_target_hunk {
-                        in_target_hunk = false;
-                    }
-                    current_hunk += 1;
-                }
-                '+' | '-' | ' ' => {
-                    if in_target_hunk {
-                        if let Ok(content) = std::str::from_utf8(line.content()) {
-                            target_hunk_lines.push(format!("{}{}", line.origin(), content));
-                        }
+            for i in 0..files.row_count() {
+                if let (Some(file), Some(is_checked)) = (files.row_data(i), checked.row_data(i)) {
+                    if is_checked && client.unstage_file(&file.filename).is_ok() {
+                        unstaged_count += 1;
                     }
                 }
-                _ => {}
             }
-            true
+            drop(client);
+            if unstaged_count > 0 {
+                ui.set_status_message(SharedString::from(format!(
+                    "Unstaged {} files",
+                    unstaged_count
+                )));
+            }
+            refresh();
         });
-
-        if target_hunk_header.is_empty() {
-            return Err("Hunk not found".into());
-        }
-
-        // パッチを生成
-        let patch = format!(
-            "diff --git a/{filename} b/{filename}\n--- a/{filename}\n+++ b/{filename}\n{}\n{}",
-            target_hunk_header,
-            target_hunk_lines.join("")
-        );
-
-        // git applyでパッチを適用（--cachedでインデックスに適用）
-        use std::io::Write;
-        let workdir = repo.workdir().ok_or("No workdir")?;
-        let mut child = create_git_command()
-            .args(["apply", "--cached", "-"])
-            .current_dir(workdir)
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|e| e.to_string())?;
-
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin
-                .write_all(patch.as_bytes())
-                .map_err(|e| e.to_string())?;
-        }
-
-        let output = child.wait_with_output().map_err(|e| e.to_string())?;
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(format!("Failed to stage hunk: {}", stderr));
-        }
-
-        Ok(())
     }
 
-    fn discard_file(&self, filename: &str) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-
-        // Check if the file is untracked (new file)
-        let mut opts = StatusOptions::new();
-        opts.include_untracked(true);
-        opts.recurse_untracked_dirs(true);
+    // Discard selected files
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
+        let ui_weak = ui.as_weak();
+        let pending_confirm = pending_confirm.clone();
+        let confirm_bypass = confirm_bypass.clone();
+        ui.on_discard_selected(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let files = ui.get_unstaged_files();
+            let checked = ui.get_unstaged_checked();
+            if !confirm_bypass.get() && ui.get_confirm_discard() {
+                let count = checked.iter().filter(|c| *c).count();
+                request_confirm(
+                    &ui,
+                    &pending_confirm,
+                    PendingConfirm::DiscardSelected,
+                    &format!("Discard changes in {} files?", count),
+                    "Uncommitted changes in the checked files will be lost.",
+                    "Discard",
+                );
+                return;
+            }
+            let client = git_client.borrow();
+            let mut discarded_count = 0;
 
-        if let Ok(statuses) = repo.statuses(Some(&mut opts)) {
-            for entry in statuses.iter() {
-                let path = entry.path().unwrap_or("");
-                // Compare the path with the filename
-                if path == filename {
-                    let status = entry.status();
-                    if status.is_wt_new() {
-                        // Untracked file - delete it directly
-                        let workdir = repo.workdir().ok_or("No workdir")?;
-                        let file_path = workdir.join(filename);
-                        fs::remove_file(&file_path)
-                            .map_err(|e| format!("Failed to delete file: {}", e))?;
-                        return Ok(());
+            for i in 0..files.row_count() {
+                if let (Some(file), Some(is_checked)) = (files.row_data(i), checked.row_data(i)) {
+                    if is_checked && client.discard_file(&file.filename).is_ok() {
+                        discarded_count += 1;
                     }
-                    break;
                 }
             }
-        }
-
-        // For tracked files, restore from HEAD
-        let mut checkout_opts = git2::build::CheckoutBuilder::new();
-        checkout_opts.path(filename);
-        checkout_opts.force();
-
-        repo.checkout_head(Some(&mut checkout_opts))
-            .map_err(|e| e.to_string())?;
-        Ok(())
-    }
-
-    /// リモートにプッシュ（git pushコマンドを使用）
-    /// upstreamがないブランチでも自動的にupstreamを設定する
-    fn push(&self) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-
-        let workdir = repo.workdir().ok_or("No workdir")?;
-        let branch = self.get_current_branch();
-        if branch.is_empty() {
-            return Err("No current branch".into());
-        }
-
-        let output = create_git_command()
-            .args(["push", "-u", "origin", &branch])
-            .current_dir(workdir)
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .output()
-            .map_err(|e| e.to_string())?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(format!("Push failed: {}", stderr));
-        }
-
-        Ok(())
-    }
-
-    /// リモートからプル（git pullコマンドを使用）
-    fn pull(&self) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-
-        let workdir = repo.workdir().ok_or("No workdir")?;
-        let output = create_git_command()
-            .args(["pull"])
-            .current_dir(workdir)
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .output()
-            .map_err(|e| e.to_string())?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(format!("Pull failed: {}", stderr));
-        }
-
-        Ok(())
-    }
-
-    /// GitHubのリポジトリURLを取得
-    fn get_github_url(&self) -> Option<String> {
-        let repo = self.repo.as_ref()?;
-        let remote = repo.find_remote("origin").ok()?;
-        let url = remote.url()?;
-
-        // SSH形式 (git@github.com:user/repo.git) をHTTPS形式に変換
-        if url.starts_with("git@github.com:") {
-            let path = url
-                .strip_prefix("git@github.com:")?
-                .strip_suffix(".git")
-                .unwrap_or(url.strip_prefix("git@github.com:")?);
-            return Some(format!("https://github.com/{}", path));
-        }
-
-        // HTTPS形式 (https://github.com/user/repo.git)
-        if url.starts_with("https://github.com/") {
-            let clean_url = url.strip_suffix(".git").unwrap_or(url);
-            return Some(clean_url.to_string());
-        }
-
-        None
-    }
-
-    /// Pull Request作成URLを生成
-    fn get_pull_request_url(&self, branch_name: &str) -> Option<String> {
-        let github_url = self.get_github_url()?;
-        // GitHub PR作成URL: https://github.com/user/repo/compare/main...branch?expand=1
-        Some(format!(
-            "{}/compare/main...{}?expand=1",
-            github_url, branch_name
-        ))
+            drop(client);
+            if discarded_count > 0 {
+                ui.set_status_message(SharedString::from(format!(
+                    "Discarded {} files",
+                    discarded_count
+                )));
+            }
+            refresh();
+        });
     }
 
-    /// コミットのGitHub URLを生成
-    fn get_commit_github_url(&self, commit_hash: &str) -> Option<String> {
-        let github_url = self.get_github_url()?;
-        Some(format!("{}/commit/{}", github_url, commit_hash))
+    // Commit body（72文字を超える行を検出して本文の下に警告を出す）
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_check_commit_body(move |body| {
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_commit_body_long_line(first_long_body_line(&body));
+            }
+        });
     }
 
-    /// 指定したコミットにリセット
-    fn reset_to_commit(&self, commit_hash: &str, mode: &str) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-
-        let obj = repo
-            .revparse_single(commit_hash)
-            .map_err(|e| e.to_string())?;
-        let commit = obj.peel_to_commit().map_err(|e| e.to_string())?;
-
-        let reset_type = match mode {
-            "soft" => git2::ResetType::Soft,
-            "hard" => git2::ResetType::Hard,
-            _ => git2::ResetType::Mixed,
-        };
-
-        repo.reset(commit.as_object(), reset_type, None)
-            .map_err(|e| e.to_string())?;
-        Ok(())
+    // Insert ticket ID（ブランチ名から抽出したチケットIDを件名の先頭に付ける）
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_insert_ticket_id(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let ticket = ui.get_branch_ticket();
+            if ticket.is_empty() {
+                return;
+            }
+            let subject = prepend_ticket_id(&ui.get_commit_subject(), &ticket);
+            ui.set_commit_subject(subject.into());
+        });
     }
 
-    /// コミットをリバート（打ち消しコミットを作成）
-    fn revert_commit(&self, commit_hash: &str) -> Result<(), String> {
-        let Some(repo) = &self.repo else {
-            return Err("No repository".into());
-        };
-
-        let obj = repo
-            .revparse_single(commit_hash)
-            .map_err(|e| e.to_string())?;
-        let commit = obj.peel_to_commit().map_err(|e| e.to_string())?;
-
-        // リバートを実行
-        let mut revert_opts = git2::RevertOptions::new();
-        repo.revert(&commit, Some(&mut revert_opts))
-            .map_err(|e| e.to_string())?;
-
-        // 自動コミット
-        let sig = repo.signature().map_err(|e| e.to_string())?;
-        let mut index = repo.index().map_err(|e| e.to_string())?;
-        let tree_oid = index.write_tree().map_err(|e| e.to_string())?;
-        let tree = repo.find_tree(tree_oid).map_err(|e| e.to_string())?;
-        let head = repo.head().map_err(|e| e.to_string())?;
-        let parent = head.peel_to_commit().map_err(|e| e.to_string())?;
-
-        let message = format!("Revert \"{}\"", commit.summary().unwrap_or(""));
-        repo.commit(Some("HEAD"), &sig, &sig, &message, &tree, &[&parent])
-            .map_err(|e| e.to_string())?;
-
-        Ok(())
+    // Commit
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_ui.clone();
+        let ui_weak = ui.as_weak();
+        let history = commit_message_history.clone();
+        ui.on_commit(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let result = prepare_and_commit(&ui, &git_client.borrow(), &history);
+            match result {
+                Ok(_) => {
+                    ui.set_status_message("Commit successful".into());
+                    refresh();
+                }
+                Err(e) if e.is_empty() => {}
+                Err(e) => ui.set_status_message(SharedString::from(e)),
+            }
+        });
     }
 
-    /// インデックスからコミットハッシュを取得
-    fn get_commit_hash_by_index(&self, index: usize) -> Option<String> {
-        let repo = self.repo.as_ref()?;
-        let mut revwalk = repo.revwalk().ok()?;
-        revwalk
-            .set_sorting(git2::Sort::TIME | git2::Sort::TOPOLOGICAL)
-            .ok();
-
-        // 全ブランチを追加
-        if let Ok(branches) = repo.branches(Some(BranchType::Local)) {
-            for branch in branches.flatten() {
-                if let Ok(reference) = branch.0.get().peel_to_commit() {
-                    let _ = revwalk.push(reference.id());
-                }
+    // Amend (no edit)（push済みのコミットなら確認してから書き換える）
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_ui.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_amend_no_edit(move |force| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let client = git_client.borrow();
+            if !force && client.is_pushed("HEAD") {
+                show_rewrite_warning(
+                    &ui,
+                    "amend",
+                    "The last commit is already on a remote. Amending rewrites it, so you will need to force-push.",
+                );
+                return;
             }
-        }
-        if let Ok(branches) = repo.branches(Some(BranchType::Remote)) {
-            for branch in branches.flatten() {
-                if let Ok(reference) = branch.0.get().peel_to_commit() {
-                    let _ = revwalk.push(reference.id());
-                }
+            match client.amend_no_edit() {
+                Ok(()) => ui.set_status_message("Amended last commit".into()),
+                Err(e) => ui.set_status_message(SharedString::from(format!("Amend error: {}", e))),
             }
-        }
-
-        // Uncommitted changesをチェック
-        let (staged, unstaged) = self.get_status();
-        let has_uncommitted = !staged.is_empty() || !unstaged.is_empty();
-
-        // Uncommittedの場合はNone
-        if has_uncommitted && index == 0 {
-            return None;
-        }
-
-        let actual_index = if has_uncommitted { index - 1 } else { index };
-        let oids: Vec<_> = revwalk.take(actual_index + 1).flatten().collect();
-        oids.get(actual_index).map(|oid| oid.to_string())
+            drop(client);
+            refresh();
+        });
     }
-}
-
-fn main() -> Result<(), slint::PlatformError> {
-    let ui = MainWindow::new()?;
-    let git_client = Rc::new(RefCell::new(GitClient::new()));
-
-    // コミットメッセージ履歴を読み込み（最大10件保持）
-    let loaded_history = load_commit_history();
-    let history_model: Vec<SharedString> = loaded_history
-        .iter()
-        .map(|s| SharedString::from(s.as_str()))
-        .collect();
-    ui.set_commit_message_history(ModelRc::new(VecModel::from(history_model)));
-
-    let commit_message_history: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(loaded_history));
-    const MAX_COMMIT_HISTORY: usize = 10;
-
-    // 最近使用したリポジトリを読み込み
-    let recent_repos = load_recent_repos();
-    let recent_model: Vec<SharedString> = recent_repos
-        .iter()
-        .map(|s| SharedString::from(s.as_str()))
-        .collect();
-    ui.set_recent_repos(ModelRc::new(VecModel::from(recent_model)));
-
-    // 履歴があれば最初のリポジトリを選択、なければホームディレクトリ
-    let initial_repo = if !recent_repos.is_empty() {
-        ui.set_repo_path(recent_repos[0].clone().into());
-        ui.set_selected_repo_index(0);
-        Some(recent_repos[0].clone())
-    } else if let Some(home) = dirs::home_dir() {
-        ui.set_repo_path(home.to_string_lossy().to_string().into());
-        None
-    } else {
-        None
-    };
 
-    let refresh_ui = {
-        let ui_weak = ui.as_weak();
+    // Commit and Push
+    {
         let git_client = git_client.clone();
-        move || {
+        let refresh = refresh_ui.clone();
+        let ui_weak = ui.as_weak();
+        let history = commit_message_history.clone();
+        ui.on_commit_and_push(move || {
             let Some(ui) = ui_weak.upgrade() else {
                 return;
             };
-            let mut client = git_client.borrow_mut();
-
-            ui.set_current_branch(client.get_current_branch().into());
-            ui.set_local_branches(
-                Rc::new(slint::VecModel::from(client.get_local_branches())).into(),
-            );
-            ui.set_remote_branches(
-                Rc::new(slint::VecModel::from(client.get_remote_branches())).into(),
-            );
-            ui.set_stashes(Rc::new(slint::VecModel::from(client.get_stashes())).into());
-            let (commits, merge_lines) = client.get_commits_with_graph(300);
-            ui.set_commits(Rc::new(slint::VecModel::from(commits)).into());
-            ui.set_merge_lines(Rc::new(slint::VecModel::from(merge_lines)).into());
-
-            let (staged, unstaged) = client.get_status();
-            let staged_len = staged.len();
-            let unstaged_len = unstaged.len();
-            ui.set_staged_files(Rc::new(slint::VecModel::from(staged)).into());
-            ui.set_unstaged_files(Rc::new(slint::VecModel::from(unstaged)).into());
-
-            // チェック状態をリセット
-            ui.set_staged_checked(Rc::new(slint::VecModel::from(vec![false; staged_len])).into());
-            ui.set_unstaged_checked(
-                Rc::new(slint::VecModel::from(vec![false; unstaged_len])).into(),
-            );
-            ui.set_staged_checked_count(0);
-            ui.set_unstaged_checked_count(0);
-            ui.set_last_clicked_staged(-1);
-            ui.set_last_clicked_unstaged(-1);
-
-            ui.set_selected_commit(-1);
-            ui.set_selected_commit_hash("".into());
-            ui.set_selected_file(-1);
-            ui.set_diff_lines(Rc::new(slint::VecModel::from(Vec::<DiffLineData>::new())).into());
-        }
-    };
+            // Pull / Push実行中はコミットもしない（Pushだけ後回しにならないように）
+            if !ui.get_remote_operation().is_empty() {
+                return;
+            }
+            let result = prepare_and_commit(&ui, &git_client.borrow(), &history);
+            match result {
+                Ok(_) => {
+                    refresh();
+                    // PushはPushボタンと同じく別スレッドで実行する
+                    ui.invoke_push();
+                }
+                Err(e) if e.is_empty() => {}
+                Err(e) => ui.set_status_message(SharedString::from(e)),
+            }
+        });
+    }
 
-    // Open repository
+    // Checkout branch
     {
         let git_client = git_client.clone();
         let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_open_repo(move |path| {
-            let mut client = git_client.borrow_mut();
-            match client.open_repo(&path) {
+        ui.on_checkout_branch(move |name| {
+            let client = git_client.borrow();
+            match client.checkout_branch(&name) {
                 Ok(()) => {
-                    drop(client);
-                    // 履歴を更新
-                    let repos = add_recent_repo(&path);
                     if let Some(ui) = ui_weak.upgrade() {
-                        let recent_model: Vec<SharedString> = repos
-                            .iter()
-                            .map(|s| SharedString::from(s.as_str()))
-                            .collect();
-                        ui.set_recent_repos(ModelRc::new(VecModel::from(recent_model)));
-                        ui.set_selected_repo_index(0);
-
-                        // リポジトリ名を設定
-                        let repo_name = Path::new(&path)
-                            .file_name()
-                            .and_then(|n| n.to_str())
-                            .unwrap_or(&path)
-                            .to_string();
-                        ui.set_repo_name(SharedString::from(repo_name));
-
-                        ui.set_status_message("Repository opened".into());
+                        ui.set_status_message(SharedString::from(format!("Switched to {}", name)));
                     }
-                    refresh();
                 }
                 Err(e) => {
                     if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message(SharedString::from(format!("Error: {}", e)));
+                        if !show_checkout_conflict(&ui, &name, &e) {
+                            ui.set_status_message(SharedString::from(format!(
+                                "Checkout error: {}",
+                                e
+                            )));
+                        }
                     }
                 }
             }
+            drop(client);
+            refresh();
         });
     }
 
-    // Browse destination path for clone
+    // Checkout after stashing or discarding conflicting local changes
     {
+        let git_client = git_client.clone();
+        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_browse_clone_path(move || {
-            if let Some(path) = rfd::FileDialog::new()
-                .set_title("Select Destination Folder")
-                .pick_folder()
-            {
-                if let Some(ui) = ui_weak.upgrade() {
-                    ui.set_clone_path(path.to_string_lossy().to_string().into());
+        ui.on_resolve_checkout_conflict(move |name, force| {
+            let result = if force {
+                git_client.borrow().force_checkout_branch(&name)
+            } else {
+                git_client.borrow_mut().checkout_branch_stashing(&name)
+            };
+            if let Some(ui) = ui_weak.upgrade() {
+                match result {
+                    Ok(()) if force => ui.set_status_message(SharedString::from(format!(
+                        "Discarded local changes and switched to {}",
+                        name
+                    ))),
+                    Ok(()) => ui.set_status_message(SharedString::from(format!(
+                        "Stashed local changes and switched to {}",
+                        name
+                    ))),
+                    Err(e) => {
+                        ui.set_status_message(SharedString::from(format!("Checkout error: {}", e)))
+                    }
                 }
             }
+            refresh();
         });
     }
 
-    // Clone repository
+    // Branch quick switcher (あいまい検索)
     {
+        let git_client = git_client.clone();
         let ui_weak = ui.as_weak();
-        ui.on_clone_repo(move |url, path| {
-            let url = url.to_string();
-            let mut path_str = path.to_string();
-            let ui_weak_clone = ui_weak.clone();
-
-            std::thread::spawn(move || {
-                // スマートパス補完: 指定されたパスが存在し、かつ空でない場合
-                let path = Path::new(&path_str);
-                if path.exists()
-                    && path
-                        .read_dir()
-                        .map(|mut i| i.next().is_some())
-                        .unwrap_or(false)
-                {
-                    // URLからリポジトリ名を抽出 (e.g. https://github.com/user/repo.git -> repo)
-                    let repo_name = url
-                        .split('/')
-                        .last()
-                        .map(|s| s.trim_end_matches(".git"))
-                        .unwrap_or("repository");
-
-                    // パスにリポジトリ名を追加
-                    let new_path = path.join(repo_name);
-                    path_str = new_path.to_string_lossy().to_string();
-                }
-
-                // git cloneコマンドを実行（push/pull/fetchと同様にシステムのgitを使用）
-                let output = create_git_command()
-                    .args(["clone", &url, &path_str])
-                    .stdout(std::process::Stdio::piped())
-                    .stderr(std::process::Stdio::piped())
-                    .output();
+        ui.on_filter_branch_switcher(move |query| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let client = git_client.borrow();
+            let results = fuzzy_match_branches(
+                &query,
+                &client.get_local_branches(),
+                &client.get_remote_branches(),
+            );
+            ui.set_branch_switcher_results(ModelRc::new(VecModel::from(results)));
+            ui.set_branch_switcher_index(0);
+        });
+    }
 
-                match output {
-                    Ok(out) if out.status.success() => {
-                        let _ = slint::invoke_from_event_loop(move || {
-                            if let Some(ui) = ui_weak_clone.upgrade() {
-                                ui.set_is_cloning(false);
-                                ui.set_show_clone_dialog(false);
-                                ui.set_status_message("Clone successful".into());
-                                // Open the new repo using existing logic
-                                ui.invoke_open_repo(path_str.into());
-                            }
-                        });
-                    }
-                    Ok(out) => {
-                        let stderr = String::from_utf8_lossy(&out.stderr).to_string();
-                        let _ = slint::invoke_from_event_loop(move || {
-                            if let Some(ui) = ui_weak_clone.upgrade() {
-                                ui.set_is_cloning(false);
-                                ui.set_clone_error(stderr.into());
-                            }
-                        });
+    // Create branch
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_ui.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_create_branch(move |name| {
+            let client = git_client.borrow();
+            match client.create_branch(&name) {
+                Ok(()) => {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_message(SharedString::from(format!(
+                            "Created branch: {}",
+                            name
+                        )));
                     }
-                    Err(e) => {
-                        let error_msg = e.to_string();
-                        let _ = slint::invoke_from_event_loop(move || {
-                            if let Some(ui) = ui_weak_clone.upgrade() {
-                                ui.set_is_cloning(false);
-                                ui.set_clone_error(error_msg.into());
-                            }
-                        });
+                }
+                Err(e) => {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_message(SharedString::from(format!(
+                            "Create branch error: {}",
+                            e
+                        )));
                     }
                 }
-            });
+            }
+            drop(client);
+            refresh();
         });
     }
 
-    // Browse repository (folder dialog)
+    // Delete branch
     {
+        let git_client = git_client.clone();
+        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_browse_repo(move || {
-            if let Some(path) = rfd::FileDialog::new()
-                .set_title("Select Git Repository")
-                .pick_folder()
-            {
-                if let Some(ui) = ui_weak.upgrade() {
-                    let path_str = path.to_string_lossy().to_string();
-                    ui.set_repo_path(path_str.clone().into());
-                    ui.invoke_open_repo(path_str.into());
+        let pending_confirm = pending_confirm.clone();
+        ui.on_delete_branch(move |name, force| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let client = git_client.borrow();
+            let result = match client.delete_branch(&name, force) {
+                // 確認しない設定ならそのまま強制削除する
+                Err(GitError::NotFullyMerged) if !ui.get_confirm_force_delete() => {
+                    client.delete_branch(&name, true)
+                }
+                result => result,
+            };
+            match result {
+                Ok(()) => {
+                    ui.set_status_message(SharedString::from(format!(
+                        "Deleted branch: {}",
+                        name
+                    )));
+                }
+                Err(GitError::NotFullyMerged) => {
+                    // 未マージのコミットが失われる可能性があるので確認してから強制削除する
+                    request_confirm(
+                        &ui,
+                        &pending_confirm,
+                        PendingConfirm::ForceDelete(name.to_string()),
+                        &format!("Force delete {}?", name),
+                        "This branch is not fully merged. Its commits that are not on any other branch may become unreachable and be lost.",
+                        "Force Delete",
+                    );
+                    return;
+                }
+                Err(e) => {
+                    ui.set_status_message(SharedString::from(format!(
+                        "Delete branch error: {}",
+                        e
+                    )));
                 }
             }
+            drop(client);
+            refresh();
         });
     }
 
-    // Refresh (非同期Fetch後にUI更新)
+    // Merged branches（現在のブランチにマージ済みのブランチを一覧、初期状態は全てチェック）
     {
         let git_client = git_client.clone();
         let ui_weak = ui.as_weak();
-        ui.on_refresh(move || {
-            let ui_weak_clone = ui_weak.clone();
-            // 「Refreshing...」を表示
-            if let Some(ui) = ui_weak.upgrade() {
-                ui.set_status_message("Refresh & Fetch: Fetching...".into());
-            }
-
-            // リポジトリパスを取得（別スレッドで使用するため）
-            let repo_path = git_client.borrow().get_repo_path();
-
-            // 別スレッドでFetchを実行
-            std::thread::spawn(move || {
-                let fetch_result = if let Some(path) = repo_path {
-                    // GitClientを一時的に作成してfetchを実行
-                    let output = create_git_command()
-                        .args(["fetch", "--all"])
-                        .current_dir(&path)
-                        .stdout(std::process::Stdio::piped())
-                        .stderr(std::process::Stdio::piped())
-                        .output();
-
-                    match output {
-                        Ok(out) if out.status.success() => Ok(()),
-                        Ok(out) => {
-                            let stderr = String::from_utf8_lossy(&out.stderr);
-                            Err(format!("Fetch failed: {}", stderr))
-                        }
-                        Err(e) => Err(format!("Fetch error: {}", e)),
-                    }
-                } else {
-                    Err("No repository".to_string())
-                };
+        ui.on_load_merged_branches(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let client = git_client.borrow();
+            let current = client.get_current_branch();
+            let merged: Vec<SharedString> = client
+                .get_merged_branches(&current)
+                .into_iter()
+                .map(SharedString::from)
+                .collect();
+            ui.set_merged_branches_target(current.into());
+            ui.set_merged_branches_checked(ModelRc::new(VecModel::from(vec![true; merged.len()])));
+            ui.set_merged_branches_selected(ModelRc::new(VecModel::from(merged.clone())));
+            ui.set_merged_branches(ModelRc::new(VecModel::from(merged)));
+        });
+    }
 
-                // メインスレッドに戻ってUI更新
-                let _ = slint::invoke_from_event_loop(move || {
-                    if let Some(ui) = ui_weak_clone.upgrade() {
-                        match fetch_result {
-                            Ok(()) => {
-                                ui.set_status_message("Refresh & Fetch: Updating...".into());
-                                ui.invoke_update_local_state();
-                            }
-                            Err(e) => {
-                                ui.set_status_message(SharedString::from(e));
-                                // エラーでもローカル状態は更新
-                                ui.invoke_update_local_state();
-                            }
-                        }
-                    }
-                });
-            });
+    // Toggle merged branch check
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_toggle_merged_branch(move |idx, checked| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let checked_model = ui.get_merged_branches_checked();
+            let idx = idx as usize;
+            if idx < checked_model.row_count() {
+                checked_model.set_row_data(idx, checked);
+                let names = ui.get_merged_branches();
+                let selected: Vec<SharedString> = (0..checked_model.row_count())
+                    .filter(|&i| checked_model.row_data(i).unwrap_or(false))
+                    .filter_map(|i| names.row_data(i))
+                    .collect();
+                ui.set_merged_branches_selected(ModelRc::new(VecModel::from(selected)));
+            }
         });
     }
 
-    // Update local state (内部リフレッシュ用コールバック)
+    // Delete merged branches
     {
+        let git_client = git_client.clone();
         let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_update_local_state(move || {
-            refresh();
-            if let Some(ui) = ui_weak.upgrade() {
-                // Fetchingメッセージをクリア（既にセットされていなければ）
-                let current_msg = ui.get_status_message();
-                if current_msg == "Refresh & Fetch: Updating..." {
-                    ui.set_status_message("".into());
+        ui.on_delete_merged_branches(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let client = git_client.borrow();
+            let current = client.get_current_branch();
+            let selected = ui.get_merged_branches_selected();
+            let mut deleted = 0;
+            let mut errors = vec![];
+            for name in selected.iter() {
+                if name.as_str() == current {
+                    continue;
+                }
+                match client.delete_branch(&name, false) {
+                    Ok(()) => deleted += 1,
+                    Err(e) => errors.push(format!("{}: {}", name, e)),
                 }
             }
+            drop(client);
+            if errors.is_empty() {
+                ui.set_status_message(SharedString::from(format!(
+                    "Deleted {} merged branches",
+                    deleted
+                )));
+            } else {
+                ui.set_status_message(SharedString::from(format!(
+                    "Deleted {} merged branches; failed: {}",
+                    deleted,
+                    errors.join(", ")
+                )));
+            }
+            refresh();
         });
     }
 
-    // Stage file
+    // Merge branch
     {
         let git_client = git_client.clone();
         let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_stage_file(move |filename| {
+        ui.on_merge_branch(move |name| {
             let client = git_client.borrow();
-            if let Err(e) = client.stage_file(&filename) {
-                if let Some(ui) = ui_weak.upgrade() {
-                    ui.set_status_message(SharedString::from(format!("Stage error: {}", e)));
+            match client.merge_branch(&name) {
+                Ok(()) => {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_message(SharedString::from(format!("Merged: {}", name)));
+                    }
+                }
+                Err(e) => {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_message(SharedString::from(format!("Merge error: {}", e)));
+                    }
                 }
             }
             drop(client);
@@ -2425,457 +2787,773 @@ fn main() -> Result<(), slint::PlatformError> {
         });
     }
 
-    // Stage all
+    // Select commit
     {
         let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_stage_all(move || {
-            let client = git_client.borrow();
-            if let Err(e) = client.stage_all() {
+        let diff_cache = diff_cache.clone();
+        // Diff計算のリクエストID（連続クリック時に最新の結果だけを反映するため）
+        let diff_request_id = Arc::new(AtomicU64::new(0));
+        ui.on_select_commit(move |_index, hash| {
+            // 選択状態は既にSlint側で更新済み
+            // まずDiffエリアをクリアして選択のフィードバックを即座に表示
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_diff_files(ModelRc::default());
+                show_diff_lines(&ui, vec![]);
+                ui.set_selected_diff_file(-1);
+                ui.set_diff_view_title("".into());
+                DiffStats::default().apply_to(&ui);
+            }
+
+            // リポジトリパスを取得
+            let repo_path = {
+                let client = git_client.borrow();
+                client.get_repo_path()
+            };
+
+            let Some(repo_path) = repo_path else {
+                return;
+            };
+
+            // 別スレッドでDiff計算を実行
+            let context_lines = git_client.borrow().diff_context_lines;
+            let ui_weak = ui_weak.clone();
+            let hash = hash.to_string();
+            let request_id = diff_request_id.fetch_add(1, Ordering::SeqCst) + 1;
+            let diff_request_id = diff_request_id.clone();
+
+            // 計算済みならスレッドを起こさずにそのまま表示
+            let key = DiffCacheKey {
+                commit_hash: hash.clone(),
+                file_index: None,
+                context_lines,
+            };
+            let cached = diff_cache.lock().ok().and_then(|mut cache| cache.get(&key));
+            if let Some(diff) = cached {
                 if let Some(ui) = ui_weak.upgrade() {
-                    ui.set_status_message(SharedString::from(format!("Stage all error: {}", e)));
+                    show_commit_diff(&ui, diff);
                 }
+                return;
             }
-            drop(client);
-            refresh();
+
+            let diff_cache = diff_cache.clone();
+            std::thread::spawn(move || {
+                let diff = compute_commit_diff_in_thread(repo_path, hash.clone(), context_lines);
+                if let Ok(mut cache) = diff_cache.lock() {
+                    cache.insert(key, diff.clone());
+                }
+
+                // UIスレッドに結果を送信
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(ui) = ui_weak.upgrade() else {
+                        return;
+                    };
+                    // 後から発行されたリクエストがあれば、この結果は古いので捨てる
+                    if diff_request_id.load(Ordering::SeqCst) != request_id {
+                        return;
+                    }
+                    // 選択が変わっていないか確認
+                    if ui.get_selected_commit_hash() != hash.as_str() {
+                        return;
+                    }
+                    show_commit_diff(&ui, diff);
+                });
+            });
         });
     }
 
-    // Unstage file
+    // Select diff file
     {
         let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_unstage_file(move |filename| {
+        let diff_cache = diff_cache.clone();
+        ui.on_select_diff_file(move |file_index| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let commit_hash = ui.get_selected_commit_hash().to_string();
+            if commit_hash.is_empty() {
+                return;
+            }
             let client = git_client.borrow();
-            if let Err(e) = client.unstage_file(&filename) {
-                if let Some(ui) = ui_weak.upgrade() {
-                    ui.set_status_message(SharedString::from(format!("Unstage error: {}", e)));
+            let compare_from = ui.get_compare_from_hash().to_string();
+            let (diff_lines, total_count) = if compare_from.is_empty() {
+                let key = DiffCacheKey {
+                    commit_hash: commit_hash.clone(),
+                    file_index: Some(file_index as usize),
+                    context_lines: client.diff_context_lines,
+                };
+                let cached = diff_cache.lock().ok().and_then(|mut cache| cache.get(&key));
+                if let Some((_, diff_lines, total_count, _)) = cached {
+                    (diff_lines, total_count)
+                } else {
+                    let (diff_lines, total_count) =
+                        client.get_commit_file_diff(&commit_hash, file_index as usize);
+                    if let Ok(mut cache) = diff_cache.lock() {
+                        cache.insert(
+                            key,
+                            (
+                                vec![],
+                                diff_lines.clone(),
+                                total_count,
+                                DiffStats::default(),
+                            ),
+                        );
+                    }
+                    (diff_lines, total_count)
                 }
-            }
-            drop(client);
-            refresh();
+            } else {
+                client.get_refs_file_diff(&compare_from, &commit_hash, file_index as usize)
+            };
+            show_diff_lines(&ui, diff_lines);
+            ui.set_diff_total_lines(total_count as i32);
+            ui.set_diff_view_title(compare_title(&compare_from, &commit_hash).into());
         });
     }
 
-    // Discard file changes
+    // Commit signatures (表示中の行だけ別スレッドで検証し、結果はハッシュごとにキャッシュ)
     {
         let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_discard_file(move |filename| {
-            let client = git_client.borrow();
-            match client.discard_file(&filename) {
-                Ok(()) => {
-                    if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message(SharedString::from(format!(
-                            "Discarded changes: {}",
-                            filename
-                        )));
+        let signature_cache: Arc<Mutex<HashMap<String, &'static str>>> =
+            Arc::new(Mutex::new(HashMap::new()));
+        ui.on_request_signatures(move |first, count| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let Some(path) = git_client.borrow().get_repo_path() else {
+                return;
+            };
+            let commits = ui.get_commits();
+            let first = first.max(0) as usize;
+            let end = (first + count.max(0) as usize).min(commits.row_count());
+            let mut missing = vec![];
+            {
+                let Ok(mut cache) = signature_cache.lock() else {
+                    return;
+                };
+                for i in first..end {
+                    let Some(mut row) = commits.row_data(i) else {
+                        continue;
+                    };
+                    if row.is_uncommitted {
+                        continue;
                     }
-                }
-                Err(e) => {
-                    if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message(SharedString::from(format!("Discard error: {}", e)));
+                    match cache.get(row.full_hash.as_str()) {
+                        // 検証中（""）は結果が届くまで待つ
+                        Some(status) => {
+                            if !status.is_empty() && row.signature != *status {
+                                row.signature = (*status).into();
+                                commits.set_row_data(i, row);
+                            }
+                        }
+                        None => {
+                            cache.insert(row.full_hash.to_string(), "");
+                            missing.push(row.full_hash.to_string());
+                        }
                     }
                 }
             }
-            drop(client);
-            refresh();
+            if missing.is_empty() {
+                return;
+            }
+
+            let git_available = ui.get_git_available();
+            let signature_cache = signature_cache.clone();
+            let ui_weak = ui_weak.clone();
+            std::thread::spawn(move || {
+                let results = check_commit_signatures(&path, &missing, git_available);
+                let _ = slint::invoke_from_event_loop(move || {
+                    let mut statuses = HashMap::new();
+                    if let Ok(mut cache) = signature_cache.lock() {
+                        for (hash, status) in results {
+                            cache.insert(hash.clone(), status);
+                            statuses.insert(hash, status);
+                        }
+                        // 検証できなかったものは次回スクロール時に再試行
+                        for hash in &missing {
+                            if cache.get(hash) == Some(&"") {
+                                cache.remove(hash);
+                            }
+                        }
+                    }
+                    let Some(ui) = ui_weak.upgrade() else {
+                        return;
+                    };
+                    let commits = ui.get_commits();
+                    for i in 0..commits.row_count() {
+                        let Some(mut row) = commits.row_data(i) else {
+                            continue;
+                        };
+                        if let Some(status) = statuses.get(row.full_hash.as_str()) {
+                            row.signature = (*status).into();
+                            commits.set_row_data(i, row);
+                        }
+                    }
+                });
+            });
         });
     }
 
-    // Unstage all
+    // Compare two commits (Shift+Click で選択中のコミットと比較)
     {
         let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_unstage_all(move || {
+        ui.on_compare_commits(move |from, to| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
             let client = git_client.borrow();
-            if let Err(e) = client.unstage_all() {
-                if let Some(ui) = ui_weak.upgrade() {
-                    ui.set_status_message(SharedString::from(format!("Unstage all error: {}", e)));
-                }
+            // 行の並びではなく履歴上の前後関係で、古い方 → 新しい方の向きにする
+            let (from, to) = client.order_commits(&from, &to);
+            if from != ui.get_compare_from_hash().as_str() {
+                let from_index = ui.get_compare_from_index();
+                ui.set_compare_from_index(ui.get_selected_commit());
+                ui.set_selected_commit(from_index);
+                ui.set_compare_from_hash(from.as_str().into());
+                ui.set_selected_commit_hash(to.as_str().into());
             }
-            drop(client);
-            refresh();
+            let (diff_files, diff_lines, total_count, stats) = client.diff_refs(&from, &to);
+            ui.set_diff_files(Rc::new(slint::VecModel::from(diff_files)).into());
+            ui.set_selected_diff_file(-1);
+            show_diff_lines(&ui, diff_lines);
+            ui.set_diff_total_lines(total_count as i32);
+            stats.apply_to(&ui);
+            ui.set_diff_view_title(compare_title(&from, &to).into());
         });
     }
 
-    // Toggle staged check
+    // View file at revision
     {
+        let git_client = git_client.clone();
         let ui_weak = ui.as_weak();
-        ui.on_toggle_staged_check(move |idx, checked| {
+        ui.on_view_file_at_commit(move |hash, path| {
             let Some(ui) = ui_weak.upgrade() else {
                 return;
             };
-            let checked_model = ui.get_staged_checked();
-            let idx = idx as usize;
-            if idx < checked_model.row_count() {
-                checked_model.set_row_data(idx, checked);
-                // カウント更新
-                let count = (0..checked_model.row_count())
-                    .filter(|&i| checked_model.row_data(i).unwrap_or(false))
-                    .count();
-                ui.set_staged_checked_count(count as i32);
-            }
+            let client = git_client.borrow();
+            let lines = match client.get_file_at_commit(&hash, &path) {
+                Ok(content) => file_content_to_lines(&content),
+                Err(GitError::BinaryFile) => vec![binary_file_placeholder()],
+                Err(e) => {
+                    ui.set_status_message(SharedString::from(format!("View file error: {}", e)));
+                    return;
+                }
+            };
+            let count = lines.len();
+            show_diff_lines(&ui, lines);
+            ui.set_diff_total_lines(count as i32);
+            ui.set_diff_view_title(SharedString::from(format!(
+                "{} @ {}",
+                path,
+                &hash[..7.min(hash.len())]
+            )));
         });
     }
 
-    // Toggle unstaged check
+    // Select file
     {
+        let git_client = git_client.clone();
         let ui_weak = ui.as_weak();
-        ui.on_toggle_unstaged_check(move |idx, checked| {
+        ui.on_select_file(move |filename, staged| {
             let Some(ui) = ui_weak.upgrade() else {
                 return;
             };
-            let checked_model = ui.get_unstaged_checked();
-            let idx = idx as usize;
-            if idx < checked_model.row_count() {
-                checked_model.set_row_data(idx, checked);
-                // カウント更新
-                let count = (0..checked_model.row_count())
-                    .filter(|&i| checked_model.row_data(i).unwrap_or(false))
-                    .count();
-                ui.set_unstaged_checked_count(count as i32);
+            let client = git_client.borrow();
+            let (diff_lines, total_count) = working_file_diff(&ui, &client, &filename, staged);
+            show_diff_lines(&ui, diff_lines);
+            ui.set_diff_total_lines(total_count as i32);
+            // コンフリクト中のファイルはbase/ours/theirsを並べて表示する
+            let conflict = if staged {
+                None
+            } else {
+                client.get_conflict_versions(&filename).ok()
+            };
+            let to_model = |lines: Option<Vec<String>>| -> ModelRc<SharedString> {
+                let lines: Vec<SharedString> = lines
+                    .unwrap_or_else(|| vec!["(not present)".to_string()])
+                    .into_iter()
+                    .map(SharedString::from)
+                    .collect();
+                ModelRc::new(VecModel::from(lines))
+            };
+            match conflict {
+                Some(versions) => {
+                    ui.set_conflict_base_lines(to_model(versions.base));
+                    ui.set_conflict_ours_lines(to_model(versions.ours));
+                    ui.set_conflict_theirs_lines(to_model(versions.theirs));
+                    ui.set_conflict_view_file(filename.clone());
+                }
+                None => ui.set_conflict_view_file("".into()),
             }
+            // Stage Hunk用にファイル情報を保存
+            ui.set_current_diff_filename(filename.clone());
+            ui.set_current_diff_is_staged(staged);
         });
     }
 
-    // Staged range select (Shift+Click)
+    // Stage hunk
     {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
         let ui_weak = ui.as_weak();
-        ui.on_staged_range_select(move |idx| {
+        ui.on_stage_hunk(move |hunk_index| {
             let Some(ui) = ui_weak.upgrade() else {
                 return;
             };
-            let last = ui.get_last_clicked_staged();
-            if last < 0 {
-                // 前回クリックがない場合は単一選択
-                ui.invoke_toggle_staged_check(idx, true);
+            let filename = ui.get_current_diff_filename().to_string();
+            if filename.is_empty() {
+                ui.set_status_message("No file selected".into());
                 return;
             }
-            let checked_model = ui.get_staged_checked();
-            let start = last.min(idx) as usize;
-            let end = last.max(idx) as usize;
-            for i in start..=end {
-                if i < checked_model.row_count() {
-                    checked_model.set_row_data(i, true);
+            let client = git_client.borrow();
+            match client.stage_hunk(&filename, hunk_index as usize) {
+                Ok(()) => {
+                    ui.set_status_message(SharedString::from(format!(
+                        "Staged hunk {} of {}",
+                        hunk_index + 1,
+                        filename
+                    )));
+                    // Diffを更新
+                    let (diff_lines, total_count) = client.get_file_diff(&filename, false);
+                    show_diff_lines(&ui, diff_lines);
+                    ui.set_diff_total_lines(total_count as i32);
+                }
+                Err(e) => {
+                    ui.set_status_message(SharedString::from(format!("Stage hunk error: {}", e)));
                 }
             }
-            let count = (0..checked_model.row_count())
-                .filter(|&i| checked_model.row_data(i).unwrap_or(false))
-                .count();
-            ui.set_staged_checked_count(count as i32);
+            drop(client);
+            refresh();
         });
     }
 
-    // Unstaged range select (Shift+Click)
+    // Stage all hunks except one
     {
+        let git_client = git_client.clone();
+        let refresh = refresh_status.clone();
         let ui_weak = ui.as_weak();
-        ui.on_unstaged_range_select(move |idx| {
+        ui.on_stage_all_hunks_except(move |hunk_index| {
             let Some(ui) = ui_weak.upgrade() else {
                 return;
             };
-            let last = ui.get_last_clicked_unstaged();
-            if last < 0 {
-                ui.invoke_toggle_unstaged_check(idx, true);
+            let filename = ui.get_current_diff_filename().to_string();
+            if filename.is_empty() {
+                ui.set_status_message("No file selected".into());
                 return;
             }
-            let checked_model = ui.get_unstaged_checked();
-            let start = last.min(idx) as usize;
-            let end = last.max(idx) as usize;
-            for i in start..=end {
-                if i < checked_model.row_count() {
-                    checked_model.set_row_data(i, true);
+            let client = git_client.borrow();
+            match client.stage_all_hunks_except(&filename, hunk_index as usize) {
+                Ok(()) => {
+                    ui.set_status_message(SharedString::from(format!(
+                        "Staged all hunks of {} except hunk {}",
+                        filename,
+                        hunk_index + 1
+                    )));
+                    // Diffを更新
+                    let (diff_lines, total_count) = client.get_file_diff(&filename, false);
+                    show_diff_lines(&ui, diff_lines);
+                    ui.set_diff_total_lines(total_count as i32);
+                }
+                Err(e) => {
+                    ui.set_status_message(SharedString::from(format!("Stage hunks error: {}", e)));
                 }
             }
-            let count = (0..checked_model.row_count())
-                .filter(|&i| checked_model.row_data(i).unwrap_or(false))
-                .count();
-            ui.set_unstaged_checked_count(count as i32);
+            drop(client);
+            refresh();
         });
     }
 
-    // Stage selected files
+    // Collapse / expand hunks (折りたたみ状態はhunk-collapsedに保持)
     {
-        let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_stage_selected(move || {
+        ui.on_toggle_hunk(move |hunk_index| {
             let Some(ui) = ui_weak.upgrade() else {
                 return;
             };
-            let client = git_client.borrow();
-            let files = ui.get_unstaged_files();
-            let checked = ui.get_unstaged_checked();
-            let mut staged_count = 0;
-
-            for i in 0..files.row_count() {
-                if let (Some(file), Some(is_checked)) = (files.row_data(i), checked.row_data(i)) {
-                    if is_checked {
-                        if client.stage_file(&file.filename).is_ok() {
-                            staged_count += 1;
-                        }
-                    }
-                }
-            }
-            drop(client);
-            if staged_count > 0 {
-                ui.set_status_message(SharedString::from(format!("Staged {} files", staged_count)));
+            let mut collapsed: std::collections::HashSet<i32> = ui
+                .get_hunk_collapsed()
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| *c)
+                .map(|(i, _)| i as i32)
+                .collect();
+            if !collapsed.remove(&hunk_index) {
+                collapsed.insert(hunk_index);
             }
-            refresh();
+            apply_hunk_collapse(&ui, collapsed);
         });
     }
-
-    // Unstage selected files
     {
-        let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_unstage_selected(move || {
+        ui.on_set_all_hunks_collapsed(move |collapse| {
             let Some(ui) = ui_weak.upgrade() else {
                 return;
             };
-            let client = git_client.borrow();
-            let files = ui.get_staged_files();
-            let checked = ui.get_staged_checked();
-            let mut unstaged_count = 0;
+            let collapsed = if collapse {
+                ui.get_diff_source_lines()
+                    .iter()
+                    .map(|line| line.hunk_index)
+                    .filter(|&i| i >= 0)
+                    .collect()
+            } else {
+                std::collections::HashSet::new()
+            };
+            apply_hunk_collapse(&ui, collapsed);
+        });
+    }
 
-            for i in 0..files.row_count() {
-                if let (Some(file), Some(is_checked)) = (files.row_data(i), checked.row_data(i)) {
-                    if is_checked {
-                        if client.unstage_file(&file.filename).is_ok() {
-                            unstaged_count += 1;
+    // Checkout remote branch
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_ui.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_checkout_remote_branch(move |name| {
+            let client = git_client.borrow();
+            match client.checkout_remote_branch(&name) {
+                Ok(()) => {
+                    let local_name = local_name_for_remote(&name);
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_message(SharedString::from(format!(
+                            "Checked out {} from {}",
+                            local_name, name
+                        )));
+                    }
+                }
+                Err(e) => {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        // ローカルブランチは作成済みなので、衝突時はそのブランチへの切替をやり直す
+                        if !show_checkout_conflict(&ui, &local_name_for_remote(&name), &e) {
+                            ui.set_status_message(SharedString::from(format!(
+                                "Checkout error: {}",
+                                e
+                            )));
                         }
                     }
                 }
             }
             drop(client);
-            if unstaged_count > 0 {
-                ui.set_status_message(SharedString::from(format!(
-                    "Unstaged {} files",
-                    unstaged_count
-                )));
-            }
             refresh();
         });
     }
 
-    // Discard selected files
+    // Open the create-tracking-branch dialog (ローカル名はリモート名を除いた名前で初期化)
     {
-        let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_discard_selected(move || {
+        ui.on_begin_create_tracking_branch(move |remote_ref| {
             let Some(ui) = ui_weak.upgrade() else {
                 return;
             };
-            let client = git_client.borrow();
-            let files = ui.get_unstaged_files();
-            let checked = ui.get_unstaged_checked();
-            let mut discarded_count = 0;
+            ui.set_tracking_local_name(local_name_for_remote(&remote_ref).into());
+            ui.set_tracking_remote_ref(remote_ref);
+            ui.set_tracking_checkout(true);
+            ui.set_show_create_tracking_branch(true);
+        });
+    }
 
-            for i in 0..files.row_count() {
-                if let (Some(file), Some(is_checked)) = (files.row_data(i), checked.row_data(i)) {
-                    if is_checked {
-                        if client.discard_file(&file.filename).is_ok() {
-                            discarded_count += 1;
+    // Create a local branch tracking a remote branch
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_ui.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_create_tracking_branch(move |remote_ref, local_name, checkout| {
+            let result =
+                git_client
+                    .borrow()
+                    .create_tracking_branch(&remote_ref, &local_name, checkout);
+            if let Some(ui) = ui_weak.upgrade() {
+                match result {
+                    Ok(()) => ui.set_status_message(SharedString::from(format!(
+                        "Created {} tracking {}{}",
+                        local_name.trim(),
+                        remote_ref,
+                        if checkout { " and checked it out" } else { "" }
+                    ))),
+                    Err(e) => {
+                        if !show_checkout_conflict(&ui, local_name.trim(), &e) {
+                            ui.set_status_message(SharedString::from(format!(
+                                "Create branch error: {}",
+                                e
+                            )))
                         }
                     }
                 }
             }
-            drop(client);
-            if discarded_count > 0 {
-                ui.set_status_message(SharedString::from(format!(
-                    "Discarded {} files",
-                    discarded_count
-                )));
-            }
             refresh();
         });
     }
 
-    // Commit
+    // Pull/Push/Fetch（ネットワーク待ちでUIが固まらないよう別スレッドで実行、実行中の再クリックは無視）
     {
         let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        let history = commit_message_history.clone();
-        ui.on_commit(move || {
+        ui.on_pull(move || {
             let Some(ui) = ui_weak.upgrade() else {
                 return;
             };
-            let message = ui.get_commit_message().to_string();
-            if message.is_empty() {
+            if !ui.get_remote_operation().is_empty() {
                 return;
             }
-            let client = git_client.borrow();
-            match client.commit(&message) {
-                Ok(()) => {
-                    // 履歴に追加
-                    {
-                        let mut hist = history.borrow_mut();
-                        // 既に存在する場合は削除してから先頭に追加
-                        hist.retain(|m| m != &message);
-                        hist.insert(0, message.clone());
-                        if hist.len() > MAX_COMMIT_HISTORY {
-                            hist.truncate(MAX_COMMIT_HISTORY);
+            let Some(path) = git_client.borrow().get_repo_path() else {
+                ui.set_status_message("Pull error: No repository".into());
+                return;
+            };
+            let mode = ui.get_pull_mode().to_string();
+            let transport = git_client.borrow().remote_transport();
+            ui.set_remote_operation("pull".into());
+            ui.set_push_rejected(false);
+            ui.set_status_message("Pulling...".into());
+
+            let ui_weak = ui_weak.clone();
+            std::thread::spawn(move || {
+                let result = pull_repo(&path, &mode, transport);
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(ui) = ui_weak.upgrade() else {
+                        return;
+                    };
+                    ui.set_remote_operation("".into());
+                    match result {
+                        Ok(()) => ui.set_status_message("Pull successful".into()),
+                        Err(e) => {
+                            ui.set_status_message(SharedString::from(format!("Pull error: {}", e)))
                         }
-                        // UIに反映
-                        let model: Vec<SharedString> = hist
-                            .iter()
-                            .map(|s| SharedString::from(s.as_str()))
-                            .collect();
-                        ui.set_commit_message_history(ModelRc::new(VecModel::from(model)));
-                        // ファイルに保存
-                        save_commit_history(&hist);
-                    }
-                    ui.set_commit_message("".into());
-                    ui.set_commit_history_index(-1);
-                    ui.set_status_message("Commit successful".into());
-                }
-                Err(e) => {
-                    ui.set_status_message(SharedString::from(format!("Commit error: {}", e)));
-                }
-            }
-            drop(client);
-            refresh();
+                    }
+                    // UIスレッドに戻ったのでローカル状態を更新
+                    ui.invoke_update_local_state();
+                });
+            });
         });
     }
-
-    // Commit and Push
     {
         let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        let history = commit_message_history.clone();
-        ui.on_commit_and_push(move || {
+        ui.on_push(move || {
             let Some(ui) = ui_weak.upgrade() else {
                 return;
             };
-            let message = ui.get_commit_message().to_string();
-            if message.is_empty() {
+            if !ui.get_remote_operation().is_empty() {
                 return;
             }
-            let client = git_client.borrow();
-            match client.commit(&message) {
-                Ok(()) => {
-                    // 履歴に追加
-                    {
-                        let mut hist = history.borrow_mut();
-                        hist.retain(|m| m != &message);
-                        hist.insert(0, message.clone());
-                        if hist.len() > MAX_COMMIT_HISTORY {
-                            hist.truncate(MAX_COMMIT_HISTORY);
-                        }
-                        let model: Vec<SharedString> = hist
-                            .iter()
-                            .map(|s| SharedString::from(s.as_str()))
-                            .collect();
-                        ui.set_commit_message_history(ModelRc::new(VecModel::from(model)));
-                        // ファイルに保存
-                        save_commit_history(&hist);
-                    }
-                    ui.set_commit_message("".into());
-                    ui.set_commit_history_index(-1);
-                    // Pushを実行
-                    match client.push() {
-                        Ok(()) => {
-                            ui.set_status_message("Commit & Push successful".into());
+            let (path, branch) = match git_client.borrow().push_target() {
+                Ok(target) => target,
+                Err(e) => {
+                    ui.set_status_message(SharedString::from(format!("Push error: {}", e)));
+                    return;
+                }
+            };
+            let transport = git_client.borrow().remote_transport();
+            ui.set_remote_operation("push".into());
+            ui.set_push_rejected(false);
+            ui.set_status_message(SharedString::from(format!("Pushing {}...", branch)));
+
+            let ui_weak = ui_weak.clone();
+            std::thread::spawn(move || {
+                let result = push_branch(&path, &branch, transport);
+                let _ = slint::invoke_from_event_loop(move || {
+                    let Some(ui) = ui_weak.upgrade() else {
+                        return;
+                    };
+                    ui.set_remote_operation("".into());
+                    match result {
+                        Ok(()) => ui.set_status_message("Push successful".into()),
+                        Err(e @ GitError::PushRejected) => {
+                            ui.set_push_rejected(true);
+                            ui.set_status_message(e.to_string().into());
                         }
                         Err(e) => {
-                            ui.set_status_message(SharedString::from(format!(
-                                "Commit successful, but push failed: {}",
-                                e
-                            )));
+                            ui.set_status_message(SharedString::from(format!("Push error: {}", e)))
                         }
                     }
-                }
-                Err(e) => {
-                    ui.set_status_message(SharedString::from(format!("Commit error: {}", e)));
-                }
+                    // UIスレッドに戻ったのでローカル状態を更新
+                    ui.invoke_update_local_state();
+                });
+            });
+        });
+    }
+
+    // Copy commit hash to clipboard
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_copy_commit_hash(move |hash| {
+            let hash_str = hash.to_string();
+            copy_to_clipboard_async(hash_str.clone());
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_status_message(SharedString::from(format!(
+                    "Copied: {}",
+                    &hash_str[..7.min(hash_str.len())]
+                )));
             }
-            drop(client);
-            refresh();
         });
     }
 
-    // Checkout branch
+    // Copy commit message to clipboard
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_copy_commit_message(move |message| {
+            let message_str = message.to_string();
+            copy_to_clipboard_async(message_str.clone());
+            if let Some(ui) = ui_weak.upgrade() {
+                // 長いメッセージは省略して表示（文字数ベースで安全にスライス）
+                let display_msg: String = if message_str.chars().count() > 30 {
+                    format!("{}...", message_str.chars().take(30).collect::<String>())
+                } else {
+                    message_str
+                };
+                ui.set_status_message(SharedString::from(format!("Copied: {}", display_msg)));
+            }
+        });
+    }
+
+    // Copy file path to clipboard（absoluteならワークツリーのパスと結合）
     {
         let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_checkout_branch(move |name| {
-            let client = git_client.borrow();
-            match client.checkout_branch(&name) {
-                Ok(()) => {
-                    if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message(SharedString::from(format!("Switched to {}", name)));
-                    }
-                }
-                Err(e) => {
-                    if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message(SharedString::from(format!("Checkout error: {}", e)));
-                    }
-                }
+        ui.on_copy_file_path(move |filename, absolute| {
+            let path_str = if absolute {
+                git_client
+                    .borrow()
+                    .resolve_workdir_path(&filename)
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_else(|| filename.to_string())
+            } else {
+                filename.to_string()
+            };
+            copy_to_clipboard_async(path_str.clone());
+            if let Some(ui) = ui_weak.upgrade() {
+                // 長いパスは先頭を省略してファイル名側を残す
+                let count = path_str.chars().count();
+                let display_path: String = if count > 40 {
+                    format!(
+                        "...{}",
+                        path_str.chars().skip(count - 40).collect::<String>()
+                    )
+                } else {
+                    path_str
+                };
+                ui.set_status_message(SharedString::from(format!("Copied: {}", display_path)));
             }
-            drop(client);
-            refresh();
         });
     }
 
-    // Create branch
+    // Copy line reference (行番号クリックで path:line、コミットのDiffでは hash:path:line をコピー)
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_copy_line_ref(move |row, line, old_side| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let lines: Vec<DiffLineData> = ui.get_diff_lines().iter().collect();
+            let fallback = if ui.get_commit_mode() {
+                ui.get_current_diff_filename().to_string()
+            } else {
+                ui.get_diff_files()
+                    .row_data(ui.get_selected_diff_file().max(0) as usize)
+                    .map(|f| f.filename.to_string())
+                    .unwrap_or_default()
+            };
+            let Some(path) = diff_path_at(&lines, row.max(0) as usize)
+                .or((!fallback.is_empty()).then_some(fallback))
+            else {
+                return;
+            };
+
+            // 作業ツリーのDiffはパスのみ、コミットのDiffは行番号の側のコミットを付ける
+            let commit_hash = ui.get_selected_commit_hash().to_string();
+            let reference = if ui.get_commit_mode() || commit_hash.is_empty() {
+                format!("{}:{}", path, line)
+            } else {
+                let compare_from = ui.get_compare_from_hash().to_string();
+                let rev = match (old_side, compare_from.is_empty()) {
+                    (false, _) => commit_hash[..7.min(commit_hash.len())].to_string(),
+                    (true, true) => format!("{}^", &commit_hash[..7.min(commit_hash.len())]),
+                    (true, false) => compare_from[..7.min(compare_from.len())].to_string(),
+                };
+                format!("{}:{}:{}", rev, path, line)
+            };
+            copy_to_clipboard_async(reference.clone());
+            ui.set_status_message(SharedString::from(format!("Copied: {}", reference)));
+        });
+    }
+
+    // Undo last destructive action (破棄・ブランチ削除・ハードリセットを1つ戻す)
     {
         let git_client = git_client.clone();
         let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_create_branch(move |name| {
-            let client = git_client.borrow();
-            match client.create_branch(&name) {
-                Ok(()) => {
-                    if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message(SharedString::from(format!(
-                            "Created branch: {}",
-                            name
-                        )));
+        ui.on_undo_last_action(move || {
+            let result = git_client.borrow().undo_last_action();
+            if let Some(ui) = ui_weak.upgrade() {
+                match result {
+                    Ok(description) => {
+                        ui.set_status_message(SharedString::from(format!("Undid {}", description)))
                     }
-                }
-                Err(e) => {
-                    if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message(SharedString::from(format!(
-                            "Create branch error: {}",
-                            e
-                        )));
+                    Err(e) => {
+                        ui.set_status_message(SharedString::from(format!("Undo error: {}", e)))
                     }
                 }
             }
-            drop(client);
             refresh();
         });
     }
 
-    // Delete branch
+    // Reset to commit
     {
         let git_client = git_client.clone();
         let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_delete_branch(move |name| {
+        let pending_confirm = pending_confirm.clone();
+        let confirm_bypass = confirm_bypass.clone();
+        ui.on_reset_to_commit(move |hash, mode, force| {
+            // Uncommitted行はハッシュが空
+            if hash.is_empty() {
+                return;
+            }
             let client = git_client.borrow();
-            match client.delete_branch(&name) {
+            if !force && client.reset_drops_pushed(&hash) {
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_rewrite_hash(hash.clone());
+                    ui.set_rewrite_mode(mode.clone());
+                    show_rewrite_warning(
+                        &ui,
+                        "reset",
+                        "Commits that are already on a remote will be removed from this branch. You will need to force-push.",
+                    );
+                }
+                return;
+            }
+            // push済み警告で確認済み（force）でなければ、hard reset は別途確認する
+            if !force && mode == "hard" && !confirm_bypass.get() {
+                if let Some(ui) = ui_weak.upgrade() {
+                    if ui.get_confirm_hard_reset() {
+                        request_confirm(
+                            &ui,
+                            &pending_confirm,
+                            PendingConfirm::ResetToCommit {
+                                hash: hash.to_string(),
+                                mode: mode.to_string(),
+                            },
+                            &format!("Hard reset to {}?", &hash[..7]),
+                            "Uncommitted changes in the working tree and index will be lost.",
+                            "Reset",
+                        );
+                        return;
+                    }
+                }
+            }
+            match client.reset_to_commit(&hash, &mode) {
                 Ok(()) => {
                     if let Some(ui) = ui_weak.upgrade() {
                         ui.set_status_message(SharedString::from(format!(
-                            "Deleted branch: {}",
-                            name
+                            "Reset ({}) to {}",
+                            mode,
+                            &hash[..7]
                         )));
                     }
                 }
                 Err(e) => {
                     if let Some(ui) = ui_weak.upgrade() {
                         ui.set_status_message(SharedString::from(format!(
-                            "Delete branch error: {}",
+                            "Reset error: {}",
                             e
                         )));
                     }
@@ -2886,325 +3564,447 @@ fn main() -> Result<(), slint::PlatformError> {
         });
     }
 
-    // Merge branch
+    // Reflog
     {
         let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_merge_branch(move |name| {
+        ui.on_load_reflog(move || {
             let client = git_client.borrow();
-            match client.merge_branch(&name) {
-                Ok(()) => {
-                    if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message(SharedString::from(format!("Merged: {}", name)));
-                    }
-                }
-                Err(e) => {
-                    if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message(SharedString::from(format!("Merge error: {}", e)));
-                    }
-                }
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.set_reflog_entries(Rc::new(VecModel::from(client.get_reflog("HEAD"))).into());
+                ui.set_reflog_selected_index(-1);
             }
-            drop(client);
-            refresh();
         });
     }
 
-    // Select commit
+    // Show only the current branch's history in the graph
     {
         let git_client = git_client.clone();
+        let refresh_graph = refresh_graph.clone();
         let ui_weak = ui.as_weak();
-        ui.on_select_commit(move |_index, hash| {
-            // 選択状態は既にSlint側で更新済み
-            // まずDiffエリアをクリアして選択のフィードバックを即座に表示
-            if let Some(ui) = ui_weak.upgrade() {
-                ui.set_diff_files(ModelRc::default());
-                ui.set_diff_lines(ModelRc::default());
-                ui.set_selected_diff_file(-1);
+        ui.on_set_current_branch_only(move |only| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let mut client = git_client.borrow_mut();
+            client.set_current_branch_only(only);
+            if let Some(path) = client.get_repo_path() {
+                save_repo_state_value(&path, "current_branch_only", only.into());
             }
+            drop(client);
+            refresh_graph(shown_uncommitted_count(&ui));
+        });
+    }
 
-            // リポジトリパスを取得
-            let repo_path = {
-                let client = git_client.borrow();
-                client.get_repo_path()
+    // Filter the commit graph by author / branch
+    {
+        let git_client = git_client.clone();
+        let refresh_graph = refresh_graph.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_set_graph_filter(move |author, branch| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
             };
+            // 作者は "Name <email>" の候補から選ぶのでメールだけを使う
+            let email = parse_author(&author)
+                .ok()
+                .flatten()
+                .map(|(_, email)| email)
+                .unwrap_or_default();
+            git_client.borrow_mut().set_graph_filter(&email, &branch);
+            refresh_graph(shown_uncommitted_count(&ui));
+        });
+    }
 
-            let Some(repo_path) = repo_path else {
+    // Load repository insights（全コミットをたどるので別スレッドで集計する）
+    {
+        let git_client = git_client.clone();
+        let ui_weak = ui.as_weak();
+        let stats_generation = stats_generation.clone();
+        ui.on_load_repo_stats(move || {
+            let Some(background) = git_client.borrow().reopen() else {
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_status_message(SharedString::from(format!(
+                        "Insights error: {}",
+                        GitError::NoRepository
+                    )));
+                }
                 return;
             };
-
-            // 別スレッドでDiff計算を実行
+            let generation = stats_generation.fetch_add(1, Ordering::SeqCst) + 1;
+            let stats_generation = stats_generation.clone();
             let ui_weak = ui_weak.clone();
-            let hash = hash.to_string();
             std::thread::spawn(move || {
-                let (diff_files, diff_lines, total_count) =
-                    compute_commit_diff_in_thread(repo_path, hash.clone());
-
-                // UIスレッドに結果を送信
+                let stats = background.get_repo_stats().map_err(|e| e.to_string());
                 let _ = slint::invoke_from_event_loop(move || {
+                    if stats_generation.load(Ordering::SeqCst) != generation {
+                        return;
+                    }
                     let Some(ui) = ui_weak.upgrade() else {
                         return;
                     };
-                    // 選択が変わっていないか確認
-                    if ui.get_selected_commit_hash().to_string() != hash {
-                        return;
+                    match stats {
+                        Ok(stats) => {
+                            ui.set_repo_stats(stats);
+                            ui.set_show_repo_stats(true);
+                        }
+                        Err(e) => ui.set_status_message(SharedString::from(format!(
+                            "Insights error: {}",
+                            e
+                        ))),
                     }
-                    ui.set_diff_files(Rc::new(slint::VecModel::from(diff_files)).into());
-                    ui.set_selected_diff_file(-1);
-                    ui.set_diff_lines(Rc::new(slint::VecModel::from(diff_lines)).into());
-                    ui.set_diff_total_lines(total_count as i32);
                 });
             });
         });
     }
 
-    // Select diff file
+    // Reset to reflog entry
     {
         let git_client = git_client.clone();
+        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_select_diff_file(move |file_index| {
-            let Some(ui) = ui_weak.upgrade() else {
-                return;
-            };
-            let commit_hash = ui.get_selected_commit_hash().to_string();
-            if commit_hash.is_empty() {
+        let pending_confirm = pending_confirm.clone();
+        let confirm_bypass = confirm_bypass.clone();
+        ui.on_reset_to_reflog_entry(move |hash, mode, force| {
+            let client = git_client.borrow();
+            if !force && client.reset_drops_pushed(&hash) {
+                if let Some(ui) = ui_weak.upgrade() {
+                    ui.set_rewrite_hash(hash.clone());
+                    ui.set_rewrite_mode(mode.clone());
+                    show_rewrite_warning(
+                        &ui,
+                        "reflog-reset",
+                        "Commits that are already on a remote will be removed from this branch. You will need to force-push.",
+                    );
+                }
                 return;
             }
-            let client = git_client.borrow();
-            let (diff_lines, total_count) =
-                client.get_commit_file_diff(&commit_hash, file_index as usize);
-            ui.set_diff_lines(Rc::new(slint::VecModel::from(diff_lines)).into());
-            ui.set_diff_total_lines(total_count as i32);
+            if !force && mode == "hard" && !confirm_bypass.get() {
+                if let Some(ui) = ui_weak.upgrade() {
+                    if ui.get_confirm_hard_reset() {
+                        request_confirm(
+                            &ui,
+                            &pending_confirm,
+                            PendingConfirm::ResetToReflogEntry {
+                                hash: hash.to_string(),
+                                mode: mode.to_string(),
+                            },
+                            &format!("Hard reset to {}?", &hash[..7]),
+                            "Uncommitted changes in the working tree and index will be lost.",
+                            "Reset",
+                        );
+                        return;
+                    }
+                }
+            }
+            let result = client.reset_to_commit(&hash, &mode);
+            if let Some(ui) = ui_weak.upgrade() {
+                match result {
+                    Ok(()) => {
+                        ui.set_status_message(SharedString::from(format!(
+                            "Reset ({}) to {}",
+                            mode,
+                            &hash[..7]
+                        )));
+                        ui.set_reflog_entries(
+                            Rc::new(VecModel::from(client.get_reflog("HEAD"))).into(),
+                        );
+                        ui.set_reflog_selected_index(-1);
+                    }
+                    Err(e) => {
+                        ui.set_status_message(SharedString::from(format!("Reset error: {}", e)));
+                    }
+                }
+            }
+            drop(client);
+            refresh();
         });
     }
 
-    // Select file
+    // Bisect start
     {
         let git_client = git_client.clone();
+        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_select_file(move |filename, staged| {
-            let Some(ui) = ui_weak.upgrade() else {
-                return;
-            };
+        ui.on_bisect_start(move || {
             let client = git_client.borrow();
-            let (diff_lines, total_count) = client.get_file_diff(&filename, staged);
-            ui.set_diff_lines(Rc::new(slint::VecModel::from(diff_lines)).into());
-            ui.set_diff_total_lines(total_count as i32);
-            // Stage Hunk用にファイル情報を保存
-            ui.set_current_diff_filename(filename.clone());
-            ui.set_current_diff_is_staged(staged);
+            let result = client.bisect_start();
+            drop(client);
+            if let Some(ui) = ui_weak.upgrade() {
+                match result {
+                    Ok(()) => {
+                        ui.set_bisect_status("mark a bad and a good commit".into());
+                        ui.set_status_message("Bisect started".into());
+                    }
+                    Err(e) => ui.set_status_message(SharedString::from(e.to_string())),
+                }
+            }
+            refresh();
         });
     }
 
-    // Stage hunk
+    // Bisect good/bad（ハッシュが空ならHEADをマーク）
     {
         let git_client = git_client.clone();
         let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_stage_hunk(move |hunk_index| {
-            let Some(ui) = ui_weak.upgrade() else {
-                return;
-            };
-            let filename = ui.get_current_diff_filename().to_string();
-            if filename.is_empty() {
-                ui.set_status_message("No file selected".into());
-                return;
-            }
+        ui.on_bisect_mark(move |hash, good| {
             let client = git_client.borrow();
-            match client.stage_hunk(&filename, hunk_index as usize) {
-                Ok(()) => {
-                    ui.set_status_message(SharedString::from(format!(
-                        "Staged hunk {} of {}",
-                        hunk_index + 1,
-                        filename
-                    )));
-                    // Diffを更新
-                    let (diff_lines, total_count) = client.get_file_diff(&filename, false);
-                    ui.set_diff_lines(Rc::new(slint::VecModel::from(diff_lines)).into());
-                    ui.set_diff_total_lines(total_count as i32);
-                }
-                Err(e) => {
-                    ui.set_status_message(SharedString::from(format!("Stage hunk error: {}", e)));
+            let target = if hash.is_empty() {
+                "HEAD"
+            } else {
+                hash.as_str()
+            };
+            let result = if good {
+                client.bisect_mark_good(target)
+            } else {
+                client.bisect_mark_bad(target)
+            };
+            drop(client);
+            if let Some(ui) = ui_weak.upgrade() {
+                match result {
+                    Ok(progress) => {
+                        ui.set_bisect_status(progress.describe().into());
+                        if let BisectProgress::Found(hash) = &progress {
+                            ui.set_status_message(SharedString::from(format!(
+                                "Bisect finished: {} is the first bad commit",
+                                &hash[..7.min(hash.len())]
+                            )));
+                        }
+                    }
+                    Err(e) => ui.set_status_message(SharedString::from(e.to_string())),
                 }
             }
-            drop(client);
             refresh();
         });
     }
 
-    // Checkout remote branch
+    // Bisect reset
     {
         let git_client = git_client.clone();
         let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_checkout_remote_branch(move |name| {
+        ui.on_bisect_reset(move || {
             let client = git_client.borrow();
-            match client.checkout_remote_branch(&name) {
-                Ok(()) => {
-                    let local_name = name.split('/').skip(1).collect::<Vec<_>>().join("/");
-                    if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message(SharedString::from(format!(
-                            "Checked out {} from {}",
-                            local_name, name
-                        )));
-                    }
-                }
-                Err(e) => {
-                    if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message(SharedString::from(format!("Checkout error: {}", e)));
-                    }
+            let result = client.bisect_reset();
+            drop(client);
+            if let Some(ui) = ui_weak.upgrade() {
+                match result {
+                    Ok(()) => ui.set_status_message("Bisect reset".into()),
+                    Err(e) => ui.set_status_message(SharedString::from(e.to_string())),
                 }
             }
-            drop(client);
             refresh();
         });
     }
 
-    // Pull/Push/Fetch
+    // Revert commit
     {
         let git_client = git_client.clone();
         let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_pull(move || {
+        ui.on_revert_commit(move |index, no_commit| {
             let client = git_client.borrow();
-            match client.pull() {
-                Ok(()) => {
-                    if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message("Pull successful".into());
+            if let Some(hash) = client.get_commit_hash_by_index(index as usize) {
+                match client.revert_commit(&hash, no_commit) {
+                    Ok(message) => {
+                        if let Some(ui) = ui_weak.upgrade() {
+                            if no_commit {
+                                // 続けてリバートした場合は最初のメッセージを残す
+                                if ui.get_commit_subject().is_empty() {
+                                    set_commit_message_ui(&ui, &message);
+                                }
+                                ui.set_status_message(SharedString::from(format!(
+                                    "Reverted {} (changes staged, not committed)",
+                                    &hash[..7]
+                                )));
+                            } else {
+                                ui.set_status_message(SharedString::from(format!(
+                                    "Reverted {}",
+                                    &hash[..7]
+                                )));
+                            }
+                        }
                     }
-                    drop(client);
-                    refresh();
-                }
-                Err(e) => {
-                    if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message(SharedString::from(format!("Pull error: {}", e)));
+                    Err(e) => {
+                        if let Some(ui) = ui_weak.upgrade() {
+                            ui.set_status_message(SharedString::from(format!(
+                                "Revert error: {}",
+                                e
+                            )));
+                        }
                     }
-                    drop(client);
-                    refresh();
                 }
             }
+            drop(client);
+            refresh();
         });
     }
+
+    // fixup! / squash! コミット（ステージ済みの変更を選択したコミット宛てにコミット）
     {
         let git_client = git_client.clone();
         let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_push(move || {
+        ui.on_commit_autosquash(move |index, kind| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
             let client = git_client.borrow();
-            match client.push() {
+            let Some(hash) = client.get_commit_hash_by_index(index as usize) else {
+                return;
+            };
+            let result = if kind == "squash" {
+                // コミット欄のメッセージをsquash時に追加する本文として使う
+                let message =
+                    assemble_commit_message(&ui.get_commit_subject(), &ui.get_commit_body());
+                client.commit_squash(&hash, &message)
+            } else {
+                client.commit_fixup(&hash)
+            };
+            drop(client);
+            match result {
                 Ok(()) => {
-                    if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message("Push successful".into());
+                    if kind == "squash" {
+                        set_commit_message_ui(&ui, "");
                     }
-                    drop(client);
-                    refresh();
+                    ui.set_status_message(SharedString::from(format!(
+                        "Created {}! commit for {}",
+                        kind,
+                        &hash[..7]
+                    )));
                 }
                 Err(e) => {
-                    if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message(SharedString::from(format!("Push error: {}", e)));
-                    }
-                    drop(client);
-                    refresh();
+                    ui.set_status_message(SharedString::from(format!("Commit error: {}", e)));
                 }
             }
+            refresh();
         });
     }
 
-    // Copy commit hash to clipboard
+    // rebase -i --autosquash（選択したコミット以降のfixup! / squash! を取り込む）
     {
+        let git_client = git_client.clone();
+        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_copy_commit_hash(move |hash| {
-            let hash_str = hash.to_string();
-            copy_to_clipboard_async(hash_str.clone());
-            if let Some(ui) = ui_weak.upgrade() {
-                ui.set_status_message(SharedString::from(format!(
-                    "Copied: {}",
-                    &hash_str[..7.min(hash_str.len())]
-                )));
+        ui.on_rebase_autosquash(move |hash, force| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            // Uncommitted行はハッシュが空
+            if hash.is_empty() {
+                return;
+            }
+            let client = git_client.borrow();
+            if !force && client.is_pushed(&hash) {
+                ui.set_rewrite_hash(hash.clone());
+                show_rewrite_warning(
+                    &ui,
+                    "autosquash",
+                    "Commits that are already on a remote will be rewritten. You will need to force-push.",
+                );
+                return;
             }
+            let result = client.rebase_autosquash(&hash);
+            drop(client);
+            match result {
+                Ok(()) => ui.set_status_message(SharedString::from(format!(
+                    "Autosquashed commits since {}",
+                    &hash[..7]
+                ))),
+                Err(e) => ui.set_status_message(e.to_string().into()),
+            }
+            refresh();
         });
     }
 
-    // Copy commit message to clipboard
+    // Revert the selected range (Shift+Click で選択した範囲)
     {
+        let git_client = git_client.clone();
+        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_copy_commit_message(move |message| {
-            let message_str = message.to_string();
-            copy_to_clipboard_async(message_str.clone());
-            if let Some(ui) = ui_weak.upgrade() {
-                // 長いメッセージは省略して表示（文字数ベースで安全にスライス）
-                let display_msg: String = if message_str.chars().count() > 30 {
-                    format!("{}...", message_str.chars().take(30).collect::<String>())
-                } else {
-                    message_str
-                };
-                ui.set_status_message(SharedString::from(format!("Copied: {}", display_msg)));
+        ui.on_revert_range(move |combine| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let from = ui.get_compare_from_hash().to_string();
+            let to = ui.get_selected_commit_hash().to_string();
+            if from.is_empty() || to.is_empty() {
+                return;
+            }
+            let client = git_client.borrow();
+            let result = client
+                .commits_in_range(&from, &to)
+                .and_then(|hashes| client.revert_commits(&hashes, combine));
+            match result {
+                Ok(count) => {
+                    ui.set_status_message(SharedString::from(format!(
+                        "Reverted {} commit(s){}",
+                        count,
+                        if combine { " in one commit" } else { "" }
+                    )));
+                }
+                Err(e) => {
+                    ui.set_status_message(SharedString::from(format!("Revert error: {}", e)));
+                }
             }
+            drop(client);
+            refresh();
         });
     }
 
-    // Reset to commit
+    // Open file in the default application
     {
         let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_reset_to_commit(move |index, mode| {
-            let client = git_client.borrow();
-            if let Some(hash) = client.get_commit_hash_by_index(index as usize) {
-                match client.reset_to_commit(&hash, &mode) {
+        ui.on_open_file_in_editor(move |filename| {
+            let path = git_client.borrow().resolve_workdir_path(&filename);
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            match path {
+                Some(path) if path.is_file() => match open::that(&path) {
                     Ok(()) => {
-                        if let Some(ui) = ui_weak.upgrade() {
-                            ui.set_status_message(SharedString::from(format!(
-                                "Reset ({}) to {}",
-                                mode,
-                                &hash[..7]
-                            )));
-                        }
-                    }
-                    Err(e) => {
-                        if let Some(ui) = ui_weak.upgrade() {
-                            ui.set_status_message(SharedString::from(format!(
-                                "Reset error: {}",
-                                e
-                            )));
-                        }
+                        ui.set_status_message(SharedString::from(format!("Opening {}", filename)))
                     }
-                }
+                    Err(e) => ui.set_status_message(SharedString::from(format!(
+                        "Failed to open {}: {}",
+                        filename, e
+                    ))),
+                },
+                _ => ui.set_status_message(SharedString::from(format!(
+                    "File no longer exists: {}",
+                    filename
+                ))),
             }
-            drop(client);
-            refresh();
         });
     }
 
-    // Revert commit
+    // Reveal file in the file manager
     {
         let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
         let ui_weak = ui.as_weak();
-        ui.on_revert_commit(move |index| {
-            let client = git_client.borrow();
-            if let Some(hash) = client.get_commit_hash_by_index(index as usize) {
-                match client.revert_commit(&hash) {
-                    Ok(()) => {
-                        if let Some(ui) = ui_weak.upgrade() {
-                            ui.set_status_message(SharedString::from(format!(
-                                "Reverted {}",
-                                &hash[..7]
-                            )));
-                        }
-                    }
-                    Err(e) => {
-                        if let Some(ui) = ui_weak.upgrade() {
-                            ui.set_status_message(SharedString::from(format!(
-                                "Revert error: {}",
-                                e
-                            )));
-                        }
-                    }
-                }
+        ui.on_reveal_file(move |filename| {
+            let path = git_client.borrow().resolve_workdir_path(&filename);
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let Some(path) = path else {
+                ui.set_status_message("No repository".into());
+                return;
+            };
+            let missing = !path.exists();
+            match reveal_in_file_manager(&path) {
+                Ok(()) if missing => ui.set_status_message(SharedString::from(format!(
+                    "File no longer exists, opened its folder: {}",
+                    filename
+                ))),
+                Ok(()) => {}
+                Err(e) => ui.set_status_message(SharedString::from(format!(
+                    "Failed to reveal {}: {}",
+                    filename, e
+                ))),
             }
-            drop(client);
-            refresh();
         });
     }
 
@@ -3235,6 +4035,69 @@ fn main() -> Result<(), slint::PlatformError> {
         });
     }
 
+    // Export a commit as a patch file
+    {
+        let git_client = git_client.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_export_patch(move |hash, subject| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let patch = match git_client.borrow().export_commit_patch(&hash) {
+                Ok(patch) => patch,
+                Err(e) => {
+                    ui.set_status_message(SharedString::from(format!("Export error: {}", e)));
+                    return;
+                }
+            };
+            let Some(path) = rfd::FileDialog::new()
+                .set_title("Save Patch")
+                .set_file_name(patch_file_name(1, &subject))
+                .add_filter("Patch", &["patch", "diff"])
+                .save_file()
+            else {
+                return;
+            };
+            match std::fs::write(&path, patch) {
+                Ok(()) => ui.set_status_message(SharedString::from(format!(
+                    "Saved patch to {}",
+                    path.display()
+                ))),
+                Err(e) => ui.set_status_message(SharedString::from(format!("Export error: {}", e))),
+            }
+        });
+    }
+
+    // Export the selected range as patch files (Shift+Click で選択した範囲)
+    {
+        let git_client = git_client.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_export_patch_range(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let from = ui.get_compare_from_hash().to_string();
+            let to = ui.get_selected_commit_hash().to_string();
+            if from.is_empty() || to.is_empty() {
+                return;
+            }
+            let Some(dir) = rfd::FileDialog::new()
+                .set_title("Select Folder for Patches")
+                .pick_folder()
+            else {
+                return;
+            };
+            match git_client.borrow().export_range_patches(&from, &to, &dir) {
+                Ok(count) => ui.set_status_message(SharedString::from(format!(
+                    "Saved {} patch file(s) to {}",
+                    count,
+                    dir.display()
+                ))),
+                Err(e) => ui.set_status_message(SharedString::from(format!("Export error: {}", e))),
+            }
+        });
+    }
+
     // Copy branch name to clipboard
     {
         let ui_weak = ui.as_weak();
@@ -3247,6 +4110,39 @@ fn main() -> Result<(), slint::PlatformError> {
         });
     }
 
+    // Set / unset upstream (空のupstreamは解除)
+    {
+        let git_client = git_client.clone();
+        let refresh = refresh_branches.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_set_upstream(move |branch_name, upstream| {
+            let upstream = upstream.trim().to_string();
+            let client = git_client.borrow();
+            let result = if upstream.is_empty() {
+                client.unset_upstream(&branch_name)
+            } else {
+                client.set_upstream(&branch_name, &upstream)
+            };
+            drop(client);
+            if let Some(ui) = ui_weak.upgrade() {
+                match result {
+                    Ok(()) if upstream.is_empty() => ui.set_status_message(SharedString::from(
+                        format!("Unset upstream of {}", branch_name),
+                    )),
+                    Ok(()) => ui.set_status_message(SharedString::from(format!(
+                        "{} now tracks {}",
+                        branch_name, upstream
+                    ))),
+                    Err(e) => ui.set_status_message(SharedString::from(format!(
+                        "Set upstream error: {}",
+                        e
+                    ))),
+                }
+            }
+            refresh();
+        });
+    }
+
     // Create Pull Request (open in browser)
     {
         let git_client = git_client.clone();
@@ -3255,11 +4151,18 @@ fn main() -> Result<(), slint::PlatformError> {
             let client = git_client.borrow();
             if let Some(pr_url) = client.get_pull_request_url(&branch_name) {
                 if open::that(&pr_url).is_ok() {
+                    // 比較URLでは本文を埋められないプロバイダもあるため、テンプレートはクリップボードへ
+                    let template = client.get_pull_request_template();
+                    let copied = template.is_some();
+                    if let Some(template) = template {
+                        copy_to_clipboard_async(template);
+                    }
                     if let Some(ui) = ui_weak.upgrade() {
-                        ui.set_status_message(SharedString::from(format!(
-                            "Opening PR for {}",
-                            branch_name
-                        )));
+                        ui.set_status_message(SharedString::from(if copied {
+                            format!("Opening PR for {} (PR template copied)", branch_name)
+                        } else {
+                            format!("Opening PR for {}", branch_name)
+                        }));
                     }
                 } else {
                     if let Some(ui) = ui_weak.upgrade() {
@@ -3274,6 +4177,164 @@ fn main() -> Result<(), slint::PlatformError> {
         });
     }
 
+    // Load commit template (コミットモード開始時、メッセージが空なら挿入)
+    {
+        let git_client = git_client.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_load_commit_template(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            if !ui.get_commit_subject().is_empty() || !ui.get_commit_body().is_empty() {
+                return;
+            }
+            if let Some(template) = git_client.borrow().get_commit_template() {
+                set_commit_message_ui(&ui, &template);
+            }
+        });
+    }
+
+    // Set git identity (user.name / user.email)
+    {
+        let git_client = git_client.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_save_identity(move |name, email| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            match git_client.borrow().set_identity(&name, &email) {
+                Ok(()) => {
+                    ui.set_show_identity_dialog(false);
+                    ui.set_status_message(SharedString::from(format!(
+                        "Git identity set: {} <{}>",
+                        name.trim(),
+                        email.trim()
+                    )));
+                }
+                Err(e) => {
+                    ui.set_status_message(SharedString::from(format!(
+                        "Failed to set identity: {}",
+                        e
+                    )));
+                }
+            }
+        });
+    }
+
+    // Save settings
+    {
+        let git_client = git_client.clone();
+        let ui_weak = ui.as_weak();
+        let schedule_auto_fetch = schedule_auto_fetch.clone();
+        ui.on_save_settings(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let mut settings = load_settings();
+            settings.insert(
+                "commit_message_pattern".into(),
+                ui.get_commit_message_pattern().to_string().into(),
+            );
+            settings.insert(
+                "ticket_pattern".into(),
+                ui.get_ticket_pattern().to_string().into(),
+            );
+            settings.insert("run_commit_hooks".into(), ui.get_run_commit_hooks().into());
+            settings.insert("use_embedded_git".into(), ui.get_use_embedded_git().into());
+            settings.insert(
+                "stage_checked_on_commit".into(),
+                ui.get_stage_checked_on_commit().into(),
+            );
+            settings.insert("show_whitespace".into(), ui.get_show_whitespace().into());
+            settings.insert(
+                "auto_fetch_minutes".into(),
+                ui.get_auto_fetch_interval().into(),
+            );
+            save_settings(&settings);
+            git_client
+                .borrow_mut()
+                .set_run_commit_hooks(ui.get_run_commit_hooks() && ui.get_git_available());
+            git_client
+                .borrow_mut()
+                .set_remote_transport(remote_transport(ui.get_use_embedded_git()));
+            schedule_auto_fetch(ui.get_auto_fetch_interval());
+            ui.set_branch_ticket(
+                extract_ticket_id(&ui.get_current_branch(), &ui.get_ticket_pattern())
+                    .unwrap_or_default()
+                    .into(),
+            );
+            ui.set_status_message("Settings saved".into());
+        });
+    }
+
+    // Set theme (dark / light / system)
+    {
+        ui.on_set_theme(move |theme| {
+            let mut settings = load_settings();
+            settings.insert("theme".into(), theme.to_string().into());
+            save_settings(&settings);
+        });
+    }
+
+    // Confirm preference（設定ダイアログから「確認する」を切り替え）
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_set_confirm_preference(move |kind, enabled| {
+            let Some(kind) = ConfirmKind::parse(&kind) else {
+                return;
+            };
+            if let Some(ui) = ui_weak.upgrade() {
+                set_confirm_preference_ui(&ui, kind, enabled);
+            }
+            let mut settings = load_settings();
+            settings.insert(kind.setting_key().into(), enabled.into());
+            save_settings(&settings);
+        });
+    }
+
+    // Confirm action（確認ダイアログで実行 → 保留中の操作を確認なしで再実行）
+    {
+        let ui_weak = ui.as_weak();
+        let pending_confirm = pending_confirm.clone();
+        let confirm_bypass = confirm_bypass.clone();
+        ui.on_confirm_action_accepted(move |dont_ask| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            ui.set_show_confirm_action(false);
+            let Some(action) = pending_confirm.borrow_mut().take() else {
+                return;
+            };
+            if dont_ask {
+                ui.invoke_set_confirm_preference(action.kind().as_str().into(), false);
+            }
+            confirm_bypass.set(true);
+            match action {
+                PendingConfirm::DiscardFile(filename) => ui.invoke_discard_file(filename.into()),
+                PendingConfirm::DiscardSelected => ui.invoke_discard_selected(),
+                PendingConfirm::DiscardFolder(folder) => ui.invoke_discard_folder(folder.into()),
+                PendingConfirm::ResetToCommit { hash, mode } => {
+                    ui.invoke_reset_to_commit(hash.into(), mode.into(), false)
+                }
+                PendingConfirm::ResetToReflogEntry { hash, mode } => {
+                    ui.invoke_reset_to_reflog_entry(hash.into(), mode.into(), false)
+                }
+                PendingConfirm::ForceDelete(name) => ui.invoke_delete_branch(name.into(), true),
+                PendingConfirm::StashDrop(index) => ui.invoke_stash_drop(index),
+            }
+            confirm_bypass.set(false);
+        });
+    }
+
+    // Set pull mode (merge / ff-only / rebase)
+    {
+        ui.on_set_pull_mode(move |mode| {
+            let mut settings = load_settings();
+            settings.insert("pull_mode".into(), mode.to_string().into());
+            save_settings(&settings);
+        });
+    }
+
     // Navigate commit message history (keyboard up/down)
     {
         let history = commit_message_history.clone();
@@ -3309,9 +4370,9 @@ fn main() -> Result<(), slint::PlatformError> {
 
             ui.set_commit_history_index(new_index);
             if new_index >= 0 && (new_index as usize) < hist.len() {
-                ui.set_commit_message(SharedString::from(hist[new_index as usize].as_str()));
+                ui.set_commit_subject(SharedString::from(hist[new_index as usize].as_str()));
             } else {
-                ui.set_commit_message("".into());
+                ui.set_commit_subject("".into());
             }
         });
     }
@@ -3319,7 +4380,8 @@ fn main() -> Result<(), slint::PlatformError> {
     // Stash operations
     {
         let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
+        let refresh_status = refresh_status.clone();
+        let refresh_stashes = refresh_stashes.clone();
         let ui_weak = ui.as_weak();
         ui.on_stash_save(move |message, include_untracked| {
             let mut client = git_client.borrow_mut();
@@ -3339,22 +4401,32 @@ fn main() -> Result<(), slint::PlatformError> {
                 }
             }
             drop(client);
-            refresh();
+            refresh_status();
+            refresh_stashes();
         });
     }
     {
         let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
+        let refresh_status = refresh_status.clone();
+        let refresh_stashes = refresh_stashes.clone();
         let ui_weak = ui.as_weak();
         ui.on_stash_apply(move |index| {
             let mut client = git_client.borrow_mut();
             // applyは競合する可能性があるため、エラー時はリロードして状態を更新
             match client.stash_apply(index as usize) {
-                Ok(_) => {
+                Ok(StashApplyOutcome::Clean) => {
                     if let Some(ui) = ui_weak.upgrade() {
                         ui.set_status_message("Stash applied".into());
                     }
                 }
+                Ok(StashApplyOutcome::Conflicts(files)) => {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_message(SharedString::from(format!(
+                            "Stash applied with conflicts in {}; resolve them",
+                            files.join(", ")
+                        )));
+                    }
+                }
                 Err(e) => {
                     if let Some(ui) = ui_weak.upgrade() {
                         ui.set_status_message(SharedString::from(format!(
@@ -3365,21 +4437,31 @@ fn main() -> Result<(), slint::PlatformError> {
                 }
             }
             drop(client);
-            refresh();
+            refresh_status();
+            refresh_stashes();
         });
     }
     {
         let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
+        let refresh_status = refresh_status.clone();
+        let refresh_stashes = refresh_stashes.clone();
         let ui_weak = ui.as_weak();
         ui.on_stash_pop(move |index| {
             let mut client = git_client.borrow_mut();
             match client.stash_pop(index as usize) {
-                Ok(_) => {
+                Ok(StashApplyOutcome::Clean) => {
                     if let Some(ui) = ui_weak.upgrade() {
                         ui.set_status_message("Stash popped".into());
                     }
                 }
+                Ok(StashApplyOutcome::Conflicts(files)) => {
+                    if let Some(ui) = ui_weak.upgrade() {
+                        ui.set_status_message(SharedString::from(format!(
+                            "Stash applied with conflicts in {}; resolve them (stash kept)",
+                            files.join(", ")
+                        )));
+                    }
+                }
                 Err(e) => {
                     if let Some(ui) = ui_weak.upgrade() {
                         ui.set_status_message(SharedString::from(format!(
@@ -3390,14 +4472,32 @@ fn main() -> Result<(), slint::PlatformError> {
                 }
             }
             drop(client);
-            refresh();
+            refresh_status();
+            refresh_stashes();
         });
     }
     {
         let git_client = git_client.clone();
-        let refresh = refresh_ui.clone();
+        let refresh_stashes = refresh_stashes.clone();
         let ui_weak = ui.as_weak();
+        let pending_confirm = pending_confirm.clone();
+        let confirm_bypass = confirm_bypass.clone();
         ui.on_stash_drop(move |index| {
+            if !confirm_bypass.get() {
+                if let Some(ui) = ui_weak.upgrade() {
+                    if ui.get_confirm_stash_drop() {
+                        request_confirm(
+                            &ui,
+                            &pending_confirm,
+                            PendingConfirm::StashDrop(index),
+                            &format!("Drop stash@{{{}}}?", index),
+                            "The stashed changes will be deleted.",
+                            "Drop",
+                        );
+                        return;
+                    }
+                }
+            }
             let mut client = git_client.borrow_mut();
             match client.stash_drop(index as usize) {
                 Ok(_) => {
@@ -3415,7 +4515,44 @@ fn main() -> Result<(), slint::PlatformError> {
                 }
             }
             drop(client);
-            refresh();
+            refresh_stashes();
+        });
+    }
+
+    // Stash rename
+    {
+        let ui_weak = ui.as_weak();
+        ui.on_begin_stash_rename(move |index| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let Some(stash) = ui.get_stashes().iter().find(|s| s.index == index) else {
+                return;
+            };
+            let (_, message) = split_stash_message(&stash.message);
+            ui.set_rename_stash_index(index);
+            ui.set_rename_stash_message(message.into());
+            ui.set_show_rename_stash(true);
+        });
+    }
+    {
+        let git_client = git_client.clone();
+        let refresh_stashes = refresh_stashes.clone();
+        let ui_weak = ui.as_weak();
+        ui.on_stash_rename(move |index, message| {
+            let mut client = git_client.borrow_mut();
+            let result = client.stash_rename(index as usize, &message);
+            drop(client);
+            if let Some(ui) = ui_weak.upgrade() {
+                match result {
+                    Ok(()) => ui.set_status_message("Stash renamed".into()),
+                    Err(e) => ui.set_status_message(SharedString::from(format!(
+                        "Stash rename error: {}",
+                        e
+                    ))),
+                }
+            }
+            refresh_stashes();
         });
     }
 
@@ -3423,6 +4560,8 @@ fn main() -> Result<(), slint::PlatformError> {
     if let Some(repo_path) = initial_repo {
         let mut client = git_client.borrow_mut();
         if client.open_repo(&repo_path).is_ok() {
+            let repo_path = client.get_repo_path().unwrap_or(repo_path);
+            apply_repo_state(&ui, &mut client, &repo_path);
             drop(client);
 
             // UIにリポジトリ名を設定
@@ -3439,3 +4578,133 @@ fn main() -> Result<(), slint::PlatformError> {
 
     ui.run()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+
+    /// テスト用の一時ディレクトリ（テストが失敗しても残らないよう、drop時に削除する）
+    pub(crate) struct TestDir(std::path::PathBuf);
+
+    impl TestDir {
+        pub(crate) fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("rust-git-gui-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            TestDir(dir)
+        }
+    }
+
+    impl std::ops::Deref for TestDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TestDir {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// 一時ディレクトリに空コミット1つだけのリポジトリを作る
+    /// コミットを作るテストのため、グローバル設定がない環境でも使える作者情報を設定しておく
+    pub(crate) fn init_test_repo(name: &str) -> (TestDir, Repository, Oid) {
+        let dir = TestDir::new(name);
+        let repo = Repository::init(&dir).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        drop(config);
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let oid = repo
+            .commit(Some("HEAD"), &sig, &sig, "initial", &tree, &[])
+            .unwrap();
+        drop(tree);
+        (dir, repo, oid)
+    }
+
+    #[test]
+    fn ticket_id_is_extracted_from_branch_name() {
+        assert_eq!(
+            extract_ticket_id("feature/ABC-123-login", DEFAULT_TICKET_PATTERN).as_deref(),
+            Some("ABC-123")
+        );
+        assert_eq!(extract_ticket_id("main", DEFAULT_TICKET_PATTERN), None);
+        // パターンが空や不正なら抽出しない
+        assert_eq!(extract_ticket_id("ABC-123", ""), None);
+        assert_eq!(extract_ticket_id("ABC-123", "("), None);
+        assert_eq!(
+            extract_ticket_id("fix/42-typo", r"[0-9]+").as_deref(),
+            Some("42")
+        );
+
+        assert_eq!(
+            prepend_ticket_id("Fix login", "ABC-123"),
+            "ABC-123 Fix login"
+        );
+        assert_eq!(prepend_ticket_id("", "ABC-123"), "ABC-123");
+        assert_eq!(prepend_ticket_id("ABC-123 Fix", "ABC-123"), "ABC-123 Fix");
+    }
+
+    #[test]
+    fn commit_message_joins_subject_and_body_with_blank_line() {
+        assert_eq!(assemble_commit_message(" Fix bug ", ""), "Fix bug");
+        assert_eq!(assemble_commit_message("Fix bug", "\n\n  "), "Fix bug");
+        assert_eq!(
+            assemble_commit_message("Fix bug", "\nDetails\n\nMore\n"),
+            "Fix bug\n\nDetails\n\nMore"
+        );
+        // 分割して組み立て直すと元に戻る
+        let (subject, body) = split_commit_message("Fix bug\n\nDetails\nMore");
+        assert_eq!(
+            (subject.as_str(), body.as_str()),
+            ("Fix bug", "Details\nMore")
+        );
+        assert_eq!(
+            assemble_commit_message(&subject, &body),
+            "Fix bug\n\nDetails\nMore"
+        );
+        assert_eq!(first_long_body_line("short\n"), 0);
+        assert_eq!(first_long_body_line(&format!("ok\n{}", "x".repeat(73))), 2);
+    }
+
+    #[test]
+    fn fetched_ago_uses_largest_whole_unit() {
+        assert_eq!(format_fetched_ago(30), "Fetched just now");
+        assert_eq!(format_fetched_ago(5 * 60 + 59), "Fetched 5m ago");
+        assert_eq!(format_fetched_ago(2 * 3600), "Fetched 2h ago");
+        assert_eq!(format_fetched_ago(3 * 86400 + 10), "Fetched 3d ago");
+        // 時計が巻き戻った場合も負の値は出さない
+        assert_eq!(format_fetched_ago(-120), "Fetched just now");
+    }
+
+    #[test]
+    fn confirm_preferences_default_to_enabled() {
+        let mut settings = serde_json::Map::new();
+        settings.insert("confirm_stash_drop".into(), false.into());
+        assert!(!confirm_enabled(&settings, ConfirmKind::StashDrop));
+        // 未設定の操作は確認する
+        for kind in [
+            ConfirmKind::Discard,
+            ConfirmKind::HardReset,
+            ConfirmKind::ForceDelete,
+        ] {
+            assert!(confirm_enabled(&settings, kind));
+            assert_eq!(ConfirmKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(ConfirmKind::parse("unknown"), None);
+    }
+}
@@ -0,0 +1,447 @@
+//! 設定ファイルへの保存と読み込み（最近使用したリポジトリ、アプリ設定、リポジトリごとの状態）
+
+use chrono::Local;
+use std::fs;
+use std::path::Path;
+
+// ========== リポジトリ履歴管理 ==========
+
+const MAX_RECENT_REPOS: usize = 10;
+
+fn get_config_path() -> std::path::PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| std::path::PathBuf::from("."))
+        .join("git-client")
+        .join("recent_repos.json")
+}
+
+fn get_commit_history_path() -> std::path::PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| std::path::PathBuf::from("."))
+        .join("git-client")
+        .join("commit_history.json")
+}
+
+pub fn load_commit_history() -> Vec<String> {
+    let path = get_commit_history_path();
+    if let Ok(content) = fs::read_to_string(&path) {
+        serde_json::from_str(&content).unwrap_or_default()
+    } else {
+        Vec::new()
+    }
+}
+
+pub fn save_commit_history(history: &[String]) {
+    let path = get_commit_history_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(history) {
+        let _ = fs::write(&path, json);
+    }
+}
+
+// ========== リポジトリごとのUI状態 ==========
+
+fn get_repo_state_path() -> std::path::PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| std::path::PathBuf::from("."))
+        .join("git-client")
+        .join("repo_state.json")
+}
+
+/// 指定リポジトリのUI状態を読み込む（リポジトリパス → キー → 値）
+pub fn load_repo_state(repo_path: &str) -> serde_json::Map<String, serde_json::Value> {
+    let path = get_repo_state_path();
+    let Ok(content) = fs::read_to_string(&path) else {
+        return serde_json::Map::new();
+    };
+    let mut all: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(&content).unwrap_or_default();
+    match all.remove(repo_path) {
+        Some(serde_json::Value::Object(state)) => state,
+        _ => serde_json::Map::new(),
+    }
+}
+
+/// 指定リポジトリのUI状態を1項目更新して保存
+pub fn save_repo_state_value(repo_path: &str, key: &str, value: serde_json::Value) {
+    let path = get_repo_state_path();
+    let mut all: serde_json::Map<String, serde_json::Value> = fs::read_to_string(&path)
+        .ok()
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default();
+    let entry = all
+        .entry(repo_path.to_string())
+        .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+    if let serde_json::Value::Object(state) = entry {
+        state.insert(key.to_string(), value);
+    }
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(&all) {
+        let _ = fs::write(&path, json);
+    }
+}
+
+/// fetchに成功した時刻をリポジトリの状態として記録（再起動後も保持）
+pub fn record_fetch_time(repo_path: &str) {
+    save_repo_state_value(repo_path, "last_fetched", Local::now().timestamp().into());
+}
+
+fn get_settings_path() -> std::path::PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| std::path::PathBuf::from("."))
+        .join("git-client")
+        .join("settings.json")
+}
+
+/// アプリ設定を読み込む（キー → 値のJSONオブジェクト）
+pub fn load_settings() -> serde_json::Map<String, serde_json::Value> {
+    let path = get_settings_path();
+    if let Ok(content) = fs::read_to_string(&path) {
+        serde_json::from_str(&content).unwrap_or_default()
+    } else {
+        serde_json::Map::new()
+    }
+}
+
+pub fn save_settings(settings: &serde_json::Map<String, serde_json::Value>) {
+    let path = get_settings_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(settings) {
+        let _ = fs::write(&path, json);
+    }
+}
+
+pub fn setting_str(settings: &serde_json::Map<String, serde_json::Value>, key: &str) -> String {
+    settings
+        .get(key)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// 保存するウィンドウの位置とサイズ（物理ピクセル。最大化状態は "window_maximized" に別途保存）
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl WindowGeometry {
+    pub fn from_settings(settings: &serde_json::Map<String, serde_json::Value>) -> Option<Self> {
+        let obj = settings.get("window_geometry")?.as_object()?;
+        let int = |key: &str| obj.get(key).and_then(|v| v.as_i64());
+        let geometry = WindowGeometry {
+            x: int("x")?.try_into().ok()?,
+            y: int("y")?.try_into().ok()?,
+            width: int("width")?.try_into().ok()?,
+            height: int("height")?.try_into().ok()?,
+        };
+        (geometry.width > 0 && geometry.height > 0).then_some(geometry)
+    }
+
+    fn to_json(self) -> serde_json::Value {
+        serde_json::json!({
+            "x": self.x,
+            "y": self.y,
+            "width": self.width,
+            "height": self.height,
+        })
+    }
+}
+
+/// 保存された位置がどのモニターにも掛かっていなければ（モニター構成の変更など）
+/// 最初のモニターの中央に収まるよう位置とサイズを補正する
+/// monitorsは (x, y, width, height)
+pub fn clamp_window_geometry(
+    geometry: WindowGeometry,
+    monitors: &[(i32, i32, u32, u32)],
+) -> WindowGeometry {
+    // タイトルバー付近が見えていればドラッグで戻せるので画面内とみなす
+    const VISIBLE_MARGIN: i64 = 64;
+    let (x, y) = (geometry.x as i64, geometry.y as i64);
+    let on_screen = monitors.iter().any(|&(mx, my, mw, mh)| {
+        let (mx, my) = (mx as i64, my as i64);
+        x + geometry.width as i64 > mx + VISIBLE_MARGIN
+            && x < mx + mw as i64 - VISIBLE_MARGIN
+            && y >= my
+            && y < my + mh as i64 - VISIBLE_MARGIN
+    });
+    let Some(&(mx, my, mw, mh)) = monitors.first() else {
+        return geometry;
+    };
+    if on_screen {
+        return geometry;
+    }
+    let width = geometry.width.min(mw);
+    let height = geometry.height.min(mh);
+    WindowGeometry {
+        x: mx + ((mw - width) / 2) as i32,
+        y: my + ((mh - height) / 2) as i32,
+        width,
+        height,
+    }
+}
+
+/// 終了時のウィンドウ位置・サイズを設定に保存する
+/// 最大化・最小化中は通常時の値が取れないため、前回保存した位置・サイズを残す
+pub fn save_window_geometry(window: &slint::Window) {
+    let mut settings = load_settings();
+    let maximized = window.is_maximized();
+    settings.insert("window_maximized".into(), maximized.into());
+    if !maximized && !window.is_minimized() {
+        let position = window.position();
+        let size = window.size();
+        let geometry = WindowGeometry {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+        };
+        settings.insert("window_geometry".into(), geometry.to_json());
+    }
+    save_settings(&settings);
+}
+
+/// 最近使用したリポジトリ（ファイル上は使用順。ピン留めしたものは上限で削除されない）
+#[derive(Clone)]
+pub struct RecentRepo {
+    pub path: String,
+    pub pinned: bool,
+    /// 最後に開いた時刻（UNIX秒。旧形式から移行したものは0）
+    pub last_opened: i64,
+}
+
+pub fn load_recent_repos() -> Vec<RecentRepo> {
+    let path = get_config_path();
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    let values: Vec<serde_json::Value> = serde_json::from_str(&content).unwrap_or_default();
+
+    // 旧形式（パス文字列の配列）は読み込み時に新形式へ移行する
+    let is_legacy = values.iter().any(|v| v.is_string());
+    let repos: Vec<RecentRepo> = values
+        .into_iter()
+        .filter_map(|v| match v {
+            serde_json::Value::String(path) => Some(RecentRepo {
+                path,
+                pinned: false,
+                last_opened: 0,
+            }),
+            serde_json::Value::Object(obj) => Some(RecentRepo {
+                path: obj.get("path")?.as_str()?.to_string(),
+                pinned: obj.get("pinned").and_then(|p| p.as_bool()).unwrap_or(false),
+                last_opened: obj.get("last_opened").and_then(|t| t.as_i64()).unwrap_or(0),
+            }),
+            _ => None,
+        })
+        .collect();
+    if is_legacy {
+        save_recent_repos(&repos);
+    }
+    repos
+}
+
+fn save_recent_repos(repos: &[RecentRepo]) {
+    let path = get_config_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let values: Vec<serde_json::Value> = repos
+        .iter()
+        .map(|r| {
+            serde_json::json!({ "path": r.path, "pinned": r.pinned, "last_opened": r.last_opened })
+        })
+        .collect();
+    if let Ok(json) = serde_json::to_string_pretty(&values) {
+        let _ = fs::write(&path, json);
+    }
+}
+
+pub fn add_recent_repo(path: &str) -> Vec<RecentRepo> {
+    let mut repos = load_recent_repos();
+    // 既存のエントリを削除（ピン留め状態は引き継ぐ）
+    let pinned = repos.iter().any(|r| r.path == path && r.pinned);
+    repos.retain(|r| r.path != path);
+    // 先頭に追加
+    repos.insert(
+        0,
+        RecentRepo {
+            path: path.to_string(),
+            pinned,
+            last_opened: Local::now().timestamp(),
+        },
+    );
+    // ピン留めされていないものが最大数を超えたら削除
+    let mut unpinned = 0;
+    repos.retain(|r| {
+        if r.pinned {
+            return true;
+        }
+        unpinned += 1;
+        unpinned <= MAX_RECENT_REPOS
+    });
+    save_recent_repos(&repos);
+    repos
+}
+
+/// 最近使用したリポジトリの並び順（サイドバーのコンボボックスのindexに対応）
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RecentRepoSort {
+    /// 使用順（ファイル上の順序そのまま）
+    Manual,
+    /// フォルダ名の昇順
+    Name,
+    /// 最後に開いた時刻の新しい順
+    LastOpened,
+}
+
+impl RecentRepoSort {
+    pub fn from_index(index: i32) -> Self {
+        match index {
+            1 => RecentRepoSort::Name,
+            2 => RecentRepoSort::LastOpened,
+            _ => RecentRepoSort::Manual,
+        }
+    }
+}
+
+/// 表示順（ピン留めを先頭に、それぞれ指定の並び順）に並べ替え、
+/// パスに検索文字列（大文字小文字を区別しない）を含むものだけに絞り込む
+pub fn recent_repos_display_order(
+    repos: &[RecentRepo],
+    sort: RecentRepoSort,
+    filter: &str,
+) -> Vec<RecentRepo> {
+    let filter = filter.trim().to_lowercase();
+    let mut sorted: Vec<RecentRepo> = repos
+        .iter()
+        .filter(|r| filter.is_empty() || r.path.to_lowercase().contains(&filter))
+        .cloned()
+        .collect();
+    match sort {
+        RecentRepoSort::Manual => {}
+        RecentRepoSort::Name => sorted.sort_by_cached_key(|r| {
+            let name = Path::new(&r.path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            (name, r.path.to_lowercase())
+        }),
+        RecentRepoSort::LastOpened => sorted.sort_by_key(|r| std::cmp::Reverse(r.last_opened)),
+    }
+    // 安定ソートなので各グループ内の並び順は保たれる
+    sorted.sort_by_key(|r| !r.pinned);
+    sorted
+}
+
+/// 指定したパスのリポジトリのピン留めを切り替える
+pub fn toggle_pin_repo(path: &str) -> Vec<RecentRepo> {
+    let mut repos = load_recent_repos();
+    if let Some(repo) = repos.iter_mut().find(|r| r.path == path) {
+        repo.pinned = !repo.pinned;
+        save_recent_repos(&repos);
+    }
+    repos
+}
+
+/// 存在しなくなったリポジトリを履歴から一括削除
+/// ネットワークドライブが一時的に見えないだけの場合もあるため自動では削除しない
+pub fn remove_missing_recent_repos() -> Vec<RecentRepo> {
+    let mut repos = load_recent_repos();
+    repos.retain(|r| Path::new(&r.path).exists());
+    save_recent_repos(&repos);
+    repos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_geometry_is_clamped_back_on_screen() {
+        let monitors = [(0, 0, 1920, 1080), (1920, 0, 1280, 1024)];
+        let geometry = |x, y, width, height| WindowGeometry {
+            x,
+            y,
+            width,
+            height,
+        };
+        // 2枚目のモニター上や、一部がはみ出しているだけならそのまま
+        let on_second = geometry(2000, 100, 1200, 800);
+        assert_eq!(clamp_window_geometry(on_second, &monitors), on_second);
+        let partly_off = geometry(-300, 50, 1280, 900);
+        assert_eq!(clamp_window_geometry(partly_off, &monitors), partly_off);
+        // 外されたモニター上の位置は最初のモニターの中央へ
+        assert_eq!(
+            clamp_window_geometry(geometry(3500, 100, 1280, 900), &monitors),
+            geometry(320, 90, 1280, 900)
+        );
+        // タイトルバーが上にはみ出している場合も戻し、大きすぎればモニターに合わせる
+        assert_eq!(
+            clamp_window_geometry(geometry(100, -40, 2560, 1440), &monitors),
+            geometry(0, 0, 1920, 1080)
+        );
+        // モニター情報が取れなければ補正しない
+        let far = geometry(9000, 9000, 800, 600);
+        assert_eq!(clamp_window_geometry(far, &[]), far);
+
+        let mut settings = serde_json::Map::new();
+        assert_eq!(WindowGeometry::from_settings(&settings), None);
+        settings.insert("window_geometry".into(), on_second.to_json());
+        assert_eq!(WindowGeometry::from_settings(&settings), Some(on_second));
+        settings.insert(
+            "window_geometry".into(),
+            serde_json::json!({ "x": 0, "y": 0, "width": 0, "height": 600 }),
+        );
+        assert_eq!(WindowGeometry::from_settings(&settings), None);
+    }
+
+    #[test]
+    fn recent_repos_sort_and_filter_keep_pinned_first() {
+        let repo = |path: &str, pinned: bool, last_opened: i64| RecentRepo {
+            path: path.into(),
+            pinned,
+            last_opened,
+        };
+        let repos = vec![
+            repo("/work/zeta", false, 100),
+            repo("/work/Alpha", false, 300),
+            repo("/home/beta", true, 50),
+            repo("/work/gamma", false, 200),
+        ];
+        let paths = |sort, filter| -> Vec<String> {
+            recent_repos_display_order(&repos, sort, filter)
+                .into_iter()
+                .map(|r| r.path)
+                .collect()
+        };
+        assert_eq!(
+            paths(RecentRepoSort::Manual, ""),
+            ["/home/beta", "/work/zeta", "/work/Alpha", "/work/gamma"]
+        );
+        assert_eq!(
+            paths(RecentRepoSort::Name, ""),
+            ["/home/beta", "/work/Alpha", "/work/gamma", "/work/zeta"]
+        );
+        assert_eq!(
+            paths(RecentRepoSort::LastOpened, ""),
+            ["/home/beta", "/work/Alpha", "/work/gamma", "/work/zeta"]
+        );
+        // 検索は大文字小文字を区別せずパス全体に対して行う
+        assert_eq!(
+            paths(RecentRepoSort::LastOpened, " WORK/"),
+            ["/work/Alpha", "/work/gamma", "/work/zeta"]
+        );
+        assert_eq!(paths(RecentRepoSort::Manual, "alp"), ["/work/Alpha"]);
+        assert_eq!(RecentRepoSort::from_index(7), RecentRepoSort::Manual);
+    }
+}
@@ -0,0 +1,313 @@
+//! 組み込みのlibgit2によるfetch / push / pull（システムにgitがない環境向け）
+
+use git2::build::CheckoutBuilder;
+use git2::{
+    BranchType, Cred, ErrorCode, FetchOptions, Oid, PushOptions, RemoteCallbacks, Repository,
+};
+
+use crate::git_client::{remote_command_error, GitError};
+
+/// リモート操作の実行方法（設定で切り替える）
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RemoteTransport {
+    /// システムのgitコマンド（認証はcredential helperやSSHの設定に任せる）
+    #[default]
+    Cli,
+    /// 組み込みのlibgit2（SSHエージェントとgitのcredential helper設定で認証）
+    Embedded,
+}
+
+/// 認証を試す回数の上限（libgit2は失敗しても同じコールバックを呼び続けるため）
+const MAX_CREDENTIAL_ATTEMPTS: usize = 3;
+
+/// 認証情報を返すコールバックを設定する
+/// SSHはエージェント、HTTPSはgitのcredential helperを使う（GUIからはパスワードを入力できない）
+fn credential_callbacks(config: &git2::Config) -> RemoteCallbacks<'_> {
+    let mut callbacks = RemoteCallbacks::new();
+    let mut attempts = 0;
+    callbacks.credentials(move |url, username, allowed| {
+        attempts += 1;
+        if attempts > MAX_CREDENTIAL_ATTEMPTS {
+            return Err(git2::Error::from_str("authentication failed"));
+        }
+        if allowed.is_ssh_key() {
+            Cred::ssh_key_from_agent(username.unwrap_or("git"))
+        } else if allowed.is_user_pass_plaintext() {
+            Cred::credential_helper(config, url, username)
+        } else if allowed.is_username() {
+            Cred::username(username.unwrap_or("git"))
+        } else {
+            Cred::default()
+        }
+    });
+    callbacks
+}
+
+/// libgit2のエラーをCLI版と同じ形のエラーにする（認証失敗は区別する）
+fn remote_error(action: &str, e: git2::Error) -> GitError {
+    if e.code() == ErrorCode::Auth {
+        GitError::AuthFailed(action.to_string())
+    } else {
+        remote_command_error(action, e.message())
+    }
+}
+
+/// 1つのリモートをfetch（進捗は受信オブジェクト数の割合で通知）
+fn fetch_remote(
+    repo: &Repository,
+    config: &git2::Config,
+    name: &str,
+    on_progress: &mut impl FnMut(&str, i32),
+) -> Result<(), GitError> {
+    let mut remote = repo.find_remote(name)?;
+    let mut callbacks = credential_callbacks(config);
+    let mut last_percent = -1;
+    callbacks.transfer_progress(|stats| {
+        if stats.total_objects() > 0 {
+            let percent = (stats.received_objects() * 100 / stats.total_objects()) as i32;
+            // 同じ値の連続通知は間引く
+            if percent != last_percent {
+                on_progress("Receiving objects", percent);
+                last_percent = percent;
+            }
+        }
+        true
+    });
+    let mut opts = FetchOptions::new();
+    opts.remote_callbacks(callbacks);
+    remote
+        .fetch(&[] as &[&str], Some(&mut opts), None)
+        .map_err(|e| remote_error("Fetch", e))
+}
+
+/// 全リモートをfetch（git fetch --all 相当）
+pub fn fetch_all(path: &str, mut on_progress: impl FnMut(&str, i32)) -> Result<(), GitError> {
+    let repo = Repository::open(path)?;
+    let config = repo.config()?;
+    for name in repo.remotes()?.iter().flatten() {
+        fetch_remote(&repo, &config, name, &mut on_progress)?;
+    }
+    Ok(())
+}
+
+/// ブランチをoriginにプッシュ（git push -u origin <branch> 相当）
+pub fn push_branch(path: &str, branch: &str) -> Result<(), GitError> {
+    let repo = Repository::open(path)?;
+    let config = repo.config()?;
+    let mut remote = repo.find_remote("origin")?;
+    let refspec = format!("refs/heads/{0}:refs/heads/{0}", branch);
+
+    // サーバー側で拒否された参照はpush自体は成功扱いでここに理由が届く
+    let mut rejection: Option<String> = None;
+    {
+        let mut callbacks = credential_callbacks(&config);
+        callbacks.push_update_reference(|_refname, status| {
+            if let Some(status) = status {
+                rejection = Some(status.to_string());
+            }
+            Ok(())
+        });
+        let mut opts = PushOptions::new();
+        opts.remote_callbacks(callbacks);
+        match remote.push(&[refspec.as_str()], Some(&mut opts)) {
+            Ok(()) => {}
+            Err(e) if e.code() == ErrorCode::NotFastForward => return Err(GitError::PushRejected),
+            Err(e) => return Err(remote_error("Push", e)),
+        }
+    }
+    if let Some(status) = rejection {
+        let lower = status.to_lowercase();
+        if lower.contains("non-fast-forward") || lower.contains("fetch first") {
+            return Err(GitError::PushRejected);
+        }
+        return Err(format!("Push failed: {}", status).into());
+    }
+
+    // upstreamがなければ設定する（リモート追跡ブランチはpushで作成済み）
+    let mut local = repo.find_branch(branch, BranchType::Local)?;
+    if local.upstream().is_err() {
+        local.set_upstream(Some(&format!("origin/{}", branch)))?;
+    }
+    Ok(())
+}
+
+/// 現在のブランチのupstreamからプル（git pull 相当）
+/// modeは "merge"（通常のpull）/ "ff-only" / "rebase"
+/// コンフリクトする場合は何も変更せずにエラーを返す（組み込み版ではコンフリクト中の状態を作らない）
+pub fn pull_repo(path: &str, mode: &str) -> Result<(), GitError> {
+    let repo = Repository::open(path)?;
+    let config = repo.config()?;
+    let head = repo.head()?;
+    if !head.is_branch() {
+        return Err("Cannot pull on a detached HEAD".into());
+    }
+    let head_refname = head.name().ok_or("Invalid branch name")?.to_string();
+    let branch_name = head.shorthand().unwrap_or_default().to_string();
+    let upstream_refname = repo
+        .branch_upstream_name(&head_refname)
+        .map_err(|_| GitError::from(format!("{} has no upstream branch", branch_name)))?;
+    let upstream_refname = upstream_refname.as_str().ok_or("Invalid upstream name")?;
+    let remote_name = repo.branch_remote_name(upstream_refname)?;
+    let remote_name = remote_name.as_str().ok_or("Invalid remote name")?;
+
+    fetch_remote(&repo, &config, remote_name, &mut |_, _| {})?;
+
+    let upstream_oid = repo.refname_to_id(upstream_refname)?;
+    let upstream = repo.find_annotated_commit(upstream_oid)?;
+    let (analysis, _) = repo.merge_analysis(&[&upstream])?;
+    if analysis.is_up_to_date() {
+        return Ok(());
+    }
+    if analysis.is_fast_forward() {
+        return fast_forward(&repo, &head_refname, upstream_oid);
+    }
+    match mode {
+        "ff-only" => Err("Cannot fast-forward; diverged. Pull with merge or rebase".into()),
+        "rebase" => rebase_onto(&repo, &upstream),
+        _ => {
+            let upstream_name = upstream_refname
+                .strip_prefix("refs/remotes/")
+                .unwrap_or(upstream_refname);
+            merge_upstream(&repo, upstream_oid, upstream_name)
+        }
+    }
+}
+
+/// 作業ツリーの変更を上書きしないようにチェックアウト（衝突すればエラー）
+fn checkout_safe(repo: &Repository, tree: &git2::Tree) -> Result<(), GitError> {
+    repo.checkout_tree(tree.as_object(), Some(CheckoutBuilder::new().safe()))
+        .map_err(|e| match e.code() {
+            ErrorCode::Conflict => "Local changes would be overwritten by pull".into(),
+            _ => GitError::from(e),
+        })
+}
+
+/// ブランチをupstreamまで進める
+fn fast_forward(repo: &Repository, refname: &str, target: Oid) -> Result<(), GitError> {
+    let commit = repo.find_commit(target)?;
+    checkout_safe(repo, &commit.tree()?)?;
+    repo.find_reference(refname)?
+        .set_target(target, "pull: Fast-forward")?;
+    Ok(())
+}
+
+/// upstreamをマージしたコミットを作る（コンフリクトする場合は何もしない）
+fn merge_upstream(
+    repo: &Repository,
+    upstream_oid: Oid,
+    upstream_name: &str,
+) -> Result<(), GitError> {
+    let local = repo.head()?.peel_to_commit()?;
+    let theirs = repo.find_commit(upstream_oid)?;
+    let mut index = repo.merge_commits(&local, &theirs, None)?;
+    if index.has_conflicts() {
+        return Err("Pull has conflicts; merge with the git CLI to resolve them".into());
+    }
+    let tree = repo.find_tree(index.write_tree_to(repo)?)?;
+    checkout_safe(repo, &tree)?;
+    let sig = repo.signature()?;
+    let message = format!("Merge remote-tracking branch '{}'", upstream_name);
+    repo.commit(
+        Some("HEAD"),
+        &sig,
+        &sig,
+        &message,
+        &tree,
+        &[&local, &theirs],
+    )?;
+    Ok(())
+}
+
+/// ローカルのコミットをupstreamの上に積み直す（コンフリクトしたら中止して元に戻す）
+fn rebase_onto(repo: &Repository, upstream: &git2::AnnotatedCommit) -> Result<(), GitError> {
+    let sig = repo.signature()?;
+    let mut rebase = repo.rebase(None, Some(upstream), None, None)?;
+    let result = (|| -> Result<(), GitError> {
+        while let Some(operation) = rebase.next() {
+            operation?;
+            if repo.index()?.has_conflicts() {
+                return Err("Pull has conflicts; rebase with the git CLI to resolve them".into());
+            }
+            match rebase.commit(None, &sig, None) {
+                // upstreamに同じ変更が既にあるコミットは飛ばす
+                Err(e) if e.code() == ErrorCode::Applied => {}
+                other => {
+                    other?;
+                }
+            }
+        }
+        Ok(())
+    })();
+    match result {
+        Ok(()) => {
+            rebase.finish(Some(&sig))?;
+            Ok(())
+        }
+        Err(e) => {
+            let _ = rebase.abort();
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{init_test_repo, TestDir};
+    use git2::Signature;
+    use std::fs;
+    use std::path::Path;
+
+    fn commit_file(repo: &Repository, name: &str, content: &str) -> Oid {
+        let workdir = repo.workdir().unwrap();
+        fs::write(workdir.join(name), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(name)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+        let parent = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, name, &tree, &[&parent])
+            .unwrap()
+    }
+
+    #[test]
+    fn embedded_push_and_pull_round_trip_through_local_remote() {
+        let (dir, repo, _) = init_test_repo("embedded-remote");
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+        let bare_dir = TestDir::new("embedded-remote-bare");
+        Repository::init_bare(&bare_dir).unwrap();
+        repo.remote("origin", bare_dir.to_str().unwrap()).unwrap();
+        let path = dir.to_str().unwrap();
+
+        // 初回のpushでupstreamが設定される
+        commit_file(&repo, "a.txt", "a\n");
+        push_branch(path, &branch).unwrap();
+        let local = repo.find_branch(&branch, BranchType::Local).unwrap();
+        assert!(local.upstream().is_ok());
+
+        // 別のクローンから進めたコミットをfast-forwardで取り込む
+        let other_dir = TestDir::new("embedded-remote-other");
+        let other = Repository::clone(bare_dir.to_str().unwrap(), &other_dir).unwrap();
+        let remote_head = commit_file(&other, "b.txt", "b\n");
+        push_branch(other_dir.to_str().unwrap(), &branch).unwrap();
+        pull_repo(path, "merge").unwrap();
+        assert_eq!(repo.head().unwrap().target(), Some(remote_head));
+        assert!(dir.join("b.txt").exists());
+
+        // 分岐するとpushは拒否され、ff-onlyのpullは失敗し、mergeのpullはマージコミットを作る
+        commit_file(&repo, "c.txt", "c\n");
+        commit_file(&other, "d.txt", "d\n");
+        push_branch(other_dir.to_str().unwrap(), &branch).unwrap();
+        assert!(matches!(
+            push_branch(path, &branch),
+            Err(GitError::PushRejected)
+        ));
+        assert!(pull_repo(path, "ff-only").is_err());
+        pull_repo(path, "merge").unwrap();
+        let merge = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(merge.parent_count(), 2);
+        assert!(dir.join("d.txt").exists() && dir.join("c.txt").exists());
+        push_branch(path, &branch).unwrap();
+    }
+}